@@ -87,4 +87,24 @@ pub async fn login(
             })
         }
     }
-}
\ No newline at end of file
+}
+
+/// Returns the role name (admin, manager, cashier) of an active user.
+pub async fn get_user_role(
+    pool: &sqlx::Pool<sqlx::Sqlite>,
+    user_id: &str,
+) -> Option<String> {
+    sqlx::query_as::<_, (String,)>(
+        "SELECT r.name FROM users u JOIN roles r ON u.role_id = r.id WHERE u.id = ? AND u.is_active = 1"
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+    .ok()
+    .flatten()
+    .map(|t| t.0)
+}
+
+pub async fn is_manager(pool: &sqlx::Pool<sqlx::Sqlite>, user_id: &str) -> bool {
+    matches!(get_user_role(pool, user_id).await.as_deref(), Some("admin") | Some("manager"))
+}
@@ -0,0 +1,405 @@
+use axum::{Json, extract::{Path, Query, State}};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use crate::api::AppState;
+use crate::api::auth::is_manager;
+use crate::api::labels::is_valid_gs1;
+use crate::api::units::resolve_quantity;
+use crate::models::ApiResponse;
+
+#[derive(Serialize)]
+pub struct ScaleLayout {
+    pub prefix: String,
+    pub plu_start: i32,
+    pub plu_length: i32,
+    pub value_type: String,
+    pub value_start: i32,
+    pub value_length: i32,
+    pub decimals: i32,
+    pub is_active: bool,
+}
+
+#[derive(Deserialize)]
+pub struct ScaleLayoutRequest {
+    /// Zero-based positions within the 13-digit code.
+    pub plu_start: i32,
+    pub plu_length: i32,
+    /// 'weight' (kg) or 'price'.
+    pub value_type: String,
+    pub value_start: i32,
+    pub value_length: i32,
+    pub decimals: i32,
+    pub is_active: Option<bool>,
+    pub user_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResolveQuery {
+    pub code: String,
+}
+
+/// A scanned code turned into a sale line: `quantity` of `unit` at `unit_price`.
+#[derive(Serialize)]
+pub struct BarcodeResolution {
+    pub product_id: String,
+    pub sku: String,
+    pub name: String,
+    /// 'scale', 'product' or 'pack'.
+    pub source: String,
+    pub unit: String,
+    pub quantity: f64,
+    pub unit_price: f64,
+    pub plu: Option<String>,
+    /// Weight in kg printed by the scale.
+    pub embedded_weight: Option<f64>,
+    /// Price printed by the scale; the line should be charged exactly this.
+    pub embedded_price: Option<f64>,
+}
+
+/// Resolves a scanned code, decoding variable-weight labels (prefixes 20-29) by their
+/// layout and PLU before falling back to product and pack barcodes.
+pub async fn resolve_barcode(
+    conn: &mut SqliteConnection,
+    code: &str,
+) -> Result<Option<BarcodeResolution>, String> {
+    let code = code.trim();
+
+    if code.len() == 13 && code.starts_with('2') && code.chars().all(|c| c.is_ascii_digit()) {
+        if let Some(resolution) = resolve_scale_barcode(conn, code).await? {
+            return Ok(Some(resolution));
+        }
+    }
+
+    let product: Option<(String, String, String, String, f64)> = sqlx::query_as(
+        "SELECT id, sku, name, unit, price FROM products WHERE barcode = ? AND is_active = 1"
+    )
+    .bind(code)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    if let Some((product_id, sku, name, unit, price)) = product {
+        return Ok(Some(BarcodeResolution {
+            product_id,
+            sku,
+            name,
+            source: "product".to_string(),
+            unit,
+            quantity: 1.0,
+            unit_price: price,
+            plu: None,
+            embedded_weight: None,
+            embedded_price: None,
+        }));
+    }
+
+    let pack: Option<(String, String, String, String, f64, f64)> = sqlx::query_as(
+        r#"
+        SELECT p.id, p.sku, p.name, pu.unit_code, pu.factor, p.price
+        FROM product_units pu
+        JOIN products p ON p.id = pu.product_id
+        WHERE pu.barcode = ? AND p.is_active = 1
+        "#
+    )
+    .bind(code)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(pack.map(|(product_id, sku, name, unit, factor, price)| BarcodeResolution {
+        product_id,
+        sku,
+        name,
+        source: "pack".to_string(),
+        unit,
+        quantity: 1.0,
+        unit_price: price * factor,
+        plu: None,
+        embedded_weight: None,
+        embedded_price: None,
+    }))
+}
+
+/// PLU and embedded value (kg or price) of a scale label, given the layout's
+/// (start, length) digit positions; `None` when they fall outside the code.
+fn decode_scale_label(code: &str, plu: (i32, i32), value: (i32, i32), decimals: i32) -> Option<(i64, f64)> {
+    let digits = |(start, length): (i32, i32)| {
+        let start = usize::try_from(start).ok()?;
+        let end = start.checked_add(usize::try_from(length).ok()?)?;
+        code.get(start..end).filter(|digits| !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()))
+    };
+    let plu = digits(plu)?.parse::<i64>().ok()?;
+    let value = digits(value)?.parse::<f64>().ok()? / 10f64.powi(decimals);
+    Some((plu, value))
+}
+
+/// Decodes a scale label; `None` when no active layout or PLU matches.
+async fn resolve_scale_barcode(
+    conn: &mut SqliteConnection,
+    code: &str,
+) -> Result<Option<BarcodeResolution>, String> {
+    let layout: Option<(i32, i32, String, i32, i32, i32)> = sqlx::query_as(
+        r#"
+        SELECT plu_start, plu_length, value_type, value_start, value_length, decimals
+        FROM scale_barcode_layouts
+        WHERE prefix = ? AND is_active = 1
+        "#
+    )
+    .bind(&code[..2])
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let Some((plu_start, plu_length, value_type, value_start, value_length, decimals)) = layout else {
+        return Ok(None);
+    };
+    if !is_valid_gs1(code) {
+        return Err("Dígito verificador inválido en la etiqueta de báscula".to_string());
+    }
+
+    let Some((plu, value)) = decode_scale_label(code, (plu_start, plu_length), (value_start, value_length), decimals) else {
+        return Err("La etiqueta de báscula no coincide con su formato".to_string());
+    };
+
+    // PLUs are matched numerically so '00123' on the label finds '123' in the catalog
+    let product: Option<(String, String, String, String, f64, String)> = sqlx::query_as(
+        r#"
+        SELECT id, sku, name, unit, price, plu FROM products
+        WHERE plu IS NOT NULL AND plu <> '' AND CAST(plu AS INTEGER) = ? AND is_active = 1
+        "#
+    )
+    .bind(plu)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let Some((product_id, sku, name, unit, price, plu)) = product else {
+        return Ok(None);
+    };
+
+    if value_type == "weight" {
+        // Weights are in kg; the product may be priced per g, per lb, etc.
+        let per_kg = resolve_quantity(conn, &product_id, Some("kg"), value)
+            .await
+            .map_err(|_| format!("{} no se vende por peso", name))?;
+        Ok(Some(BarcodeResolution {
+            product_id,
+            sku,
+            name,
+            source: "scale".to_string(),
+            unit: "kg".to_string(),
+            quantity: value,
+            unit_price: price * per_kg.factor,
+            plu: Some(plu),
+            embedded_weight: Some(value),
+            embedded_price: None,
+        }))
+    } else {
+        if price <= 0.0 {
+            return Err(format!("{} no tiene precio para calcular la cantidad", name));
+        }
+        Ok(Some(BarcodeResolution {
+            product_id,
+            sku,
+            name,
+            source: "scale".to_string(),
+            unit,
+            quantity: (value / price * 1000.0).round() / 1000.0,
+            unit_price: price,
+            plu: Some(plu),
+            embedded_weight: None,
+            embedded_price: Some(value),
+        }))
+    }
+}
+
+pub async fn resolve_barcode_handler(
+    State(state): State<AppState>,
+    Query(params): Query<ResolveQuery>,
+) -> Json<ApiResponse<BarcodeResolution>> {
+    let db = state.db.lock().await;
+
+    let result = match db.pool().acquire().await {
+        Ok(mut conn) => resolve_barcode(&mut conn, &params.code).await,
+        Err(e) => Err(e.to_string()),
+    };
+
+    match result {
+        Ok(Some(resolution)) => Json(ApiResponse {
+            success: true,
+            data: Some(resolution),
+            message: None,
+        }),
+        Ok(None) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Código {} no encontrado", params.code.trim())),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        }),
+    }
+}
+
+type ScaleLayoutRow = (String, i32, i32, String, i32, i32, i32, i32);
+
+pub async fn list_scale_layouts(
+    State(state): State<AppState>,
+) -> Json<ApiResponse<Vec<ScaleLayout>>> {
+    let db = state.db.lock().await;
+
+    let result: Result<Vec<ScaleLayoutRow>, sqlx::Error> = sqlx::query_as(
+        r#"
+        SELECT prefix, plu_start, plu_length, value_type, value_start, value_length, decimals, is_active
+        FROM scale_barcode_layouts
+        ORDER BY prefix
+        "#
+    )
+    .fetch_all(db.pool())
+    .await;
+
+    match result {
+        Ok(rows) => Json(ApiResponse {
+            success: true,
+            data: Some(
+                rows.into_iter()
+                    .map(|(prefix, plu_start, plu_length, value_type, value_start, value_length, decimals, is_active)| ScaleLayout {
+                        prefix,
+                        plu_start,
+                        plu_length,
+                        value_type,
+                        value_start,
+                        value_length,
+                        decimals,
+                        is_active: is_active == 1,
+                    })
+                    .collect(),
+            ),
+            message: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn update_scale_layout(
+    State(state): State<AppState>,
+    Path(prefix): Path<String>,
+    Json(payload): Json<ScaleLayoutRequest>,
+) -> Json<ApiResponse<ScaleLayout>> {
+    let db = state.db.lock().await;
+
+    if !is_manager(db.pool(), &payload.user_id).await {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Solo un gerente puede cambiar la configuración".to_string()),
+        });
+    }
+
+    // Both fields must sit between the prefix and the check digit without overlapping
+    let within = |start: i32, length: i32| start >= 2 && length > 0 && start + length <= 12;
+    let overlaps = payload.plu_start < payload.value_start + payload.value_length
+        && payload.value_start < payload.plu_start + payload.plu_length;
+    let error = if !(prefix.len() == 2 && prefix.starts_with('2') && prefix.chars().all(|c| c.is_ascii_digit())) {
+        Some("El prefijo debe estar entre 20 y 29")
+    } else if payload.value_type != "weight" && payload.value_type != "price" {
+        Some("Tipo de valor inválido (weight o price)")
+    } else if !within(payload.plu_start, payload.plu_length) || !within(payload.value_start, payload.value_length) || overlaps {
+        Some("Las posiciones del PLU y del valor no son válidas")
+    } else if !(0..=4).contains(&payload.decimals) {
+        Some("Decimales inválidos")
+    } else {
+        None
+    };
+    if let Some(message) = error {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(message.to_string()),
+        });
+    }
+
+    let is_active = payload.is_active.unwrap_or(true);
+    let result = sqlx::query(
+        r#"
+        INSERT INTO scale_barcode_layouts
+            (prefix, plu_start, plu_length, value_type, value_start, value_length, decimals, is_active, updated_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, datetime('now'))
+        ON CONFLICT(prefix) DO UPDATE SET
+            plu_start = excluded.plu_start, plu_length = excluded.plu_length,
+            value_type = excluded.value_type, value_start = excluded.value_start,
+            value_length = excluded.value_length, decimals = excluded.decimals,
+            is_active = excluded.is_active, updated_at = excluded.updated_at
+        "#
+    )
+    .bind(&prefix)
+    .bind(payload.plu_start)
+    .bind(payload.plu_length)
+    .bind(&payload.value_type)
+    .bind(payload.value_start)
+    .bind(payload.value_length)
+    .bind(payload.decimals)
+    .bind(if is_active { 1 } else { 0 })
+    .execute(db.pool())
+    .await;
+
+    match result {
+        Ok(_) => Json(ApiResponse {
+            success: true,
+            data: Some(ScaleLayout {
+                prefix,
+                plu_start: payload.plu_start,
+                plu_length: payload.plu_length,
+                value_type: payload.value_type,
+                value_start: payload.value_start,
+                value_length: payload.value_length,
+                decimals: payload.decimals,
+                is_active,
+            }),
+            message: Some("Formato de báscula guardado".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::labels::gs1_check_digit;
+
+    fn with_check_digit(payload: &str) -> String {
+        format!("{}{}", payload, gs1_check_digit(payload).unwrap())
+    }
+
+    #[test]
+    fn decodes_weight_label() {
+        // Prefix 20, PLU 01234, weight 01.250 kg
+        let code = with_check_digit("200123401250");
+        assert_eq!(decode_scale_label(&code, (2, 5), (7, 5), 3), Some((1234, 1.25)));
+    }
+
+    #[test]
+    fn decodes_price_label() {
+        // Prefix 22, PLU 0042, price 0089.90
+        let code = with_check_digit("220042008990");
+        assert_eq!(decode_scale_label(&code, (2, 4), (6, 6), 2), Some((42, 89.9)));
+    }
+
+    #[test]
+    fn rejects_layouts_outside_the_code() {
+        let code = with_check_digit("200123401250");
+        assert_eq!(decode_scale_label(&code, (2, 5), (10, 5), 3), None);
+        assert_eq!(decode_scale_label(&code, (-1, 5), (7, 5), 3), None);
+        assert_eq!(decode_scale_label(&code, (2, 0), (7, 5), 3), None);
+    }
+}
@@ -0,0 +1,217 @@
+use axum::{Json, extract::{Path, State}};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use crate::api::AppState;
+use crate::api::auth::is_manager;
+use crate::api::cash_register::expected_cash;
+use crate::models::ApiResponse;
+
+/// 'pay_in' adds cash to the drawer; 'pay_out' (supplier payments, expenses) and
+/// 'safe_drop' (excess cash sent to the safe) take it out.
+const MOVEMENT_TYPES: [&str; 3] = ["pay_in", "pay_out", "safe_drop"];
+
+#[derive(Serialize)]
+pub struct CashMovement {
+    pub id: String,
+    pub shift_id: String,
+    pub movement_type: String,
+    pub amount: f64,
+    pub reason: String,
+    pub user_id: String,
+    pub user_name: String,
+    pub authorized_by: String,
+    pub authorized_by_name: String,
+    pub created_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct CreateCashMovementRequest {
+    pub movement_type: String,
+    pub amount: f64,
+    pub reason: String,
+    pub user_id: String,
+    /// Manager who approves the movement; may be the same user.
+    pub authorized_by: String,
+}
+
+/// Cash that entered and left the drawer outside of sales during a shift.
+#[derive(Serialize)]
+pub struct MovementTotals {
+    pub pay_ins: f64,
+    pub pay_outs: f64,
+    pub safe_drops: f64,
+}
+
+impl MovementTotals {
+    /// Net effect on the drawer.
+    pub fn net(&self) -> f64 {
+        self.pay_ins - self.pay_outs - self.safe_drops
+    }
+}
+
+pub async fn movement_totals(conn: &mut SqliteConnection, shift_id: &str) -> Result<MovementTotals, sqlx::Error> {
+    let (pay_ins, pay_outs, safe_drops): (f64, f64, f64) = sqlx::query_as(
+        r#"
+        SELECT COALESCE(SUM(CASE WHEN movement_type = 'pay_in' THEN amount END), 0.0),
+               COALESCE(SUM(CASE WHEN movement_type = 'pay_out' THEN amount END), 0.0),
+               COALESCE(SUM(CASE WHEN movement_type = 'safe_drop' THEN amount END), 0.0)
+        FROM cash_movements
+        WHERE shift_id = ?
+        "#
+    )
+    .bind(shift_id)
+    .fetch_one(conn)
+    .await?;
+
+    Ok(MovementTotals {
+        pay_ins,
+        pay_outs,
+        safe_drops,
+    })
+}
+
+type CashMovementRow = (String, String, String, f64, String, String, String, String, String, String);
+
+async fn fetch_movements(conn: &mut SqliteConnection, shift_id: &str) -> Result<Vec<CashMovement>, sqlx::Error> {
+    let rows: Vec<CashMovementRow> = sqlx::query_as(
+        r#"
+        SELECT m.id, m.shift_id, m.movement_type, m.amount, m.reason, m.user_id, u.full_name,
+               m.authorized_by, a.full_name, m.created_at
+        FROM cash_movements m
+        JOIN users u ON u.id = m.user_id
+        JOIN users a ON a.id = m.authorized_by
+        WHERE m.shift_id = ?
+        ORDER BY m.created_at
+        "#
+    )
+    .bind(shift_id)
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(id, shift_id, movement_type, amount, reason, user_id, user_name, authorized_by, authorized_by_name, created_at)| CashMovement {
+            id,
+            shift_id,
+            movement_type,
+            amount,
+            reason,
+            user_id,
+            user_name,
+            authorized_by,
+            authorized_by_name,
+            created_at,
+        })
+        .collect())
+}
+
+pub async fn list_cash_movements(
+    State(state): State<AppState>,
+    Path(shift_id): Path<String>,
+) -> Json<ApiResponse<Vec<CashMovement>>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        let mut conn = db.pool().acquire().await?;
+        fetch_movements(&mut conn, &shift_id).await
+    }
+    .await;
+
+    match result {
+        Ok(movements) => Json(ApiResponse {
+            success: true,
+            data: Some(movements),
+            message: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn create_cash_movement(
+    State(state): State<AppState>,
+    Path(shift_id): Path<String>,
+    Json(payload): Json<CreateCashMovementRequest>,
+) -> Json<ApiResponse<CashMovement>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        if !MOVEMENT_TYPES.contains(&payload.movement_type.as_str()) {
+            return Err(format!("Tipo de movimiento inválido: {}", payload.movement_type));
+        }
+        if payload.amount <= 0.0 {
+            return Err("El monto debe ser mayor a cero".to_string());
+        }
+        if payload.reason.trim().is_empty() {
+            return Err("El motivo es obligatorio".to_string());
+        }
+        if !is_manager(db.pool(), &payload.authorized_by).await {
+            return Err("El movimiento debe autorizarlo un gerente".to_string());
+        }
+
+        let mut tx = db.pool().begin().await.map_err(|e| e.to_string())?;
+        let shift: Option<(f64,)> = sqlx::query_as("SELECT opening_balance FROM shifts WHERE id = ? AND status = 'open'")
+            .bind(&shift_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        let Some((opening_balance,)) = shift else {
+            return Err("Turno no encontrado o ya cerrado".to_string());
+        };
+
+        // Cash can't leave a drawer that doesn't hold it
+        if payload.movement_type != "pay_in" {
+            let in_drawer = expected_cash(&mut tx, &shift_id, opening_balance)
+                .await
+                .map_err(|e| e.to_string())?
+                .expected;
+            if payload.amount > in_drawer + 0.005 {
+                return Err(format!("No hay suficiente efectivo en caja: hay {:.2}", in_drawer));
+            }
+        }
+
+        let movement_id = uuid::Uuid::new_v4().to_string();
+        sqlx::query(
+            r#"
+            INSERT INTO cash_movements (id, shift_id, movement_type, amount, reason, user_id, authorized_by, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, datetime('now'))
+            "#
+        )
+        .bind(&movement_id)
+        .bind(&shift_id)
+        .bind(&payload.movement_type)
+        .bind(payload.amount)
+        .bind(payload.reason.trim())
+        .bind(&payload.user_id)
+        .bind(&payload.authorized_by)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let movement = fetch_movements(&mut tx, &shift_id)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .find(|movement| movement.id == movement_id)
+            .ok_or_else(|| "Movimiento no encontrado".to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok(movement)
+    }
+    .await;
+
+    match result {
+        Ok(movement) => Json(ApiResponse {
+            success: true,
+            data: Some(movement),
+            message: Some("Movimiento de efectivo registrado".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        }),
+    }
+}
@@ -1,6 +1,11 @@
-use axum::{Json, extract::{State, Path}};
+use axum::{Json, extract::{State, Path, Query}};
 use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite, SqliteConnection};
 use crate::api::AppState;
+use crate::api::auth::{get_user_role, is_manager};
+use crate::api::cash_movements::movement_totals;
+use crate::api::denominations::{fetch_count, save_count, store_currency, validate_count, CashCount, DenominationCount};
+use crate::api::settings::get_setting;
 use crate::models::ApiResponse;
 
 #[derive(Serialize)]
@@ -8,7 +13,19 @@ pub struct CashRegister {
     pub id: String,
     pub name: String,
     pub location: Option<String>,
+    pub location_id: Option<String>,
     pub is_active: bool,
+    /// Cashiers close without seeing the expected amount.
+    pub blind_close: bool,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateRegisterRequest {
+    pub name: Option<String>,
+    /// Stock location that this register's sales draw from.
+    pub location_id: Option<String>,
+    pub is_active: Option<bool>,
+    pub blind_close: Option<bool>,
 }
 
 #[derive(Serialize)]
@@ -25,6 +42,7 @@ pub struct Shift {
     pub expected_balance: Option<f64>,
     pub difference: Option<f64>,
     pub notes: Option<String>,
+    /// 'open', 'pending_review' (discrepancy awaiting a manager) or 'closed'.
     pub status: String,
 }
 
@@ -33,32 +51,190 @@ pub struct OpenShiftRequest {
     pub user_id: String,
     pub register_id: String,
     pub opening_balance: f64,
+    /// Breakdown of the opening balance by bill and coin.
+    pub denominations: Option<Vec<DenominationCount>>,
+    /// Currency of the breakdown; defaults to the store currency.
+    pub currency: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub struct CloseShiftRequest {
+    /// Cash counted in the drawer.
     pub closing_balance: f64,
     pub notes: Option<String>,
+    /// Who closes; defaults to the shift's cashier.
+    pub user_id: Option<String>,
+    /// Batch totals from the card terminal and other non-cash tenders, by payment method.
+    pub tender_totals: Option<Vec<TenderTotal>>,
+    /// Breakdown of the closing balance by bill and coin.
+    pub denominations: Option<Vec<DenominationCount>>,
+    pub currency: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TenderTotal {
+    pub method: String,
+    pub amount: f64,
+}
+
+/// How the expected drawer cash comes about.
+#[derive(Serialize)]
+pub struct ExpectedCash {
+    pub opening_balance: f64,
+    /// Cash handed over by customers, before change.
+    pub cash_payments: f64,
+    pub cash_refunds: f64,
+    pub change_given: f64,
+    pub pay_ins: f64,
+    pub pay_outs: f64,
+    pub safe_drops: f64,
+    pub expected: f64,
+}
+
+/// A tender's takings against what the cashier declared: the drawer count for cash,
+/// the terminal batch total for the rest.
+#[derive(Serialize)]
+pub struct TenderReconciliation {
+    pub method: String,
+    pub expected: Option<f64>,
+    pub declared: Option<f64>,
+    pub difference: Option<f64>,
+}
+
+#[derive(Deserialize)]
+pub struct ShiftViewQuery {
+    /// Who is looking; blind-closed figures are only shown to managers.
+    pub user_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ReviewShiftRequest {
+    pub user_id: String,
+    pub notes: String,
 }
 
+#[derive(Serialize)]
+pub struct ShiftReview {
+    pub reviewed_by: String,
+    pub reviewer_name: String,
+    pub reviewed_at: String,
+    pub notes: String,
+}
+
+/// On a blind-closed shift, sales and expected figures are left out for anyone but a manager.
 #[derive(Serialize)]
 pub struct ShiftSummary {
     pub shift: Shift,
-    pub total_sales: f64,
+    pub total_sales: Option<f64>,
     pub total_transactions: i32,
-    pub cash_sales: f64,
-    pub card_sales: f64,
-    pub other_sales: f64,
+    pub cash_sales: Option<f64>,
+    pub card_sales: Option<f64>,
+    pub other_sales: Option<f64>,
+    pub cash: Option<ExpectedCash>,
+    pub tenders: Vec<TenderReconciliation>,
+    pub opening_count: Option<CashCount>,
+    pub closing_count: Option<CashCount>,
+    pub blind_close: bool,
+    pub review: Option<ShiftReview>,
 }
 
+impl ShiftSummary {
+    fn hide_expected(&mut self) {
+        self.shift.expected_balance = None;
+        self.shift.difference = None;
+        self.total_sales = None;
+        self.cash_sales = None;
+        self.card_sales = None;
+        self.other_sales = None;
+        self.cash = None;
+        for tender in &mut self.tenders {
+            tender.expected = None;
+            tender.difference = None;
+        }
+    }
+}
+
+/// Validates and stores a drawer count when one was given.
+async fn record_count(
+    conn: &mut SqliteConnection,
+    shift_id: &str,
+    stage: &str,
+    currency: Option<&str>,
+    counts: Option<&[DenominationCount]>,
+    declared: f64,
+) -> Result<(), String> {
+    let Some(counts) = counts else {
+        return Ok(());
+    };
+    let currency = match currency {
+        Some(currency) => currency.to_uppercase(),
+        None => store_currency(conn).await.map_err(|e| e.to_string())?,
+    };
+    validate_count(conn, &currency, counts, declared).await?;
+    save_count(conn, shift_id, stage, &currency, counts).await.map_err(|e| e.to_string())
+}
+
+/// Cash the drawer should hold: opening + cash payments − cash refunds − change given
+/// + pay-ins − pay-outs − safe drops. Refunds are negative cash payments.
+pub async fn expected_cash(
+    conn: &mut SqliteConnection,
+    shift_id: &str,
+    opening_balance: f64,
+) -> Result<ExpectedCash, sqlx::Error> {
+    let (cash_payments, cash_refunds, change_given): (f64, f64, f64) = sqlx::query_as(
+        r#"
+        SELECT COALESCE(SUM(CASE WHEN p.amount > 0 THEN COALESCE(p.tendered, p.amount) END), 0.0),
+               COALESCE(SUM(CASE WHEN p.amount < 0 THEN -p.amount END), 0.0),
+               COALESCE(SUM(p.change_given), 0.0)
+        FROM payments p
+        JOIN sales s ON s.id = p.sale_id
+        WHERE s.shift_id = ? AND p.method = 'cash' AND p.status = 'completed'
+        "#
+    )
+    .bind(shift_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    let movements = movement_totals(conn, shift_id).await?;
+
+    Ok(ExpectedCash {
+        opening_balance,
+        cash_payments,
+        cash_refunds,
+        change_given,
+        pay_ins: movements.pay_ins,
+        pay_outs: movements.pay_outs,
+        safe_drops: movements.safe_drops,
+        expected: opening_balance + cash_payments - cash_refunds - change_given + movements.net(),
+    })
+}
+
+/// Non-cash takings per payment method.
+async fn tender_takings(conn: &mut SqliteConnection, shift_id: &str) -> Result<Vec<(String, f64)>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        SELECT p.method, COALESCE(SUM(p.amount), 0.0)
+        FROM payments p
+        JOIN sales s ON s.id = p.sale_id
+        WHERE s.shift_id = ? AND p.method <> 'cash' AND p.status = 'completed'
+        GROUP BY p.method
+        ORDER BY p.method
+        "#
+    )
+    .bind(shift_id)
+    .fetch_all(conn)
+    .await
+}
+
+type RegisterRow = (String, String, String, String, i32, i32);
+
 pub async fn list_registers(
     State(state): State<AppState>,
 ) -> Json<ApiResponse<Vec<CashRegister>>> {
     let db = state.db.lock().await;
     
-    let result: Result<Vec<(String, String, String, i32)>, sqlx::Error> = 
+    let result: Result<Vec<RegisterRow>, sqlx::Error> = 
         sqlx::query_as(
-            "SELECT id, name, COALESCE(location, ''), is_active FROM cash_registers ORDER BY name"
+            "SELECT id, name, COALESCE(location, ''), COALESCE(location_id, ''), is_active, blind_close FROM cash_registers ORDER BY name"
         )
         .fetch_all(db.pool())
         .await;
@@ -67,11 +243,13 @@ pub async fn list_registers(
         Ok(rows) => {
             let registers: Vec<CashRegister> = rows
                 .into_iter()
-                .map(|(id, name, location, is_active)| CashRegister {
+                .map(|(id, name, location, location_id, is_active, blind_close)| CashRegister {
                     id,
                     name,
                     location: if location.is_empty() { None } else { Some(location) },
+                    location_id: if location_id.is_empty() { None } else { Some(location_id) },
                     is_active: is_active == 1,
+                    blind_close: blind_close == 1,
                 })
                 .collect();
 
@@ -89,6 +267,65 @@ pub async fn list_registers(
     }
 }
 
+pub async fn update_register(
+    State(state): State<AppState>,
+    Path(register_id): Path<String>,
+    Json(payload): Json<UpdateRegisterRequest>,
+) -> Json<ApiResponse<String>> {
+    let db = state.db.lock().await;
+
+    let mut updates = Vec::new();
+    let mut values: Vec<String> = Vec::new();
+
+    if let Some(name) = &payload.name {
+        updates.push("name = ?");
+        values.push(name.clone());
+    }
+    if let Some(location_id) = &payload.location_id {
+        // Keep the descriptive location text in sync with the linked location
+        updates.push("location_id = ?, location = (SELECT name FROM locations WHERE id = ?)");
+        values.push(location_id.clone());
+        values.push(location_id.clone());
+    }
+    if let Some(is_active) = payload.is_active {
+        updates.push("is_active = ?");
+        values.push(if is_active { "1".to_string() } else { "0".to_string() });
+    }
+    if let Some(blind_close) = payload.blind_close {
+        updates.push("blind_close = ?");
+        values.push(if blind_close { "1".to_string() } else { "0".to_string() });
+    }
+
+    if updates.is_empty() {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("No hay cambios para actualizar".to_string()),
+        });
+    }
+
+    let query_str = format!("UPDATE cash_registers SET {} WHERE id = ?", updates.join(", "));
+    values.push(register_id);
+
+    let mut query_builder = sqlx::query(&query_str);
+    for value in &values {
+        query_builder = query_builder.bind(value);
+    }
+
+    match query_builder.execute(db.pool()).await {
+        Ok(_) => Json(ApiResponse {
+            success: true,
+            data: Some("Caja actualizada".to_string()),
+            message: Some("Caja actualizada exitosamente".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
 pub async fn open_shift(
     State(state): State<AppState>,
     Json(payload): Json<OpenShiftRequest>,
@@ -113,17 +350,33 @@ pub async fn open_shift(
 
     let shift_id = uuid::Uuid::new_v4().to_string();
     
-    let result = sqlx::query(
-        r#"
-        INSERT INTO shifts (id, user_id, register_id, opened_at, opening_balance, status, created_at)
-        VALUES (?, ?, ?, datetime('now'), ?, 'open', datetime('now'))
-        "#
-    )
-    .bind(&shift_id)
-    .bind(&payload.user_id)
-    .bind(&payload.register_id)
-    .bind(payload.opening_balance)
-    .execute(db.pool())
+    let result = async {
+        let mut tx = db.pool().begin().await.map_err(|e| e.to_string())?;
+        sqlx::query(
+            r#"
+            INSERT INTO shifts (id, user_id, register_id, opened_at, opening_balance, status, created_at)
+            VALUES (?, ?, ?, datetime('now'), ?, 'open', datetime('now'))
+            "#
+        )
+        .bind(&shift_id)
+        .bind(&payload.user_id)
+        .bind(&payload.register_id)
+        .bind(payload.opening_balance)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        record_count(
+            &mut tx,
+            &shift_id,
+            "open",
+            payload.currency.as_deref(),
+            payload.denominations.as_deref(),
+            payload.opening_balance,
+        )
+        .await?;
+        tx.commit().await.map_err(|e| e.to_string())
+    }
     .await;
 
     match result {
@@ -182,6 +435,126 @@ pub async fn open_shift(
     }
 }
 
+/// Whether a shift on this register, closed by this user, is closed blind.
+async fn closes_blind(conn: &mut SqliteConnection, register_id: &str, role: Option<&str>) -> Result<bool, sqlx::Error> {
+    let (register_blind,): (i32,) = sqlx::query_as("SELECT blind_close FROM cash_registers WHERE id = ?")
+        .bind(register_id)
+        .fetch_one(&mut *conn)
+        .await?;
+    if register_blind == 1 {
+        return Ok(true);
+    }
+    let roles = get_setting(conn, "blind_close_roles").await?.unwrap_or_default();
+    Ok(role.is_some_and(|role| roles.split(',').any(|blind_role| blind_role.trim() == role)))
+}
+
+type ShiftSummaryRow = (String, String, String, String, String, String, String, f64, Option<f64>, Option<f64>, Option<f64>, String, String, i32);
+
+/// Everything known about a shift, read back from what was stored at close.
+async fn shift_summary(conn: &mut SqliteConnection, shift_id: &str) -> Result<ShiftSummary, sqlx::Error> {
+    let (id, user_id, user_name, register_id, register_name, opened_at, closed_at, opening_balance, closing_balance, expected_balance, difference, notes, status, blind_close): ShiftSummaryRow =
+        sqlx::query_as(
+            r#"
+            SELECT s.id, s.user_id, u.full_name, s.register_id, r.name, s.opened_at, COALESCE(s.closed_at, ''),
+                   s.opening_balance, s.closing_balance, s.expected_balance, s.difference,
+                   COALESCE(s.notes, ''), s.status, s.blind_close
+            FROM shifts s
+            JOIN users u ON s.user_id = u.id
+            JOIN cash_registers r ON s.register_id = r.id
+            WHERE s.id = ?
+            "#
+        )
+        .bind(shift_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    let (total_sales, total_transactions, cash_sales, card_sales): (f64, i32, f64, f64) = sqlx::query_as(
+        r#"
+        SELECT COALESCE(SUM(total), 0.0),
+               COUNT(*),
+               (SELECT COALESCE(SUM(amount), 0.0) FROM payments WHERE method = 'cash' AND status = 'completed' AND sale_id IN (SELECT id FROM sales WHERE shift_id = ?1)),
+               (SELECT COALESCE(SUM(amount), 0.0) FROM payments WHERE method IN ('card', 'debit', 'credit') AND status = 'completed' AND sale_id IN (SELECT id FROM sales WHERE shift_id = ?1))
+        FROM sales
+        WHERE shift_id = ?1
+        "#
+    )
+    .bind(shift_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let cash = expected_cash(conn, shift_id, opening_balance).await?;
+
+    let tender_rows: Vec<(String, f64, Option<f64>, Option<f64>)> = sqlx::query_as(
+        r#"
+        SELECT method, expected_amount, declared_amount, difference
+        FROM shift_tenders
+        WHERE shift_id = ?
+        ORDER BY method <> 'cash', method
+        "#
+    )
+    .bind(shift_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    let tenders = tender_rows
+        .into_iter()
+        .map(|(method, expected, declared, difference)| TenderReconciliation {
+            method,
+            expected: Some(expected),
+            declared,
+            difference,
+        })
+        .collect();
+
+    let opening_count = fetch_count(conn, shift_id, "open").await?;
+    let closing_count = fetch_count(conn, shift_id, "close").await?;
+
+    let review: Option<(String, String, String, String)> = sqlx::query_as(
+        r#"
+        SELECT s.reviewed_by, u.full_name, s.reviewed_at, COALESCE(s.review_notes, '')
+        FROM shifts s
+        JOIN users u ON u.id = s.reviewed_by
+        WHERE s.id = ?
+        "#
+    )
+    .bind(shift_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(ShiftSummary {
+        shift: Shift {
+            id,
+            user_id,
+            user_name,
+            register_id,
+            register_name,
+            opened_at,
+            closed_at: if closed_at.is_empty() { None } else { Some(closed_at) },
+            opening_balance,
+            closing_balance,
+            expected_balance,
+            difference,
+            notes: if notes.is_empty() { None } else { Some(notes) },
+            status,
+        },
+        total_sales: Some(total_sales),
+        total_transactions,
+        cash_sales: Some(cash_sales),
+        card_sales: Some(card_sales),
+        other_sales: Some(total_sales - cash_sales - card_sales),
+        cash: Some(cash),
+        tenders,
+        opening_count,
+        closing_count,
+        blind_close: blind_close == 1,
+        review: review.map(|(reviewed_by, reviewer_name, reviewed_at, notes)| ShiftReview {
+            reviewed_by,
+            reviewer_name,
+            reviewed_at,
+            notes,
+        }),
+    })
+}
+
 pub async fn close_shift(
     State(state): State<AppState>,
     Path(shift_id): Path<String>,
@@ -190,15 +563,15 @@ pub async fn close_shift(
     let db = state.db.lock().await;
     
     // Get shift data
-    let shift_data: Result<(f64,), sqlx::Error> = sqlx::query_as(
-        "SELECT opening_balance FROM shifts WHERE id = ? AND status = 'open'"
+    let shift_data: Result<(f64, String, String), sqlx::Error> = sqlx::query_as(
+        "SELECT opening_balance, user_id, register_id FROM shifts WHERE id = ? AND status = 'open'"
     )
     .bind(&shift_id)
     .fetch_one(db.pool())
     .await;
 
-    let opening_balance = match shift_data {
-        Ok((balance,)) => balance,
+    let (opening_balance, owner_id, register_id) = match shift_data {
+        Ok(shift) => shift,
         Err(_) => {
             return Json(ApiResponse {
                 success: false,
@@ -207,139 +580,226 @@ pub async fn close_shift(
             });
         }
     };
+    let closed_by = payload.user_id.clone().unwrap_or(owner_id);
+    let closer_role = get_user_role(db.pool(), &closed_by).await;
+    let closer_is_manager = is_manager(db.pool(), &closed_by).await;
+
+    // Cash is reconciled against the drawer count, every other tender against its batch total
+    let result = async {
+        let mut tx = db.pool().begin().await.map_err(|e| e.to_string())?;
+        record_count(
+            &mut tx,
+            &shift_id,
+            "close",
+            payload.currency.as_deref(),
+            payload.denominations.as_deref(),
+            payload.closing_balance,
+        )
+        .await?;
+        let blind = closes_blind(&mut tx, &register_id, closer_role.as_deref()).await.map_err(|e| e.to_string())?;
+        let cash = expected_cash(&mut tx, &shift_id, opening_balance).await.map_err(|e| e.to_string())?;
+        let difference = payload.closing_balance - cash.expected;
+
+        let mut tenders = vec![(
+            "cash".to_string(),
+            cash.expected,
+            Some(payload.closing_balance),
+            Some(difference),
+        )];
+        let declared = payload.tender_totals.as_deref().unwrap_or_default();
+        let mut takings = tender_takings(&mut tx, &shift_id).await.map_err(|e| e.to_string())?;
+        for total in declared {
+            if total.method != "cash" && !takings.iter().any(|(method, _)| *method == total.method) {
+                takings.push((total.method.clone(), 0.0));
+            }
+        }
+        for (method, expected) in takings {
+            let declared = declared.iter().find(|total| total.method == method).map(|total| total.amount);
+            tenders.push((method, expected, declared, declared.map(|declared| declared - expected)));
+        }
 
-    // Calculate expected balance from sales
-    let sales_total: Result<(f64,), sqlx::Error> = sqlx::query_as(
-        "SELECT COALESCE(SUM(total), 0) FROM sales WHERE shift_id = ?"
-    )
-    .bind(&shift_id)
-    .fetch_one(db.pool())
-    .await;
-
-    let expected_balance = opening_balance + sales_total.unwrap_or((0.0,)).0;
-    let difference = payload.closing_balance - expected_balance;
-
-    // Update shift
-    let result = sqlx::query(
-        r#"
-        UPDATE shifts 
-        SET closed_at = datetime('now'), 
-            closing_balance = ?, 
-            expected_balance = ?, 
-            difference = ?,
-            notes = ?,
-            status = 'closed'
-        WHERE id = ?
-        "#
-    )
-    .bind(payload.closing_balance)
-    .bind(expected_balance)
-    .bind(difference)
-    .bind(&payload.notes)
-    .bind(&shift_id)
-    .execute(db.pool())
-    .await;
-
-    match result {
-        Ok(_) => {
-            // Fetch complete shift data
-            let shift_data: Result<(String, String, String, String, String, String, String, f64, f64, f64, f64, String, String), sqlx::Error> = 
-                sqlx::query_as(
-                    r#"
-                    SELECT s.id, s.user_id, u.full_name, s.register_id, r.name, s.opened_at, s.closed_at,
-                           s.opening_balance, s.closing_balance, s.expected_balance, s.difference,
-                           COALESCE(s.notes, ''), s.status
-                    FROM shifts s
-                    JOIN users u ON s.user_id = u.id
-                    JOIN cash_registers r ON s.register_id = r.id
-                    WHERE s.id = ?
-                    "#
-                )
-                .bind(&shift_id)
-                .fetch_one(db.pool())
-                .await;
-
-            // Get sales summary
-            let total_sales: f64 = sqlx::query_as::<_, (f64,)>(
-                "SELECT COALESCE(SUM(total), 0) FROM sales WHERE shift_id = ?"
-            )
-            .bind(&shift_id)
-            .fetch_one(db.pool())
+        // Large discrepancies on any tender stay open for a manager to sign off
+        let threshold = get_setting(&mut tx, "cash_review_threshold")
             .await
-            .unwrap_or((0.0,)).0;
-
-            let total_transactions: i32 = sqlx::query_as::<_, (i32,)>(
-                "SELECT COUNT(*) FROM sales WHERE shift_id = ?"
+            .map_err(|e| e.to_string())?
+            .and_then(|value| value.parse::<f64>().ok())
+            .unwrap_or(f64::INFINITY);
+        let needs_review = tenders
+            .iter()
+            .any(|(_, _, _, difference)| difference.is_some_and(|difference| difference.abs() > threshold));
+        let status = if needs_review { "pending_review" } else { "closed" };
+
+        sqlx::query(
+            r#"
+            UPDATE shifts 
+            SET closed_at = datetime('now'), 
+                closing_balance = ?, 
+                expected_balance = ?, 
+                difference = ?,
+                notes = ?,
+                status = ?,
+                blind_close = ?
+            WHERE id = ?
+            "#
+        )
+        .bind(payload.closing_balance)
+        .bind(cash.expected)
+        .bind(difference)
+        .bind(&payload.notes)
+        .bind(status)
+        .bind(blind)
+        .bind(&shift_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        for (method, expected, declared, difference) in &tenders {
+            sqlx::query(
+                r#"
+                INSERT OR REPLACE INTO shift_tenders (shift_id, method, expected_amount, declared_amount, difference)
+                VALUES (?, ?, ?, ?, ?)
+                "#
             )
             .bind(&shift_id)
-            .fetch_one(db.pool())
+            .bind(method)
+            .bind(expected)
+            .bind(declared)
+            .bind(difference)
+            .execute(&mut *tx)
             .await
-            .unwrap_or((0,)).0;
+            .map_err(|e| e.to_string())?;
+        }
+        let mut summary = shift_summary(&mut tx, &shift_id).await.map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
 
-            let cash_sales: f64 = sqlx::query_as::<_, (f64,)>(
-                "SELECT COALESCE(SUM(amount), 0) FROM payments WHERE method = 'cash' AND sale_id IN (SELECT id FROM sales WHERE shift_id = ?)"
-            )
-            .bind(&shift_id)
-            .fetch_one(db.pool())
-            .await
-            .unwrap_or((0.0,)).0;
+        if summary.blind_close && !closer_is_manager {
+            summary.hide_expected();
+        }
+        Ok::<_, String>(summary)
+    }
+    .await;
 
-            let card_sales: f64 = sqlx::query_as::<_, (f64,)>(
-                "SELECT COALESCE(SUM(amount), 0) FROM payments WHERE method IN ('card', 'debit', 'credit') AND sale_id IN (SELECT id FROM sales WHERE shift_id = ?)"
-            )
-            .bind(&shift_id)
-            .fetch_one(db.pool())
-            .await
-            .unwrap_or((0.0,)).0;
+    match result {
+        Ok(summary) => {
+            let message = if summary.shift.status == "pending_review" {
+                "Turno cerrado; la diferencia requiere revisión de un gerente"
+            } else {
+                "Turno cerrado exitosamente"
+            };
+            Json(ApiResponse {
+                success: true,
+                data: Some(summary),
+                message: Some(message.to_string()),
+            })
+        }
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error al cerrar turno: {}", e)),
+        }),
+    }
+}
 
-            match shift_data {
-                Ok((id, user_id, user_name, register_id, register_name, opened_at, closed_at, opening_balance, closing_balance, expected_balance, difference, notes, status)) => {
-                    let shift = Shift {
-                        id,
-                        user_id,
-                        user_name,
-                        register_id,
-                        register_name,
-                        opened_at,
-                        closed_at: Some(closed_at),
-                        opening_balance,
-                        closing_balance: Some(closing_balance),
-                        expected_balance: Some(expected_balance),
-                        difference: Some(difference),
-                        notes: if notes.is_empty() { None } else { Some(notes) },
-                        status,
-                    };
+pub async fn get_shift_summary(
+    State(state): State<AppState>,
+    Path(shift_id): Path<String>,
+    Query(params): Query<ShiftViewQuery>,
+) -> Json<ApiResponse<ShiftSummary>> {
+    let db = state.db.lock().await;
+    let manager = sees_expected(db.pool(), params.user_id.as_deref()).await;
 
-                    let summary = ShiftSummary {
-                        shift,
-                        total_sales,
-                        total_transactions,
-                        cash_sales,
-                        card_sales,
-                        other_sales: total_sales - cash_sales - card_sales,
-                    };
+    let result = async {
+        let mut conn = db.pool().acquire().await?;
+        shift_summary(&mut conn, &shift_id).await
+    }
+    .await;
 
-                    Json(ApiResponse {
-                        success: true,
-                        data: Some(summary),
-                        message: Some("Turno cerrado exitosamente".to_string()),
-                    })
-                }
-                Err(e) => Json(ApiResponse {
-                    success: false,
-                    data: None,
-                    message: Some(format!("Error: {}", e)),
-                }),
+    match result {
+        Ok(mut summary) => {
+            if summary.blind_close && !manager {
+                summary.hide_expected();
             }
+            Json(ApiResponse {
+                success: true,
+                data: Some(summary),
+                message: None,
+            })
         }
+        Err(sqlx::Error::RowNotFound) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Turno no encontrado".to_string()),
+        }),
         Err(e) => Json(ApiResponse {
             success: false,
             data: None,
-            message: Some(format!("Error al cerrar turno: {}", e)),
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+/// Signs off a shift whose close was held back by a large difference.
+pub async fn review_shift(
+    State(state): State<AppState>,
+    Path(shift_id): Path<String>,
+    Json(payload): Json<ReviewShiftRequest>,
+) -> Json<ApiResponse<ShiftSummary>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        if !is_manager(db.pool(), &payload.user_id).await {
+            return Err("Solo un gerente puede revisar un turno".to_string());
+        }
+        if payload.notes.trim().is_empty() {
+            return Err("Las notas de la revisión son obligatorias".to_string());
+        }
+
+        let mut tx = db.pool().begin().await.map_err(|e| e.to_string())?;
+        let updated = sqlx::query(
+            r#"
+            UPDATE shifts
+            SET status = 'closed', reviewed_by = ?, reviewed_at = datetime('now'), review_notes = ?
+            WHERE id = ? AND status = 'pending_review'
+            "#
+        )
+        .bind(&payload.user_id)
+        .bind(payload.notes.trim())
+        .bind(&shift_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        if updated.rows_affected() == 0 {
+            return Err("El turno no está pendiente de revisión".to_string());
+        }
+
+        let summary = shift_summary(&mut tx, &shift_id).await.map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok(summary)
+    }
+    .await;
+
+    match result {
+        Ok(summary) => Json(ApiResponse {
+            success: true,
+            data: Some(summary),
+            message: Some("Turno revisado y cerrado".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
         }),
     }
 }
 
+/// Blind-closed figures are shown to managers only.
+async fn sees_expected(pool: &Pool<Sqlite>, user_id: Option<&str>) -> bool {
+    match user_id {
+        Some(user_id) => is_manager(pool, user_id).await,
+        None => false,
+    }
+}
+
 pub async fn get_current_shift(
     State(state): State<AppState>,
     Path(user_id): Path<String>,
@@ -396,17 +856,19 @@ pub async fn get_current_shift(
 
 pub async fn list_shifts(
     State(state): State<AppState>,
+    Query(params): Query<ShiftViewQuery>,
 ) -> Json<ApiResponse<Vec<Shift>>> {
     let db = state.db.lock().await;
-    
+    let manager = sees_expected(db.pool(), params.user_id.as_deref()).await;
+
     // FIX: Cambiar tipos String a f64 para closing_balance, expected_balance, difference
-    let result: Result<Vec<(String, String, String, String, String, String, String, f64, f64, f64, f64, String, String)>, sqlx::Error> = 
+    let result: Result<Vec<(String, String, String, String, String, String, String, f64, f64, f64, f64, String, String, i32)>, sqlx::Error> = 
         sqlx::query_as(
             r#"
             SELECT s.id, s.user_id, u.full_name, s.register_id, r.name, s.opened_at, 
                    COALESCE(s.closed_at, ''), s.opening_balance, 
                    COALESCE(s.closing_balance, 0.0), COALESCE(s.expected_balance, 0.0),
-                   COALESCE(s.difference, 0.0), COALESCE(s.notes, ''), s.status
+                   COALESCE(s.difference, 0.0), COALESCE(s.notes, ''), s.status, s.blind_close
             FROM shifts s
             JOIN users u ON s.user_id = u.id
             JOIN cash_registers r ON s.register_id = r.id
@@ -421,7 +883,8 @@ pub async fn list_shifts(
         Ok(rows) => {
             let shifts: Vec<Shift> = rows
                 .into_iter()
-                .map(|(id, user_id, user_name, register_id, register_name, opened_at, closed_at, opening_balance, closing_balance, expected_balance, difference, notes, status)| {
+                .map(|(id, user_id, user_name, register_id, register_name, opened_at, closed_at, opening_balance, closing_balance, expected_balance, difference, notes, status, blind_close)| {
+                    let hidden = status == "open" || (blind_close == 1 && !manager);
                     Shift {
                         id,
                         user_id,
@@ -432,8 +895,8 @@ pub async fn list_shifts(
                         closed_at: if closed_at.is_empty() { None } else { Some(closed_at) },
                         opening_balance,
                         closing_balance: if status == "open" { None } else { Some(closing_balance) },
-                        expected_balance: if status == "open" { None } else { Some(expected_balance) },
-                        difference: if status == "open" { None } else { Some(difference) },
+                        expected_balance: if hidden { None } else { Some(expected_balance) },
+                        difference: if hidden { None } else { Some(difference) },
                         notes: if notes.is_empty() { None } else { Some(notes) },
                         status,
                     }
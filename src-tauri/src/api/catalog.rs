@@ -0,0 +1,666 @@
+use axum::{
+    Json,
+    extract::{Multipart, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use calamine::{open_workbook_from_rs, Reader, Xlsx};
+use serde::{Deserialize, Serialize};
+use sqlx::{Sqlite, Transaction};
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+use crate::api::AppState;
+use crate::api::locations::{default_location_id, location_quantity, StockMovement};
+use crate::api::pricing::PriceUpdate;
+use crate::models::ApiResponse;
+
+/// Columns of the import/export format, in export order. `stock` is the stock at the default location.
+const COLUMNS: [&str; 12] = [
+    "sku", "barcode", "name", "description", "category", "price", "cost",
+    "stock", "min_stock", "max_stock", "unit", "tax_rate",
+];
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    /// Validate and report without saving anything.
+    pub dry_run: Option<bool>,
+    /// Create categories that don't exist yet instead of rejecting the row.
+    pub create_categories: Option<bool>,
+    /// User recorded on the stock movements caused by the import.
+    pub user_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    /// 'csv' (default) or 'xlsx'.
+    pub format: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ImportRowError {
+    /// Spreadsheet row number, counting the header as row 1.
+    pub row: usize,
+    pub sku: Option<String>,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub total_rows: usize,
+    pub created: usize,
+    pub updated: usize,
+    pub categories_created: Vec<String>,
+    pub errors: Vec<ImportRowError>,
+}
+
+/// Values of one row; `None` means the cell was empty and the current value is kept.
+#[derive(Default)]
+struct ImportRow {
+    sku: Option<String>,
+    barcode: Option<String>,
+    name: Option<String>,
+    description: Option<String>,
+    category: Option<String>,
+    price: Option<f64>,
+    cost: Option<f64>,
+    stock: Option<f64>,
+    min_stock: Option<f64>,
+    max_stock: Option<f64>,
+    unit: Option<String>,
+    tax_rate: Option<f64>,
+}
+
+/// Maps a header to its canonical column, accepting the Spanish names used in spreadsheets.
+fn canonical_column(header: &str) -> Option<&'static str> {
+    let header = header.trim().to_lowercase();
+    let column = match header.as_str() {
+        "codigo" | "código" | "clave" => "sku",
+        "codigo_barras" | "código de barras" | "codigo de barras" | "ean" => "barcode",
+        "nombre" | "producto" => "name",
+        "descripcion" | "descripción" => "description",
+        "category_name" | "categoria" | "categoría" => "category",
+        "precio" => "price",
+        "costo" => "cost",
+        "existencia" | "existencias" => "stock",
+        "minimo" | "mínimo" => "min_stock",
+        "maximo" | "máximo" => "max_stock",
+        "unidad" => "unit",
+        "iva" => "tax_rate",
+        other => other,
+    };
+    COLUMNS.iter().find(|c| **c == column).copied()
+}
+
+fn read_csv(bytes: &[u8]) -> Result<Vec<Vec<String>>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(bytes);
+
+    reader
+        .records()
+        .map(|record| {
+            record
+                .map(|r| r.iter().map(|cell| cell.trim().to_string()).collect())
+                .map_err(|e| format!("CSV inválido: {}", e))
+        })
+        .collect()
+}
+
+fn read_xlsx(bytes: &[u8]) -> Result<Vec<Vec<String>>, String> {
+    let mut workbook: Xlsx<_> = open_workbook_from_rs(Cursor::new(bytes))
+        .map_err(|e| format!("XLSX inválido: {}", e))?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or_else(|| "El archivo no tiene hojas".to_string())?
+        .map_err(|e| format!("XLSX inválido: {}", e))?;
+
+    Ok(range
+        .rows()
+        .map(|row| row.iter().map(|cell| cell.to_string().trim().to_string()).collect())
+        .collect())
+}
+
+fn parse_rows(
+    table: &[Vec<String>],
+    errors: &mut Vec<ImportRowError>,
+) -> Result<Vec<(usize, ImportRow)>, String> {
+    let header = table.first().ok_or_else(|| "El archivo está vacío".to_string())?;
+    let columns: Vec<Option<&str>> = header.iter().map(|h| canonical_column(h)).collect();
+    if !columns.contains(&Some("sku")) && !columns.contains(&Some("barcode")) {
+        return Err("El archivo debe tener una columna sku o barcode".to_string());
+    }
+
+    let mut rows = Vec::new();
+    for (index, cells) in table.iter().enumerate().skip(1) {
+        let row_number = index + 1;
+        if cells.iter().all(|c| c.is_empty()) {
+            continue;
+        }
+
+        let mut row = ImportRow::default();
+        let mut invalid: Vec<(&str, String)> = Vec::new();
+        for (column, value) in columns.iter().zip(cells) {
+            let Some(column) = column else { continue };
+            if value.is_empty() {
+                continue;
+            }
+
+            let text = Some(value.clone());
+            let mut number = || match value.replace(['$', ','], "").parse::<f64>() {
+                Ok(n) if n.is_finite() => Some(n),
+                _ => {
+                    invalid.push((column, format!("Valor numérico inválido: {}", value)));
+                    None
+                }
+            };
+
+            match *column {
+                "sku" => row.sku = text,
+                "barcode" => row.barcode = text,
+                "name" => row.name = text,
+                "description" => row.description = text,
+                "category" => row.category = text,
+                "price" => row.price = number(),
+                "cost" => row.cost = number(),
+                "stock" => row.stock = number(),
+                "min_stock" => row.min_stock = number(),
+                "max_stock" => row.max_stock = number(),
+                "unit" => row.unit = text,
+                "tax_rate" => row.tax_rate = number(),
+                _ => {}
+            }
+        }
+
+        if invalid.is_empty() {
+            rows.push((row_number, row));
+        } else {
+            errors.extend(invalid.into_iter().map(|(field, message)| ImportRowError {
+                row: row_number,
+                sku: row.sku.clone(),
+                field: Some(field.to_string()),
+                message,
+            }));
+        }
+    }
+    Ok(rows)
+}
+
+struct ExistingProduct {
+    sku: String,
+}
+
+pub async fn import_products(
+    State(state): State<AppState>,
+    Query(params): Query<ImportQuery>,
+    mut multipart: Multipart,
+) -> Json<ApiResponse<ImportReport>> {
+    let db = state.db.lock().await;
+
+    let mut file: Option<(String, Vec<u8>)> = None;
+    while let Ok(Some(field)) = multipart.next_field().await {
+        if field.name() == Some("file") {
+            let file_name = field.file_name().unwrap_or("").to_lowercase();
+            match field.bytes().await {
+                Ok(bytes) => file = Some((file_name, bytes.to_vec())),
+                Err(e) => {
+                    return Json(ApiResponse {
+                        success: false,
+                        data: None,
+                        message: Some(format!("Error al leer archivo: {}", e)),
+                    });
+                }
+            }
+        }
+    }
+
+    let Some((file_name, bytes)) = file else {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Falta el archivo (campo 'file')".to_string()),
+        });
+    };
+
+    // XLSX files are zip archives
+    let table = if file_name.ends_with(".xlsx") || bytes.starts_with(b"PK") {
+        read_xlsx(&bytes)
+    } else {
+        read_csv(&bytes)
+    };
+
+    let mut errors = Vec::new();
+    let rows = match table.and_then(|table| parse_rows(&table, &mut errors)) {
+        Ok(rows) => rows,
+        Err(e) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some(e),
+            });
+        }
+    };
+
+    let dry_run = params.dry_run.unwrap_or(false);
+    let mut report = ImportReport {
+        dry_run,
+        total_rows: rows.len() + errors.len(),
+        created: 0,
+        updated: 0,
+        categories_created: Vec::new(),
+        errors,
+    };
+
+    let mut tx = match db.pool().begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some(format!("Error: {}", e)),
+            });
+        }
+    };
+
+    if let Err(e) = apply_rows(&mut tx, &rows, &params, &mut report).await {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error al importar: {}", e)),
+        });
+    }
+
+    report.errors.sort_by_key(|e| e.row);
+
+    // The import is all-or-nothing, so a dry run is just a rollback
+    if dry_run || !report.errors.is_empty() {
+        let _ = tx.rollback().await;
+        let message = if report.errors.is_empty() {
+            format!("Simulación: {} nuevos, {} actualizados", report.created, report.updated)
+        } else {
+            let rows: HashSet<usize> = report.errors.iter().map(|e| e.row).collect();
+            format!("{} filas con errores; no se importó ningún producto", rows.len())
+        };
+        return Json(ApiResponse {
+            success: report.errors.is_empty(),
+            data: Some(report),
+            message: Some(message),
+        });
+    }
+
+    match tx.commit().await {
+        Ok(_) => Json(ApiResponse {
+            success: true,
+            message: Some(format!("Importación completa: {} nuevos, {} actualizados", report.created, report.updated)),
+            data: Some(report),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error al importar: {}", e)),
+        }),
+    }
+}
+
+async fn apply_rows(
+    tx: &mut Transaction<'_, Sqlite>,
+    rows: &[(usize, ImportRow)],
+    params: &ImportQuery,
+    report: &mut ImportReport,
+) -> Result<(), sqlx::Error> {
+    let products: Vec<(String, String, String)> =
+        sqlx::query_as("SELECT id, sku, COALESCE(barcode, '') FROM products")
+            .fetch_all(&mut **tx)
+            .await?;
+
+    let mut existing: HashMap<String, ExistingProduct> = HashMap::new();
+    let mut by_sku: HashMap<String, String> = HashMap::new();
+    let mut by_barcode: HashMap<String, String> = HashMap::new();
+    for (id, sku, barcode) in products {
+        if !barcode.is_empty() {
+            by_barcode.insert(barcode, id.clone());
+        }
+        by_sku.insert(sku.clone(), id.clone());
+        existing.insert(id, ExistingProduct { sku });
+    }
+
+    let mut categories: HashMap<String, String> =
+        sqlx::query_as::<_, (String, String)>("SELECT id, name FROM categories WHERE is_active = 1")
+            .fetch_all(&mut **tx)
+            .await?
+            .into_iter()
+            .map(|(id, name)| (name.to_lowercase(), id))
+            .collect();
+
+    let units: HashSet<String> = sqlx::query_as::<_, (String,)>("SELECT code FROM units")
+        .fetch_all(&mut **tx)
+        .await?
+        .into_iter()
+        .map(|t| t.0)
+        .collect();
+
+    let location_id = default_location_id(tx).await?;
+
+    let mut seen_skus: HashSet<String> = HashSet::new();
+    let mut seen_barcodes: HashMap<String, usize> = HashMap::new();
+
+    for (row_number, row) in rows {
+        let row_number = *row_number;
+        let mut row_errors = Vec::new();
+        let mut fail = |field: &str, message: String| {
+            row_errors.push((field.to_string(), message));
+        };
+
+        // Match by SKU first, then by barcode
+        let existing_id = match (&row.sku, &row.barcode) {
+            (Some(sku), _) if by_sku.contains_key(sku) => by_sku.get(sku).cloned(),
+            (_, Some(barcode)) => by_barcode.get(barcode).cloned(),
+            _ => None,
+        };
+        let current = existing_id.as_ref().and_then(|id| existing.get(id));
+        let sku = row.sku.clone().or_else(|| current.map(|p| p.sku.clone()));
+
+        match &sku {
+            None => fail("sku", "SKU requerido para productos nuevos".to_string()),
+            Some(sku) if !seen_skus.insert(sku.clone()) => fail("sku", format!("SKU {} repetido en el archivo", sku)),
+            _ => {}
+        }
+
+        if let Some(barcode) = &row.barcode {
+            if let Some(other_row) = seen_barcodes.insert(barcode.clone(), row_number) {
+                fail("barcode", format!("Código de barras {} repetido (fila {})", barcode, other_row));
+            } else if let Some(owner) = by_barcode.get(barcode) {
+                if Some(owner) != existing_id.as_ref() {
+                    fail("barcode", format!("Código de barras {} ya asignado a otro producto", barcode));
+                }
+            }
+        }
+
+        if current.is_none() && row.name.is_none() {
+            fail("name", "Nombre requerido para productos nuevos".to_string());
+        }
+        if current.is_none() && row.price.is_none() {
+            fail("price", "Precio requerido para productos nuevos".to_string());
+        }
+        for (field, value) in [
+            ("price", row.price),
+            ("cost", row.cost),
+            ("stock", row.stock),
+            ("min_stock", row.min_stock),
+            ("max_stock", row.max_stock),
+            ("tax_rate", row.tax_rate),
+        ] {
+            if value.is_some_and(|v| v < 0.0) {
+                fail(field, "No se permiten valores negativos".to_string());
+            }
+        }
+        if let Some(unit) = &row.unit {
+            if !units.contains(unit) {
+                fail("unit", format!("Unidad desconocida: {}", unit));
+            }
+        }
+
+        let category_id = match &row.category {
+            None => None,
+            Some(name) => match categories.get(&name.to_lowercase()) {
+                Some(id) => Some(id.clone()),
+                None if params.create_categories.unwrap_or(false) => {
+                    let id = uuid::Uuid::new_v4().to_string();
+                    sqlx::query(
+                        "INSERT INTO categories (id, name, is_active, created_at) VALUES (?, ?, 1, datetime('now'))"
+                    )
+                    .bind(&id)
+                    .bind(name)
+                    .execute(&mut **tx)
+                    .await?;
+                    categories.insert(name.to_lowercase(), id.clone());
+                    report.categories_created.push(name.clone());
+                    Some(id)
+                }
+                None => {
+                    fail("category", format!("Categoría desconocida: {}", name));
+                    None
+                }
+            },
+        };
+
+        if !row_errors.is_empty() {
+            for (field, message) in row_errors {
+                report.errors.push(ImportRowError {
+                    row: row_number,
+                    sku: sku.clone(),
+                    field: Some(field),
+                    message,
+                });
+            }
+            continue;
+        }
+        let sku = sku.unwrap_or_default();
+
+        match existing_id {
+            Some(product_id) => {
+                sqlx::query(
+                    r#"
+                    UPDATE products SET
+                        sku = ?, barcode = COALESCE(?, barcode), name = COALESCE(?, name),
+                        description = COALESCE(?, description), category_id = COALESCE(?, category_id),
+                        min_stock = COALESCE(?, min_stock), max_stock = COALESCE(?, max_stock),
+                        unit = COALESCE(?, unit), tax_rate = COALESCE(?, tax_rate),
+                        updated_at = datetime('now')
+                    WHERE id = ?
+                    "#
+                )
+                .bind(&sku)
+                .bind(&row.barcode)
+                .bind(&row.name)
+                .bind(&row.description)
+                .bind(&category_id)
+                .bind(row.min_stock)
+                .bind(row.max_stock)
+                .bind(&row.unit)
+                .bind(row.tax_rate)
+                .bind(&product_id)
+                .execute(&mut **tx)
+                .await?;
+
+                PriceUpdate {
+                    product_id: &product_id,
+                    price: row.price,
+                    cost: row.cost,
+                    source: "import",
+                    reference_id: None,
+                    user_id: Some(&params.user_id),
+                }
+                .apply(tx)
+                .await?;
+
+                // The stock column is the default location's stock; differences are booked as
+                // signed import movements so the history stays complete without counting as shrinkage
+                if let Some(stock) = row.stock {
+                    let delta = stock - location_quantity(tx, &product_id, &location_id).await?;
+                    if delta.abs() > 1e-9 {
+                        StockMovement {
+                            product_id: &product_id,
+                            location_id: &location_id,
+                            movement_type: "import",
+                            delta,
+                            quantity: delta,
+                            reference_id: None,
+                            notes: Some("Importación de catálogo"),
+                            user_id: &params.user_id,
+                            reason_code: None,
+                        }
+                        .post(tx)
+                        .await?;
+                    }
+                }
+
+                if let Some(barcode) = &row.barcode {
+                    by_barcode.insert(barcode.clone(), product_id.clone());
+                }
+                by_sku.insert(sku.clone(), product_id.clone());
+                existing.insert(product_id, ExistingProduct { sku });
+                report.updated += 1;
+            }
+            None => {
+                let product_id = uuid::Uuid::new_v4().to_string();
+                let stock = row.stock.unwrap_or(0.0);
+
+                sqlx::query(
+                    r#"
+                    INSERT INTO products (id, sku, barcode, name, description, category_id, price, cost, stock, min_stock, max_stock, unit, tax_rate, is_active, created_at, updated_at)
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1, datetime('now'), datetime('now'))
+                    "#
+                )
+                .bind(&product_id)
+                .bind(&sku)
+                .bind(&row.barcode)
+                .bind(&row.name)
+                .bind(&row.description)
+                .bind(&category_id)
+                .bind(row.price)
+                .bind(row.cost.unwrap_or(0.0))
+                .bind(stock)
+                .bind(row.min_stock.unwrap_or(0.0))
+                .bind(row.max_stock)
+                .bind(row.unit.as_deref().unwrap_or("pza"))
+                .bind(row.tax_rate.unwrap_or(0.16))
+                .execute(&mut **tx)
+                .await?;
+
+                sqlx::query("INSERT INTO product_stock (product_id, location_id, quantity) VALUES (?, ?, ?)")
+                    .bind(&product_id)
+                    .bind(&location_id)
+                    .bind(stock)
+                    .execute(&mut **tx)
+                    .await?;
+
+                if let Some(barcode) = &row.barcode {
+                    by_barcode.insert(barcode.clone(), product_id.clone());
+                }
+                by_sku.insert(sku.clone(), product_id.clone());
+                existing.insert(product_id, ExistingProduct { sku });
+                report.created += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+type ExportRow = (String, String, String, String, String, f64, f64, f64, f64, f64, String, f64);
+
+pub async fn export_products(
+    State(state): State<AppState>,
+    Query(params): Query<ExportQuery>,
+) -> Response {
+    let db = state.db.lock().await;
+
+    let result: Result<Vec<ExportRow>, sqlx::Error> =
+        sqlx::query_as(
+            r#"
+            SELECT p.sku, COALESCE(p.barcode, ''), p.name, COALESCE(p.description, ''), COALESCE(c.name, ''),
+                   p.price, p.cost, COALESCE(ps.quantity, 0.0), COALESCE(p.min_stock, 0.0), COALESCE(p.max_stock, 0.0),
+                   p.unit, p.tax_rate
+            FROM products p
+            LEFT JOIN categories c ON p.category_id = c.id
+            LEFT JOIN product_stock ps ON ps.product_id = p.id
+                AND ps.location_id = (SELECT id FROM locations WHERE is_default = 1 LIMIT 1)
+            WHERE p.is_active = 1
+            ORDER BY p.sku
+            "#
+        )
+        .fetch_all(db.pool())
+        .await;
+
+    let rows = match result {
+        Ok(rows) => rows,
+        Err(e) => {
+            return Json(ApiResponse::<String> {
+                success: false,
+                data: None,
+                message: Some(format!("Error: {}", e)),
+            })
+            .into_response();
+        }
+    };
+
+    let table: Vec<Vec<String>> = rows
+        .into_iter()
+        .map(|(sku, barcode, name, description, category, price, cost, stock, min_stock, max_stock, unit, tax_rate)| {
+            vec![
+                sku,
+                barcode,
+                name,
+                description,
+                category,
+                price.to_string(),
+                cost.to_string(),
+                stock.to_string(),
+                min_stock.to_string(),
+                if max_stock == 0.0 { String::new() } else { max_stock.to_string() },
+                unit,
+                tax_rate.to_string(),
+            ]
+        })
+        .collect();
+
+    let xlsx = params.format.as_deref() == Some("xlsx");
+    let file = if xlsx { write_xlsx(&table) } else { write_csv(&table) };
+
+    match file {
+        Ok(bytes) => {
+            let (content_type, file_name) = if xlsx {
+                ("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet", "productos.xlsx")
+            } else {
+                ("text/csv; charset=utf-8", "productos.csv")
+            };
+            (
+                [
+                    (header::CONTENT_TYPE, content_type.to_string()),
+                    (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name)),
+                ],
+                bytes,
+            )
+                .into_response()
+        }
+        Err(e) => Json(ApiResponse::<String> {
+            success: false,
+            data: None,
+            message: Some(format!("Error al exportar: {}", e)),
+        })
+        .into_response(),
+    }
+}
+
+fn write_csv(table: &[Vec<String>]) -> Result<Vec<u8>, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(COLUMNS).map_err(|e| e.to_string())?;
+    for row in table {
+        writer.write_record(row).map_err(|e| e.to_string())?;
+    }
+    writer.into_inner().map_err(|e| e.to_string())
+}
+
+fn write_xlsx(table: &[Vec<String>]) -> Result<Vec<u8>, String> {
+    let mut workbook = rust_xlsxwriter::Workbook::new();
+    let sheet = workbook.add_worksheet();
+
+    for (col, name) in COLUMNS.iter().enumerate() {
+        sheet.write_string(0, col as u16, *name).map_err(|e| e.to_string())?;
+    }
+    for (r, row) in table.iter().enumerate() {
+        for (col, value) in row.iter().enumerate() {
+            let row_num = r as u32 + 1;
+            // Barcodes and SKUs stay text so leading zeros survive
+            let numeric = col >= 5 && col != 10;
+            match value.parse::<f64>() {
+                Ok(n) if numeric => sheet.write_number(row_num, col as u16, n),
+                _ => sheet.write_string(row_num, col as u16, value),
+            }
+            .map_err(|e| e.to_string())?;
+        }
+    }
+    workbook.save_to_buffer().map_err(|e| e.to_string())
+}
@@ -0,0 +1,834 @@
+use axum::{Json, extract::{State, Path, Query}};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use crate::api::AppState;
+use crate::api::auth::is_manager;
+use crate::api::locations::{default_location_id, StockMovement};
+use crate::models::ApiResponse;
+
+#[derive(Serialize)]
+pub struct InventoryCount {
+    pub id: String,
+    pub count_number: String,
+    pub name: String,
+    pub status: String,
+    pub location_id: String,
+    pub abc_class: Option<String>,
+    pub scheduled_for: Option<String>,
+    pub started_at: Option<String>,
+    pub created_by: String,
+    pub approved_by: Option<String>,
+    pub approved_at: Option<String>,
+    pub notes: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Serialize)]
+pub struct CountLine {
+    pub product_id: String,
+    pub sku: String,
+    pub product_name: String,
+    pub expected_quantity: Option<f64>,
+    pub counted_quantity: Option<f64>,
+    pub variance: Option<f64>,
+    pub unit_cost: f64,
+    pub cost_impact: f64,
+    pub devices: i32,
+}
+
+#[derive(Serialize)]
+pub struct CountDetail {
+    pub count: InventoryCount,
+    pub lines: Vec<CountLine>,
+    pub items_total: i32,
+    pub items_counted: i32,
+    pub shrinkage_cost: f64,
+    pub surplus_cost: f64,
+    pub net_cost_impact: f64,
+}
+
+#[derive(Serialize)]
+pub struct AbcClassification {
+    pub product_id: String,
+    pub product_name: String,
+    pub revenue: f64,
+    pub revenue_share: f64,
+    pub abc_class: String,
+}
+
+#[derive(Deserialize)]
+pub struct CreateCountRequest {
+    pub name: String,
+    pub user_id: String,
+    /// Location being counted; defaults to the main store.
+    pub location_id: Option<String>,
+    pub product_ids: Option<Vec<String>>,
+    pub category_ids: Option<Vec<String>>,
+    /// Count every product of this ABC class ('A', 'B' or 'C').
+    pub abc_class: Option<String>,
+    /// Leave the count scheduled; the snapshot is taken when it starts.
+    pub scheduled_for: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CountEntriesRequest {
+    pub user_id: String,
+    pub device_id: Option<String>,
+    /// Replace this device's previous entries for the same products instead of adding to them.
+    pub replace: Option<bool>,
+    pub entries: Vec<CountEntry>,
+}
+
+#[derive(Deserialize)]
+pub struct CountEntry {
+    pub product_id: String,
+    pub quantity: f64,
+}
+
+#[derive(Deserialize)]
+pub struct ApproveCountRequest {
+    pub user_id: String,
+    /// Treat products nobody counted as zero stock instead of leaving them untouched.
+    pub zero_uncounted: Option<bool>,
+    pub notes: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AbcQuery {
+    pub days: Option<i32>,
+}
+
+const ABC_DEFAULT_DAYS: i32 = 90;
+
+/// Classifies active products by sales revenue over the last `days`:
+/// A covers the first 80% of revenue, B the next 15% and C the rest.
+async fn classify_abc(pool: &Pool<Sqlite>, days: i32) -> Result<Vec<AbcClassification>, sqlx::Error> {
+    let rows: Vec<(String, String, f64)> = sqlx::query_as(
+        r#"
+        SELECT p.id, p.name, COALESCE(SUM(CASE WHEN s.id IS NOT NULL THEN si.total END), 0.0) as revenue
+        FROM products p
+        LEFT JOIN sale_items si ON si.product_id = p.id
+        LEFT JOIN sales s ON si.sale_id = s.id AND s.status = 'completed'
+             AND s.created_at >= datetime('now', ?)
+        WHERE p.is_active = 1
+        GROUP BY p.id, p.name
+        ORDER BY revenue DESC, p.name
+        "#
+    )
+    .bind(format!("-{} days", days))
+    .fetch_all(pool)
+    .await?;
+
+    let total: f64 = rows.iter().map(|(_, _, revenue)| revenue).sum();
+    let mut cumulative = 0.0;
+
+    Ok(rows
+        .into_iter()
+        .map(|(product_id, product_name, revenue)| {
+            let share = if total > 0.0 { revenue / total } else { 0.0 };
+            // Class is decided by the share accumulated before this product
+            let abc_class = if revenue <= 0.0 {
+                "C"
+            } else if cumulative < 0.80 {
+                "A"
+            } else if cumulative < 0.95 {
+                "B"
+            } else {
+                "C"
+            };
+            cumulative += share;
+            AbcClassification {
+                product_id,
+                product_name,
+                revenue,
+                revenue_share: share,
+                abc_class: abc_class.to_string(),
+            }
+        })
+        .collect())
+}
+
+async fn resolve_count_products(
+    pool: &Pool<Sqlite>,
+    payload_products: &Option<Vec<String>>,
+    payload_categories: &Option<Vec<String>>,
+    abc_class: &Option<String>,
+) -> Result<Vec<String>, sqlx::Error> {
+    if let Some(class) = abc_class {
+        return Ok(classify_abc(pool, ABC_DEFAULT_DAYS)
+            .await?
+            .into_iter()
+            .filter(|c| c.abc_class.eq_ignore_ascii_case(class))
+            .map(|c| c.product_id)
+            .collect());
+    }
+
+    let mut conditions = Vec::new();
+    let mut values: Vec<&String> = Vec::new();
+
+    if let Some(ids) = payload_products.as_ref().filter(|ids| !ids.is_empty()) {
+        conditions.push(format!("id IN ({})", vec!["?"; ids.len()].join(", ")));
+        values.extend(ids.iter());
+    }
+    if let Some(ids) = payload_categories.as_ref().filter(|ids| !ids.is_empty()) {
+        conditions.push(format!("category_id IN ({})", vec!["?"; ids.len()].join(", ")));
+        values.extend(ids.iter());
+    }
+
+    // Without an explicit scope the whole active catalog is counted
+    let query_str = if conditions.is_empty() {
+        "SELECT id FROM products WHERE is_active = 1".to_string()
+    } else {
+        format!("SELECT id FROM products WHERE is_active = 1 AND ({})", conditions.join(" OR "))
+    };
+
+    let mut query_builder = sqlx::query_as::<_, (String,)>(&query_str);
+    for value in values {
+        query_builder = query_builder.bind(value);
+    }
+
+    Ok(query_builder
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|t| t.0)
+        .collect())
+}
+
+async fn insert_count_items(
+    conn: &mut SqliteConnection,
+    count_id: &str,
+    product_ids: &[String],
+) -> Result<(), sqlx::Error> {
+    for product_id in product_ids {
+        sqlx::query(
+            "INSERT OR IGNORE INTO inventory_count_items (id, count_id, product_id) VALUES (?, ?, ?)"
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(count_id)
+        .bind(product_id)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Freezes expected stock at the counted location and cost for every product in the count.
+async fn freeze_snapshot(conn: &mut SqliteConnection, count_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        UPDATE inventory_count_items
+        SET expected_quantity = COALESCE((
+                SELECT ps.quantity FROM product_stock ps
+                JOIN inventory_counts c ON c.id = inventory_count_items.count_id
+                WHERE ps.product_id = inventory_count_items.product_id AND ps.location_id = c.location_id
+            ), 0.0),
+            unit_cost = (SELECT cost FROM products WHERE id = inventory_count_items.product_id)
+        WHERE count_id = ?
+        "#
+    )
+    .bind(count_id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        "UPDATE inventory_counts SET status = 'in_progress', started_at = datetime('now') WHERE id = ?"
+    )
+    .bind(count_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+type CountRow = (String, String, String, String, String, String, String, String, String, String, String, String, String);
+type CountLineRow = (String, String, String, Option<f64>, Option<f64>, f64, i32);
+
+async fn fetch_count(pool: &Pool<Sqlite>, count_id: &str) -> Result<CountDetail, sqlx::Error> {
+    let (id, count_number, name, status, location_id, abc_class, scheduled_for, started_at, created_by, approved_by, approved_at, notes, created_at): CountRow =
+        sqlx::query_as(
+            r#"
+            SELECT id, count_number, name, status, location_id, COALESCE(abc_class, ''), COALESCE(scheduled_for, ''),
+                   COALESCE(started_at, ''), created_by, COALESCE(approved_by, ''), COALESCE(approved_at, ''),
+                   COALESCE(notes, ''), created_at
+            FROM inventory_counts
+            WHERE id = ?
+            "#
+        )
+        .bind(count_id)
+        .fetch_one(pool)
+        .await?;
+
+    let rows: Vec<CountLineRow> = sqlx::query_as(
+        r#"
+        SELECT i.product_id, p.sku, p.name, i.expected_quantity,
+               (SELECT SUM(e.quantity) FROM inventory_count_entries e
+                WHERE e.count_id = i.count_id AND e.product_id = i.product_id),
+               COALESCE(i.unit_cost, p.cost),
+               (SELECT COUNT(DISTINCT COALESCE(e.device_id, e.user_id)) FROM inventory_count_entries e
+                WHERE e.count_id = i.count_id AND e.product_id = i.product_id)
+        FROM inventory_count_items i
+        JOIN products p ON i.product_id = p.id
+        WHERE i.count_id = ?
+        ORDER BY p.name
+        "#
+    )
+    .bind(count_id)
+    .fetch_all(pool)
+    .await?;
+
+    let lines: Vec<CountLine> = rows
+        .into_iter()
+        .map(|(product_id, sku, product_name, expected_quantity, counted_quantity, unit_cost, devices)| {
+            let variance = match (expected_quantity, counted_quantity) {
+                (Some(expected), Some(counted)) => Some(counted - expected),
+                _ => None,
+            };
+            CountLine {
+                product_id,
+                sku,
+                product_name,
+                expected_quantity,
+                counted_quantity,
+                variance,
+                unit_cost,
+                cost_impact: variance.unwrap_or(0.0) * unit_cost,
+                devices,
+            }
+        })
+        .collect();
+
+    let shrinkage_cost = lines.iter().filter(|l| l.cost_impact < 0.0).fold(0.0, |acc, l| acc + l.cost_impact);
+    let surplus_cost = lines.iter().filter(|l| l.cost_impact > 0.0).fold(0.0, |acc, l| acc + l.cost_impact);
+
+    Ok(CountDetail {
+        count: InventoryCount {
+            id,
+            count_number,
+            name,
+            status,
+            location_id,
+            abc_class: if abc_class.is_empty() { None } else { Some(abc_class) },
+            scheduled_for: if scheduled_for.is_empty() { None } else { Some(scheduled_for) },
+            started_at: if started_at.is_empty() { None } else { Some(started_at) },
+            created_by,
+            approved_by: if approved_by.is_empty() { None } else { Some(approved_by) },
+            approved_at: if approved_at.is_empty() { None } else { Some(approved_at) },
+            notes: if notes.is_empty() { None } else { Some(notes) },
+            created_at,
+        },
+        items_total: lines.len() as i32,
+        items_counted: lines.iter().filter(|l| l.counted_quantity.is_some()).count() as i32,
+        shrinkage_cost,
+        surplus_cost,
+        net_cost_impact: shrinkage_cost + surplus_cost,
+        lines,
+    })
+}
+
+pub async fn list_counts(
+    State(state): State<AppState>,
+) -> Json<ApiResponse<Vec<InventoryCount>>> {
+    let db = state.db.lock().await;
+
+    let result: Result<Vec<CountRow>, sqlx::Error> =
+        sqlx::query_as(
+            r#"
+            SELECT id, count_number, name, status, location_id, COALESCE(abc_class, ''), COALESCE(scheduled_for, ''),
+                   COALESCE(started_at, ''), created_by, COALESCE(approved_by, ''), COALESCE(approved_at, ''),
+                   COALESCE(notes, ''), created_at
+            FROM inventory_counts
+            ORDER BY COALESCE(scheduled_for, created_at) DESC
+            LIMIT 100
+            "#
+        )
+        .fetch_all(db.pool())
+        .await;
+
+    match result {
+        Ok(rows) => {
+            let counts: Vec<InventoryCount> = rows
+                .into_iter()
+                .map(|(id, count_number, name, status, location_id, abc_class, scheduled_for, started_at, created_by, approved_by, approved_at, notes, created_at)| {
+                    InventoryCount {
+                        id,
+                        count_number,
+                        name,
+                        status,
+                        location_id,
+                        abc_class: if abc_class.is_empty() { None } else { Some(abc_class) },
+                        scheduled_for: if scheduled_for.is_empty() { None } else { Some(scheduled_for) },
+                        started_at: if started_at.is_empty() { None } else { Some(started_at) },
+                        created_by,
+                        approved_by: if approved_by.is_empty() { None } else { Some(approved_by) },
+                        approved_at: if approved_at.is_empty() { None } else { Some(approved_at) },
+                        notes: if notes.is_empty() { None } else { Some(notes) },
+                        created_at,
+                    }
+                })
+                .collect();
+
+            Json(ApiResponse {
+                success: true,
+                data: Some(counts),
+                message: None,
+            })
+        }
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn create_count(
+    State(state): State<AppState>,
+    Json(payload): Json<CreateCountRequest>,
+) -> Json<ApiResponse<CountDetail>> {
+    let db = state.db.lock().await;
+
+    if let Some(class) = &payload.abc_class {
+        if !["A", "B", "C"].contains(&class.to_uppercase().as_str()) {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some("Clase ABC inválida (use A, B o C)".to_string()),
+            });
+        }
+    }
+
+    let count_id = uuid::Uuid::new_v4().to_string();
+    let count_number = format!("CNT-{}", uuid::Uuid::new_v4().simple().to_string()[..8].to_uppercase());
+    let abc_class = payload.abc_class.as_ref().map(|c| c.to_uppercase());
+    let status = if payload.scheduled_for.is_some() { "scheduled" } else { "in_progress" };
+
+    let location_id = match &payload.location_id {
+        Some(location_id) => location_id.clone(),
+        None => {
+            let default = async {
+                let mut conn = db.pool().acquire().await?;
+                default_location_id(&mut conn).await
+            }
+            .await;
+            match default {
+                Ok(location_id) => location_id,
+                Err(e) => {
+                    return Json(ApiResponse {
+                        success: false,
+                        data: None,
+                        message: Some(format!("Error: {}", e)),
+                    });
+                }
+            }
+        }
+    };
+
+    // The count, its items and the snapshot are written together
+    let setup = async {
+        // ABC scopes are resolved when the count starts, since classes drift with sales
+        let product_ids = if abc_class.is_none() || payload.scheduled_for.is_none() {
+            Some(
+                resolve_count_products(
+                    db.pool(),
+                    &payload.product_ids,
+                    &payload.category_ids,
+                    &abc_class,
+                )
+                .await?,
+            )
+        } else {
+            None
+        };
+
+        let mut tx = db.pool().begin().await?;
+        sqlx::query(
+            r#"
+            INSERT INTO inventory_counts (id, count_number, name, status, location_id, abc_class, scheduled_for, created_by, notes, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, datetime('now'))
+            "#
+        )
+        .bind(&count_id)
+        .bind(&count_number)
+        .bind(&payload.name)
+        .bind(status)
+        .bind(&location_id)
+        .bind(&abc_class)
+        .bind(&payload.scheduled_for)
+        .bind(&payload.user_id)
+        .bind(&payload.notes)
+        .execute(&mut *tx)
+        .await?;
+
+        if let Some(product_ids) = product_ids {
+            insert_count_items(&mut tx, &count_id, &product_ids).await?;
+        }
+        if payload.scheduled_for.is_none() {
+            freeze_snapshot(&mut tx, &count_id).await?;
+        }
+        tx.commit().await?;
+        fetch_count(db.pool(), &count_id).await
+    }
+    .await;
+
+    match setup {
+        Ok(detail) => Json(ApiResponse {
+            success: true,
+            data: Some(detail),
+            message: Some(format!("Conteo {} creado exitosamente", count_number)),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error al crear conteo: {}", e)),
+        }),
+    }
+}
+
+pub async fn start_count(
+    State(state): State<AppState>,
+    Path(count_id): Path<String>,
+) -> Json<ApiResponse<CountDetail>> {
+    let db = state.db.lock().await;
+
+    let count: Result<(String, String), sqlx::Error> = sqlx::query_as(
+        "SELECT status, COALESCE(abc_class, '') FROM inventory_counts WHERE id = ?"
+    )
+    .bind(&count_id)
+    .fetch_one(db.pool())
+    .await;
+
+    let abc_class = match count {
+        Ok((status, abc_class)) if status == "scheduled" => {
+            if abc_class.is_empty() { None } else { Some(abc_class) }
+        }
+        Ok(_) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some("Solo se pueden iniciar conteos programados".to_string()),
+            });
+        }
+        Err(_) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some("Conteo no encontrado".to_string()),
+            });
+        }
+    };
+
+    let result = async {
+        let product_ids = match &abc_class {
+            Some(_) => Some(resolve_count_products(db.pool(), &None, &None, &abc_class).await?),
+            None => None,
+        };
+
+        let mut tx = db.pool().begin().await?;
+        if let Some(product_ids) = product_ids {
+            insert_count_items(&mut tx, &count_id, &product_ids).await?;
+        }
+        freeze_snapshot(&mut tx, &count_id).await?;
+        tx.commit().await?;
+        fetch_count(db.pool(), &count_id).await
+    }
+    .await;
+
+    match result {
+        Ok(detail) => Json(ApiResponse {
+            success: true,
+            data: Some(detail),
+            message: Some("Conteo iniciado".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn get_count(
+    State(state): State<AppState>,
+    Path(count_id): Path<String>,
+) -> Json<ApiResponse<CountDetail>> {
+    let db = state.db.lock().await;
+
+    match fetch_count(db.pool(), &count_id).await {
+        Ok(detail) => Json(ApiResponse {
+            success: true,
+            data: Some(detail),
+            message: None,
+        }),
+        Err(_) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Conteo no encontrado".to_string()),
+        }),
+    }
+}
+
+pub async fn add_count_entries(
+    State(state): State<AppState>,
+    Path(count_id): Path<String>,
+    Json(payload): Json<CountEntriesRequest>,
+) -> Json<ApiResponse<String>> {
+    let db = state.db.lock().await;
+
+    let status: Result<(String,), sqlx::Error> = sqlx::query_as(
+        "SELECT status FROM inventory_counts WHERE id = ?"
+    )
+    .bind(&count_id)
+    .fetch_one(db.pool())
+    .await;
+
+    match status {
+        Ok((status,)) if status == "in_progress" => {}
+        Ok(_) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some("El conteo no está en captura".to_string()),
+            });
+        }
+        Err(_) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some("Conteo no encontrado".to_string()),
+            });
+        }
+    }
+
+    if payload.entries.iter().any(|entry| entry.quantity < 0.0) {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Las cantidades contadas no pueden ser negativas".to_string()),
+        });
+    }
+
+    let result = async {
+        let mut tx = db.pool().begin().await?;
+
+        for entry in &payload.entries {
+            let in_scope: Option<(String,)> = sqlx::query_as(
+                "SELECT id FROM inventory_count_items WHERE count_id = ? AND product_id = ?"
+            )
+            .bind(&count_id)
+            .bind(&entry.product_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            if in_scope.is_none() {
+                return Ok(Some(entry.product_id.clone()));
+            }
+
+            if payload.replace.unwrap_or(false) {
+                sqlx::query(
+                    "DELETE FROM inventory_count_entries WHERE count_id = ? AND product_id = ? AND COALESCE(device_id, '') = COALESCE(?, '')"
+                )
+                .bind(&count_id)
+                .bind(&entry.product_id)
+                .bind(&payload.device_id)
+                .execute(&mut *tx)
+                .await?;
+            }
+
+            sqlx::query(
+                r#"
+                INSERT INTO inventory_count_entries (id, count_id, product_id, quantity, device_id, user_id, created_at)
+                VALUES (?, ?, ?, ?, ?, ?, datetime('now'))
+                "#
+            )
+            .bind(uuid::Uuid::new_v4().to_string())
+            .bind(&count_id)
+            .bind(&entry.product_id)
+            .bind(entry.quantity)
+            .bind(&payload.device_id)
+            .bind(&payload.user_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok::<Option<String>, sqlx::Error>(None)
+    }
+    .await;
+
+    match result {
+        Ok(None) => Json(ApiResponse {
+            success: true,
+            data: Some("Conteo registrado".to_string()),
+            message: Some(format!("{} partidas registradas", payload.entries.len())),
+        }),
+        Ok(Some(product_id)) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("El producto {} no forma parte del conteo", product_id)),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn approve_count(
+    State(state): State<AppState>,
+    Path(count_id): Path<String>,
+    Json(payload): Json<ApproveCountRequest>,
+) -> Json<ApiResponse<CountDetail>> {
+    let db = state.db.lock().await;
+
+    if !is_manager(db.pool(), &payload.user_id).await {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Solo un gerente puede aprobar conteos".to_string()),
+        });
+    }
+
+    let detail = match fetch_count(db.pool(), &count_id).await {
+        Ok(detail) if detail.count.status == "in_progress" => detail,
+        Ok(_) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some("El conteo no está en captura".to_string()),
+            });
+        }
+        Err(_) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some("Conteo no encontrado".to_string()),
+            });
+        }
+    };
+
+    let zero_uncounted = payload.zero_uncounted.unwrap_or(false);
+
+    let result = async {
+        let mut tx = db.pool().begin().await?;
+
+        for line in &detail.lines {
+            let expected = line.expected_quantity.unwrap_or(0.0);
+            let counted = match line.counted_quantity {
+                Some(counted) => counted,
+                None if zero_uncounted => 0.0,
+                None => continue,
+            };
+            let variance = counted - expected;
+            if variance.abs() < 1e-9 {
+                continue;
+            }
+
+            // Apply the variance relative to the snapshot so sales made during the count are kept
+            StockMovement {
+                product_id: &line.product_id,
+                location_id: &detail.count.location_id,
+                movement_type: "count",
+                delta: variance,
+                quantity: variance,
+                reference_id: Some(&count_id),
+                notes: Some(&format!("Conteo {}: esperado {}, contado {}", detail.count.count_number, expected, counted)),
+                user_id: &payload.user_id,
+                reason_code: None,
+            }
+            .post(&mut tx)
+            .await?;
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE inventory_counts
+            SET status = 'approved', approved_by = ?, approved_at = datetime('now'),
+                notes = COALESCE(?, notes)
+            WHERE id = ?
+            "#
+        )
+        .bind(&payload.user_id)
+        .bind(&payload.notes)
+        .bind(&count_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await
+    }
+    .await;
+
+    if let Err(e) = result {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error al aprobar conteo: {}", e)),
+        });
+    }
+
+    match fetch_count(db.pool(), &count_id).await {
+        Ok(detail) => Json(ApiResponse {
+            success: true,
+            data: Some(detail),
+            message: Some("Conteo aprobado y ajustes aplicados".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn cancel_count(
+    State(state): State<AppState>,
+    Path(count_id): Path<String>,
+) -> Json<ApiResponse<String>> {
+    let db = state.db.lock().await;
+
+    let result = sqlx::query(
+        "UPDATE inventory_counts SET status = 'cancelled' WHERE id = ? AND status IN ('scheduled', 'in_progress')"
+    )
+    .bind(&count_id)
+    .execute(db.pool())
+    .await;
+
+    match result {
+        Ok(r) if r.rows_affected() > 0 => Json(ApiResponse {
+            success: true,
+            data: Some("Conteo cancelado".to_string()),
+            message: Some("Conteo cancelado exitosamente".to_string()),
+        }),
+        Ok(_) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Conteo no encontrado o ya cerrado".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn get_abc_classification(
+    State(state): State<AppState>,
+    Query(params): Query<AbcQuery>,
+) -> Json<ApiResponse<Vec<AbcClassification>>> {
+    let db = state.db.lock().await;
+
+    match classify_abc(db.pool(), params.days.unwrap_or(ABC_DEFAULT_DAYS)).await {
+        Ok(data) => Json(ApiResponse {
+            success: true,
+            data: Some(data),
+            message: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
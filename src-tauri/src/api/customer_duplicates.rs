@@ -0,0 +1,435 @@
+use axum::{Json, extract::{Path, Query, State}};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use crate::api::AppState;
+use crate::api::auth::is_manager;
+use crate::models::ApiResponse;
+
+/// RFCs the SAT assigns to the general public and foreigners; shared by unrelated customers.
+const GENERIC_RFCS: [&str; 2] = ["XAXX010101000", "XEXX010101000"];
+/// Name blocks bigger than this (very common names) aren't compared pair by pair.
+const MAX_NAME_BLOCK: usize = 200;
+/// A phone, email or RFC shared by more customers than this is a placeholder, not a person.
+const MAX_SHARED_VALUE: usize = 20;
+const NAME_THRESHOLD: f64 = 0.85;
+
+#[derive(Serialize, Clone)]
+pub struct DuplicateCandidate {
+    pub id: String,
+    pub name: String,
+    pub phone: Option<String>,
+    pub email: Option<String>,
+    pub rfc: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Serialize)]
+pub struct DuplicatePair {
+    pub customer: DuplicateCandidate,
+    pub duplicate: DuplicateCandidate,
+    /// 0 to 1; matching signals add up.
+    pub score: f64,
+    /// 'rfc', 'email', 'phone' and/or 'name'.
+    pub reasons: Vec<String>,
+    pub name_similarity: f64,
+}
+
+#[derive(Deserialize)]
+pub struct DuplicateQuery {
+    /// Defaults to 0.6.
+    pub min_score: Option<f64>,
+    /// Defaults to 100, at most 1000.
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct MergeCustomerRequest {
+    /// Surviving record; the customer in the path is merged into it.
+    pub target_id: String,
+    pub user_id: String,
+}
+
+#[derive(Serialize)]
+pub struct CustomerMergeResult {
+    pub source_id: String,
+    pub target_id: String,
+    pub sales_moved: u64,
+    pub loyalty_points_moved: i32,
+    pub balance_moved: f64,
+}
+
+fn normalize_phone(phone: &str) -> Option<String> {
+    let digits: String = phone.chars().filter(|c| c.is_ascii_digit()).collect();
+    // Country and long-distance prefixes (+52, 1) don't tell numbers apart
+    match digits.len() {
+        0..=6 => None,
+        n if n > 10 => Some(digits[n - 10..].to_string()),
+        _ => Some(digits),
+    }
+}
+
+fn normalize_email(email: &str) -> Option<String> {
+    let email = email.trim().to_lowercase();
+    if email.contains('@') { Some(email) } else { None }
+}
+
+fn normalize_rfc(rfc: &str) -> Option<String> {
+    let rfc: String = rfc.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_uppercase();
+    if rfc.len() < 12 || GENERIC_RFCS.contains(&rfc.as_str()) { None } else { Some(rfc) }
+}
+
+/// Lowercase words without accents or particles, in alphabetical order, so
+/// "Pérez García, José" and "jose perez garcia" compare equal.
+fn normalize_name(name: &str) -> String {
+    let plain: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'á' | 'à' | 'ä' | 'â' => 'a',
+            'é' | 'è' | 'ë' | 'ê' => 'e',
+            'í' | 'ì' | 'ï' | 'î' => 'i',
+            'ó' | 'ò' | 'ö' | 'ô' => 'o',
+            'ú' | 'ù' | 'ü' | 'û' => 'u',
+            'ñ' => 'n',
+            c if c.is_alphanumeric() => c,
+            _ => ' ',
+        })
+        .collect();
+    let mut words: Vec<&str> = plain
+        .split_whitespace()
+        .filter(|word| !matches!(*word, "de" | "del" | "la" | "las" | "los" | "y"))
+        .collect();
+    words.sort_unstable();
+    words.join(" ")
+}
+
+/// 1 minus the edit distance relative to the longer name.
+fn name_similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 0.0;
+    }
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    1.0 - previous[b.len()] as f64 / longest as f64
+}
+
+struct Normalized {
+    candidate: DuplicateCandidate,
+    name: String,
+    phone: Option<String>,
+    email: Option<String>,
+    rfc: Option<String>,
+}
+
+type DuplicateCandidateRow = (String, String, String, String, String, String);
+
+pub async fn find_duplicate_customers(
+    State(state): State<AppState>,
+    Query(params): Query<DuplicateQuery>,
+) -> Json<ApiResponse<Vec<DuplicatePair>>> {
+    let db = state.db.lock().await;
+
+    let rows: Result<Vec<DuplicateCandidateRow>, sqlx::Error> = sqlx::query_as(
+        r#"
+        SELECT id, name, COALESCE(phone, ''), COALESCE(email, ''), COALESCE(rfc, ''), created_at
+        FROM customers
+        WHERE is_active = 1
+        ORDER BY created_at
+        "#
+    )
+    .fetch_all(db.pool())
+    .await;
+
+    let rows = match rows {
+        Ok(rows) => rows,
+        Err(e) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some(format!("Error: {}", e)),
+            });
+        }
+    };
+
+    let customers: Vec<Normalized> = rows
+        .into_iter()
+        .map(|(id, name, phone, email, rfc, created_at)| Normalized {
+            name: normalize_name(&name),
+            phone: normalize_phone(&phone),
+            email: normalize_email(&email),
+            rfc: normalize_rfc(&rfc),
+            candidate: DuplicateCandidate {
+                id,
+                name,
+                phone: if phone.is_empty() { None } else { Some(phone) },
+                email: if email.is_empty() { None } else { Some(email) },
+                rfc: if rfc.is_empty() { None } else { Some(rfc) },
+                created_at,
+            },
+        })
+        .collect();
+
+    // Only customers sharing a block are compared: same phone, email or RFC, or a name
+    // word starting alike
+    let mut blocks: HashMap<String, Vec<usize>> = HashMap::new();
+    for (index, customer) in customers.iter().enumerate() {
+        let mut keys: Vec<String> = Vec::new();
+        keys.extend(customer.phone.as_ref().map(|phone| format!("phone:{}", phone)));
+        keys.extend(customer.email.as_ref().map(|email| format!("email:{}", email)));
+        keys.extend(customer.rfc.as_ref().map(|rfc| format!("rfc:{}", rfc)));
+        for word in customer.name.split(' ').filter(|word| word.chars().count() >= 3) {
+            keys.push(format!("name:{}", word.chars().take(4).collect::<String>()));
+        }
+        keys.sort_unstable();
+        keys.dedup();
+        for key in keys {
+            blocks.entry(key).or_default().push(index);
+        }
+    }
+
+    let mut compared: HashSet<(usize, usize)> = HashSet::new();
+    let mut pairs: Vec<DuplicatePair> = Vec::new();
+    let min_score = params.min_score.unwrap_or(0.6);
+    for (key, members) in &blocks {
+        let limit = if key.starts_with("name:") { MAX_NAME_BLOCK } else { MAX_SHARED_VALUE };
+        if members.len() < 2 || members.len() > limit {
+            continue;
+        }
+        for (position, &a) in members.iter().enumerate() {
+            for &b in &members[position + 1..] {
+                if !compared.insert((a, b)) {
+                    continue;
+                }
+                let (first, second) = (&customers[a], &customers[b]);
+
+                let mut reasons: Vec<String> = Vec::new();
+                let mut weights: Vec<f64> = Vec::new();
+                let same = |x: &Option<String>, y: &Option<String>| x.is_some() && x == y;
+                if same(&first.rfc, &second.rfc) {
+                    reasons.push("rfc".to_string());
+                    weights.push(0.95);
+                }
+                if same(&first.email, &second.email) {
+                    reasons.push("email".to_string());
+                    weights.push(0.9);
+                }
+                if same(&first.phone, &second.phone) {
+                    reasons.push("phone".to_string());
+                    weights.push(0.8);
+                }
+                let similarity = name_similarity(&first.name, &second.name);
+                if similarity >= NAME_THRESHOLD {
+                    reasons.push("name".to_string());
+                    weights.push(0.7 * similarity);
+                }
+
+                // Independent signals: the chance all of them are coincidences shrinks
+                let score = 1.0 - weights.iter().fold(1.0, |rest, weight| rest * (1.0 - weight));
+                if reasons.is_empty() || score < min_score {
+                    continue;
+                }
+                pairs.push(DuplicatePair {
+                    customer: first.candidate.clone(),
+                    duplicate: second.candidate.clone(),
+                    score: (score * 1000.0).round() / 1000.0,
+                    reasons,
+                    name_similarity: (similarity * 1000.0).round() / 1000.0,
+                });
+            }
+        }
+    }
+
+    pairs.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.customer.name.cmp(&b.customer.name))
+    });
+    pairs.truncate(params.limit.unwrap_or(100).min(1000));
+
+    Json(ApiResponse {
+        success: true,
+        data: Some(pairs),
+        message: None,
+    })
+}
+
+pub async fn merge_customer(
+    State(state): State<AppState>,
+    Path(source_id): Path<String>,
+    Json(payload): Json<MergeCustomerRequest>,
+) -> Json<ApiResponse<CustomerMergeResult>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        if !is_manager(db.pool(), &payload.user_id).await {
+            return Err("Solo un gerente puede fusionar clientes".to_string());
+        }
+        if source_id == payload.target_id {
+            return Err("No se puede fusionar un cliente consigo mismo".to_string());
+        }
+
+        let mut tx = db.pool().begin().await.map_err(|e| e.to_string())?;
+
+        let source: Option<(String, i32, f64, String, i32)> = sqlx::query_as(
+            "SELECT name, COALESCE(loyalty_points, 0), COALESCE(current_balance, 0), COALESCE(notes, ''), is_active FROM customers WHERE id = ?"
+        )
+        .bind(&source_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        let Some((source_name, loyalty_points, balance, source_notes, source_active)) = source else {
+            return Err("Cliente no encontrado".to_string());
+        };
+        if source_active == 0 {
+            return Err("El cliente ya está inactivo o fue fusionado".to_string());
+        }
+        let target: Option<(i32,)> = sqlx::query_as("SELECT is_active FROM customers WHERE id = ?")
+            .bind(&payload.target_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        if target.is_none_or(|(active,)| active == 0) {
+            return Err("El cliente destino no es válido".to_string());
+        }
+
+        let sales_moved = sqlx::query("UPDATE sales SET customer_id = ? WHERE customer_id = ?")
+            .bind(&payload.target_id)
+            .bind(&source_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?
+            .rows_affected();
+
+        sqlx::query("UPDATE stock_reservations SET customer_id = ? WHERE customer_id = ?")
+            .bind(&payload.target_id)
+            .bind(&source_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        // Points history follows the points, so it still counts toward the target's tier
+        sqlx::query("UPDATE loyalty_points_ledger SET customer_id = ? WHERE customer_id = ?")
+            .bind(&payload.target_id)
+            .bind(&source_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        // Points, balance and notes add up; contact and fiscal data fill gaps in the surviving record
+        sqlx::query(
+            r#"
+            UPDATE customers SET
+                loyalty_points = COALESCE(loyalty_points, 0) + ?,
+                current_balance = COALESCE(current_balance, 0) + ?,
+                notes = CASE
+                    WHEN ? = '' THEN notes
+                    WHEN COALESCE(notes, '') = '' THEN ?
+                    ELSE notes || char(10) || ?
+                END,
+                email = COALESCE(NULLIF(email, ''), (SELECT email FROM customers WHERE id = ?)),
+                phone = COALESCE(NULLIF(phone, ''), (SELECT phone FROM customers WHERE id = ?)),
+                rfc = COALESCE(NULLIF(rfc, ''), (SELECT rfc FROM customers WHERE id = ?)),
+                address = COALESCE(NULLIF(address, ''), (SELECT address FROM customers WHERE id = ?)),
+                city = COALESCE(NULLIF(city, ''), (SELECT city FROM customers WHERE id = ?)),
+                state = COALESCE(NULLIF(state, ''), (SELECT state FROM customers WHERE id = ?)),
+                postal_code = COALESCE(NULLIF(postal_code, ''), (SELECT postal_code FROM customers WHERE id = ?)),
+                business_name = COALESCE(NULLIF(business_name, ''), (SELECT business_name FROM customers WHERE id = ?)),
+                tax_regime = COALESCE(NULLIF(tax_regime, ''), (SELECT tax_regime FROM customers WHERE id = ?)),
+                cfdi_use = COALESCE(NULLIF(cfdi_use, ''), (SELECT cfdi_use FROM customers WHERE id = ?)),
+                birth_date = COALESCE(NULLIF(birth_date, ''), (SELECT birth_date FROM customers WHERE id = ?)),
+                updated_at = datetime('now')
+            WHERE id = ?
+            "#
+        )
+        .bind(loyalty_points)
+        .bind(balance)
+        .bind(&source_notes)
+        .bind(&source_notes)
+        .bind(&source_notes)
+        .bind(&source_id)
+        .bind(&source_id)
+        .bind(&source_id)
+        .bind(&source_id)        .bind(&source_id)
+        .bind(&source_id)
+        .bind(&source_id)
+        .bind(&source_id)
+        .bind(&source_id)
+        .bind(&source_id)
+        .bind(&source_id)
+        .bind(&payload.target_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        sqlx::query(
+            r#"
+            UPDATE customers SET loyalty_points = 0, current_balance = 0, is_active = 0,
+                merged_into = ?, updated_at = datetime('now')
+            WHERE id = ?
+            "#
+        )
+        .bind(&payload.target_id)
+        .bind(&source_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO audit_logs (id, user_id, action, entity_type, entity_id, changes, created_at)
+            VALUES (?, ?, 'merge', 'customer', ?, ?, datetime('now'))
+            "#
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(&payload.user_id)
+        .bind(&source_id)
+        .bind(
+            serde_json::json!({
+                "target_id": payload.target_id,
+                "source_name": source_name,
+                "sales_moved": sales_moved,
+                "loyalty_points_moved": loyalty_points,
+                "balance_moved": balance,
+            })
+            .to_string(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        Ok(CustomerMergeResult {
+            source_id: source_id.clone(),
+            target_id: payload.target_id.clone(),
+            sales_moved,
+            loyalty_points_moved: loyalty_points,
+            balance_moved: balance,
+        })
+    }
+    .await;
+
+    match result {
+        Ok(merge) => Json(ApiResponse {
+            success: true,
+            data: Some(merge),
+            message: Some("Clientes fusionados exitosamente".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        }),
+    }
+}
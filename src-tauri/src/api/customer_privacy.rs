@@ -0,0 +1,479 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use std::collections::HashMap;
+use crate::api::AppState;
+use crate::api::auth::is_manager;
+use crate::api::customers::{fetch_customer, Customer};
+use crate::api::loyalty::record_points;
+use crate::models::ApiResponse;
+
+/// What an anonymized customer is called from then on.
+const ANONYMIZED_NAME: &str = "Cliente anonimizado";
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    pub user_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct AnonymizeRequest {
+    pub user_id: String,
+    pub reason: Option<String>,
+}
+
+/// Invoicing data (RFC, razón social, régimen, uso de CFDI) is part of the profile.
+#[derive(Serialize)]
+pub struct CustomerDataExport {
+    pub exported_at: String,
+    pub profile: Customer,
+    /// Duplicate records merged into this customer, which keep their own contact data.
+    pub merged_records: Vec<Customer>,
+    pub purchases: Vec<ExportedSale>,
+    pub loyalty_points: Vec<ExportedPointsEntry>,
+    pub loyalty_tier_changes: Vec<ExportedTierChange>,
+    pub reservations: Vec<ExportedReservation>,
+}
+
+#[derive(Serialize)]
+pub struct ExportedSale {
+    pub sale_number: String,
+    pub created_at: String,
+    pub status: String,
+    pub subtotal: f64,
+    pub tax_amount: f64,
+    pub discount_amount: f64,
+    pub total: f64,
+    pub loyalty_tier: Option<String>,
+    pub points_earned: i64,
+    pub payment_methods: Vec<String>,
+    pub items: Vec<ExportedSaleItem>,
+}
+
+#[derive(Serialize)]
+pub struct ExportedSaleItem {
+    pub sku: String,
+    pub product_name: String,
+    pub quantity: f64,
+    pub unit: Option<String>,
+    pub unit_price: f64,
+    pub discount_amount: f64,
+    pub total: f64,
+}
+
+#[derive(Serialize)]
+pub struct ExportedPointsEntry {
+    pub points: i64,
+    pub reason: String,
+    pub sale_number: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Serialize)]
+pub struct ExportedTierChange {
+    pub from_tier: Option<String>,
+    pub to_tier: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Serialize)]
+pub struct ExportedReservation {
+    pub reservation_number: String,
+    pub product_name: String,
+    pub quantity: f64,
+    pub status: String,
+    pub created_at: String,
+}
+
+#[derive(Serialize)]
+pub struct AnonymizeResult {
+    pub customer_id: String,
+    /// Sales stay on the books, now tied to the anonymized record.
+    pub sales_kept: i64,
+}
+
+async fn log_action(
+    conn: &mut sqlx::SqliteConnection,
+    user_id: &str,
+    action: &str,
+    customer_id: &str,
+    changes: serde_json::Value,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO audit_logs (id, user_id, action, entity_type, entity_id, changes, created_at)
+        VALUES (?, ?, ?, 'customer', ?, ?, datetime('now'))
+        "#
+    )
+    .bind(uuid::Uuid::new_v4().to_string())
+    .bind(user_id)
+    .bind(action)
+    .bind(customer_id)
+    .bind(changes.to_string())
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Records merged into the customer, directly or through another merged record.
+async fn merged_record_ids(conn: &mut SqliteConnection, customer_id: &str) -> Result<Vec<String>, sqlx::Error> {
+    let rows: Vec<(String,)> = sqlx::query_as(
+        r#"
+        WITH RECURSIVE merged(id) AS (
+            SELECT id FROM customers WHERE merged_into = ?
+            UNION
+            SELECT c.id FROM customers c JOIN merged m ON c.merged_into = m.id
+        )
+        SELECT id FROM merged
+        "#
+    )
+    .bind(customer_id)
+    .fetch_all(conn)
+    .await?;
+    Ok(rows.into_iter().map(|(id,)| id).collect())
+}
+
+type ExportedSaleRow = (String, String, String, String, f64, f64, f64, f64, String, i64);
+type ExportedItemRow = (String, String, String, f64, String, f64, f64, f64);
+
+async fn customer_sales(pool: &Pool<Sqlite>, customer_id: &str) -> Result<Vec<ExportedSale>, sqlx::Error> {
+    let sales: Vec<ExportedSaleRow> = sqlx::query_as(
+        r#"
+        SELECT id, sale_number, created_at, status, subtotal, tax_amount, COALESCE(discount_amount, 0),
+               total, COALESCE(loyalty_tier, ''), points_earned
+        FROM sales
+        WHERE customer_id = ?
+        ORDER BY created_at
+        "#
+    )
+    .bind(customer_id)
+    .fetch_all(pool)
+    .await?;
+    let sale_ids: Vec<&str> = sales.iter().map(|sale| sale.0.as_str()).collect();
+    let sale_ids = serde_json::json!(sale_ids).to_string();
+
+    let item_rows: Vec<ExportedItemRow> = sqlx::query_as(
+        r#"
+        SELECT si.sale_id, COALESCE(p.sku, ''), COALESCE(p.name, ''), si.quantity, COALESCE(si.unit, ''),
+               si.unit_price, COALESCE(si.discount_amount, 0), si.total
+        FROM sale_items si
+        LEFT JOIN products p ON p.id = si.product_id
+        WHERE si.sale_id IN (SELECT value FROM json_each(?))
+        "#
+    )
+    .bind(&sale_ids)
+    .fetch_all(pool)
+    .await?;
+    let mut items: HashMap<String, Vec<ExportedSaleItem>> = HashMap::new();
+    for (sale_id, sku, product_name, quantity, unit, unit_price, discount_amount, total) in item_rows {
+        items.entry(sale_id).or_default().push(ExportedSaleItem {
+            sku,
+            product_name,
+            quantity,
+            unit: if unit.is_empty() { None } else { Some(unit) },
+            unit_price,
+            discount_amount,
+            total,
+        });
+    }
+
+    let payment_rows: Vec<(String, String)> = sqlx::query_as(
+        "SELECT sale_id, method FROM payments WHERE sale_id IN (SELECT value FROM json_each(?))"
+    )
+    .bind(&sale_ids)
+    .fetch_all(pool)
+    .await?;
+    let mut payments: HashMap<String, Vec<String>> = HashMap::new();
+    for (sale_id, method) in payment_rows {
+        payments.entry(sale_id).or_default().push(method);
+    }
+
+    Ok(sales
+        .into_iter()
+        .map(|(id, sale_number, created_at, status, subtotal, tax_amount, discount_amount, total, loyalty_tier, points_earned)| ExportedSale {
+            sale_number,
+            created_at,
+            status,
+            subtotal,
+            tax_amount,
+            discount_amount,
+            total,
+            loyalty_tier: if loyalty_tier.is_empty() { None } else { Some(loyalty_tier) },
+            points_earned,
+            payment_methods: payments.remove(&id).unwrap_or_default(),
+            items: items.remove(&id).unwrap_or_default(),
+        })
+        .collect())
+}
+
+async fn build_export(pool: &Pool<Sqlite>, customer_id: &str) -> Result<Option<CustomerDataExport>, sqlx::Error> {
+    let Some(profile) = fetch_customer(pool, customer_id).await? else {
+        return Ok(None);
+    };
+    let purchases = customer_sales(pool, customer_id).await?;
+
+    let mut merged_records = Vec::new();
+    let merged_ids = merged_record_ids(&mut *pool.acquire().await?, customer_id).await?;
+    for merged_id in &merged_ids {
+        merged_records.extend(fetch_customer(pool, merged_id).await?);
+    }
+
+    let points: Vec<(i64, String, String, String)> = sqlx::query_as(
+        r#"
+        SELECT l.points, l.reason, COALESCE(s.sale_number, ''), l.created_at
+        FROM loyalty_points_ledger l
+        LEFT JOIN sales s ON s.id = l.sale_id
+        WHERE l.customer_id = ?
+        ORDER BY l.created_at
+        "#
+    )
+    .bind(customer_id)
+    .fetch_all(pool)
+    .await?;
+
+    let tier_changes: Vec<(String, String, String)> = sqlx::query_as(
+        r#"
+        SELECT COALESCE(from_tier, ''), COALESCE(to_tier, ''), created_at
+        FROM loyalty_tier_changes
+        WHERE customer_id = ?
+        ORDER BY created_at
+        "#
+    )
+    .bind(customer_id)
+    .fetch_all(pool)
+    .await?;
+
+    let reservations: Vec<(String, String, f64, String, String)> = sqlx::query_as(
+        r#"
+        SELECT r.reservation_number, p.name, r.quantity, r.status, r.created_at
+        FROM stock_reservations r
+        JOIN products p ON p.id = r.product_id
+        WHERE r.customer_id = ?
+        ORDER BY r.created_at
+        "#
+    )
+    .bind(customer_id)
+    .fetch_all(pool)
+    .await?;
+
+    let (exported_at,): (String,) = sqlx::query_as("SELECT datetime('now')").fetch_one(pool).await?;
+
+    Ok(Some(CustomerDataExport {
+        exported_at,
+        profile,
+        merged_records,
+        purchases,
+        loyalty_points: points
+            .into_iter()
+            .map(|(points, reason, sale_number, created_at)| ExportedPointsEntry {
+                points,
+                reason,
+                sale_number: if sale_number.is_empty() { None } else { Some(sale_number) },
+                created_at,
+            })
+            .collect(),
+        loyalty_tier_changes: tier_changes
+            .into_iter()
+            .map(|(from_tier, to_tier, created_at)| ExportedTierChange {
+                from_tier: if from_tier.is_empty() { None } else { Some(from_tier) },
+                to_tier: if to_tier.is_empty() { None } else { Some(to_tier) },
+                created_at,
+            })
+            .collect(),
+        reservations: reservations
+            .into_iter()
+            .map(|(reservation_number, product_name, quantity, status, created_at)| ExportedReservation {
+                reservation_number,
+                product_name,
+                quantity,
+                status,
+                created_at,
+            })
+            .collect(),
+    }))
+}
+
+/// Everything stored about a customer, as a JSON download.
+pub async fn export_customer_data(
+    State(state): State<AppState>,
+    Path(customer_id): Path<String>,
+    Query(params): Query<ExportQuery>,
+) -> Response {
+    let db = state.db.lock().await;
+
+    let file = async {
+        if !is_manager(db.pool(), &params.user_id).await {
+            return Err("Solo un gerente puede exportar los datos de un cliente".to_string());
+        }
+        let export = build_export(db.pool(), &customer_id)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Cliente no encontrado".to_string())?;
+        let bytes = serde_json::to_vec_pretty(&export).map_err(|e| e.to_string())?;
+
+        let mut conn = db.pool().acquire().await.map_err(|e| e.to_string())?;
+        log_action(
+            &mut conn,
+            &params.user_id,
+            "export",
+            &customer_id,
+            serde_json::json!({ "purchases": export.purchases.len() }),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        Ok::<_, String>(bytes)
+    }
+    .await;
+
+    match file {
+        Ok(bytes) => (
+            [
+                (header::CONTENT_TYPE, "application/json".to_string()),
+                (header::CONTENT_DISPOSITION, format!("attachment; filename=\"cliente_{}.json\"", customer_id)),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(e) => Json(ApiResponse::<String> {
+            success: false,
+            data: None,
+            message: Some(e),
+        })
+        .into_response(),
+    }
+}
+
+/// Scrubs a customer's personal data for good. Sales, points history and stock movements stay,
+/// tied to the anonymized record, so the books still add up.
+pub async fn anonymize_customer(
+    State(state): State<AppState>,
+    Path(customer_id): Path<String>,
+    Json(payload): Json<AnonymizeRequest>,
+) -> Json<ApiResponse<AnonymizeResult>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        if !is_manager(db.pool(), &payload.user_id).await {
+            return Err("Solo un gerente puede anonimizar clientes".to_string());
+        }
+
+        let mut tx = db.pool().begin().await.map_err(|e| e.to_string())?;
+        let customer: Option<(f64, i64, String)> = sqlx::query_as(
+            "SELECT COALESCE(current_balance, 0), COALESCE(loyalty_points, 0), COALESCE(anonymized_at, '') FROM customers WHERE id = ?"
+        )
+        .bind(&customer_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        let Some((balance, loyalty_points, anonymized_at)) = customer else {
+            return Err("Cliente no encontrado".to_string());
+        };
+        if !anonymized_at.is_empty() {
+            return Err("El cliente ya fue anonimizado".to_string());
+        }
+        // A debt needs someone to collect from
+        if balance.abs() > 0.005 {
+            return Err(format!("El cliente tiene un saldo pendiente de {:.2}", balance));
+        }
+
+        // Points are taken back through the ledger, so the history still matches the balance
+        record_points(&mut tx, &customer_id, -loyalty_points, "manual", None, Some(&payload.user_id))
+            .await
+            .map_err(|e| e.to_string())?;
+
+        // Duplicates merged into the customer still hold their own copy of the personal data
+        let mut scrubbed = vec![customer_id.clone()];
+        scrubbed.extend(merged_record_ids(&mut tx, &customer_id).await.map_err(|e| e.to_string())?);
+        for id in &scrubbed {
+            sqlx::query(
+                r#"
+                UPDATE customers SET
+                    name = ?, email = NULL, phone = NULL, rfc = NULL, address = NULL, city = NULL, state = NULL,
+                    postal_code = NULL, business_name = NULL, tax_regime = NULL, cfdi_use = NULL, birth_date = NULL,
+                    notes = NULL, loyalty_tier = NULL, credit_limit = 0, is_active = 0,
+                    anonymized_at = datetime('now'), updated_at = datetime('now')
+                WHERE id = ?
+                "#
+            )
+            .bind(ANONYMIZED_NAME)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+
+            // Free text elsewhere may name the customer
+            sqlx::query(
+                r#"
+                UPDATE stock_reservations SET notes = NULL,
+                    status = CASE WHEN status = 'active' THEN 'released' ELSE status END,
+                    closed_at = CASE WHEN status = 'active' THEN datetime('now') ELSE closed_at END
+                WHERE customer_id = ?
+                "#
+            )
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+            sqlx::query("UPDATE sales SET notes = NULL WHERE customer_id = ?")
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+            sqlx::query(
+                r#"
+                UPDATE audit_logs SET changes = json_remove(changes, '$.source_name')
+                WHERE entity_type = 'customer' AND entity_id = ? AND action = 'merge' AND json_valid(changes)
+                "#
+            )
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+
+        let (sales_kept,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM sales WHERE customer_id = ?")
+            .bind(&customer_id)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        log_action(
+            &mut tx,
+            &payload.user_id,
+            "anonymize",
+            &customer_id,
+            serde_json::json!({
+                "reason": payload.reason,
+                "sales_kept": sales_kept,
+                "merged_records": scrubbed.len() - 1,
+            }),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        Ok(AnonymizeResult {
+            customer_id: customer_id.clone(),
+            sales_kept,
+        })
+    }
+    .await;
+
+    match result {
+        Ok(anonymized) => Json(ApiResponse {
+            success: true,
+            data: Some(anonymized),
+            message: Some("Datos personales del cliente eliminados".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        }),
+    }
+}
@@ -1,9 +1,11 @@
-use axum::{Json, extract::{State, Path}};
+use axum::{Json, extract::{State, Path, Query}};
 use serde::{Deserialize, Serialize};
 use crate::api::AppState;
-use crate::models::ApiResponse;
+use crate::api::fiscal::{validate_fiscal_data, FiscalData};
+use crate::api::loyalty::record_points;
+use crate::models::{ApiResponse, FieldError, ValidationErrorResponse};
 
-#[derive(Serialize)]
+#[derive(Serialize, sqlx::FromRow)]
 pub struct Customer {
     pub id: String,
     pub name: String,
@@ -14,14 +16,51 @@ pub struct Customer {
     pub city: Option<String>,
     pub state: Option<String>,
     pub postal_code: Option<String>,
+    /// Razón social as registered with the SAT.
+    pub business_name: Option<String>,
+    /// SAT régimen fiscal code.
+    pub tax_regime: Option<String>,
+    /// SAT uso de CFDI code used by default on invoices.
+    pub cfdi_use: Option<String>,
+    /// YYYY-MM-DD.
+    pub birth_date: Option<String>,
     pub credit_limit: f64,
     pub current_balance: f64,
     pub loyalty_points: i32,
+    pub loyalty_tier: Option<String>,
     pub notes: Option<String>,
     pub is_active: bool,
     pub created_at: String,
 }
 
+#[derive(Deserialize)]
+pub struct CustomerListQuery {
+    /// Words matched as prefixes over name, phone, email and RFC.
+    pub q: Option<String>,
+    pub is_active: Option<bool>,
+    /// Customers who owe something (or, with false, who don't).
+    pub has_balance: Option<bool>,
+    /// Balance above a non-zero credit limit.
+    pub over_credit_limit: Option<bool>,
+    pub loyalty_tier: Option<String>,
+    /// 'name', 'created_at', 'balance', 'loyalty_points' or 'relevance' (default when searching).
+    pub sort: Option<String>,
+    /// 'asc' or 'desc'.
+    pub order: Option<String>,
+    /// Page size, 50 by default and at most 500.
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct CustomerPage {
+    pub customers: Vec<Customer>,
+    /// Customers matching the filters, across all pages.
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
 #[derive(Deserialize)]
 pub struct CreateCustomerRequest {
     pub name: String,
@@ -32,6 +71,10 @@ pub struct CreateCustomerRequest {
     pub city: Option<String>,
     pub state: Option<String>,
     pub postal_code: Option<String>,
+    pub business_name: Option<String>,
+    pub tax_regime: Option<String>,
+    pub cfdi_use: Option<String>,
+    pub birth_date: Option<String>,
     pub credit_limit: Option<f64>,
     pub notes: Option<String>,
 }
@@ -46,6 +89,12 @@ pub struct UpdateCustomerRequest {
     pub city: Option<String>,
     pub state: Option<String>,
     pub postal_code: Option<String>,
+    /// Empty strings clear the fiscal fields.
+    pub business_name: Option<String>,
+    pub tax_regime: Option<String>,
+    pub cfdi_use: Option<String>,
+    /// An empty string clears it.
+    pub birth_date: Option<String>,
     pub credit_limit: Option<f64>,
     pub notes: Option<String>,
     pub is_active: Option<bool>,
@@ -70,55 +119,126 @@ pub struct CustomerStats {
     pub loyalty_points: i32,
 }
 
+/// Every `Customer` field, named for `FromRow`; empty text comes back as `None`.
+const CUSTOMER_COLUMNS: &str = r#"
+    c.id, c.name, NULLIF(c.email, '') AS email, NULLIF(c.phone, '') AS phone, NULLIF(c.rfc, '') AS rfc,
+    NULLIF(c.address, '') AS address, NULLIF(c.city, '') AS city, NULLIF(c.state, '') AS state,
+    NULLIF(c.postal_code, '') AS postal_code, NULLIF(c.business_name, '') AS business_name,
+    NULLIF(c.tax_regime, '') AS tax_regime, NULLIF(c.cfdi_use, '') AS cfdi_use,
+    NULLIF(c.birth_date, '') AS birth_date, COALESCE(c.credit_limit, 0.0) AS credit_limit,
+    COALESCE(c.current_balance, 0.0) AS current_balance, COALESCE(c.loyalty_points, 0) AS loyalty_points,
+    NULLIF(c.loyalty_tier, '') AS loyalty_tier, NULLIF(c.notes, '') AS notes,
+    COALESCE(c.is_active, 0) = 1 AS is_active, c.created_at
+"#;
+
+/// FTS5 expression where every word must match as a prefix. Input that looks like a
+/// phone number is collapsed to its digits, the way phones are indexed.
+fn search_expression(text: &str) -> Option<String> {
+    let is_phone = text.chars().any(|c| c.is_ascii_digit())
+        && text.chars().all(|c| c.is_ascii_digit() || " -()+.".contains(c));
+    let words: Vec<String> = if is_phone {
+        vec![text.chars().filter(|c| c.is_ascii_digit()).collect()]
+    } else {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| word.to_string())
+            .collect()
+    };
+
+    if words.is_empty() {
+        None
+    } else {
+        Some(words.iter().map(|word| format!("\"{}\"*", word)).collect::<Vec<_>>().join(" "))
+    }
+}
+
 pub async fn list_customers(
     State(state): State<AppState>,
-) -> Json<ApiResponse<Vec<Customer>>> {
+    Query(params): Query<CustomerListQuery>,
+) -> Json<ApiResponse<CustomerPage>> {
     let db = state.db.lock().await;
-    
-    let result: Result<Vec<(String, String, String, String, String, String, String, String, String, f64, f64, i32, String, i32, String)>, sqlx::Error> = 
-        sqlx::query_as(
-            r#"
-            SELECT id, name, COALESCE(email, ''), COALESCE(phone, ''), COALESCE(rfc, ''),
-                   COALESCE(address, ''), COALESCE(city, ''), COALESCE(state, ''), COALESCE(postal_code, ''),
-                   credit_limit, current_balance, loyalty_points, COALESCE(notes, ''), is_active, created_at
-            FROM customers 
-            ORDER BY name
-            "#
-        )
-        .fetch_all(db.pool())
-        .await;
 
-    match result {
-        Ok(rows) => {
-            let customers: Vec<Customer> = rows
-                .into_iter()
-                .map(|(id, name, email, phone, rfc, address, city, state, postal_code, credit_limit, current_balance, loyalty_points, notes, is_active, created_at)| {
-                    Customer {
-                        id,
-                        name,
-                        email: if email.is_empty() { None } else { Some(email) },
-                        phone: if phone.is_empty() { None } else { Some(phone) },
-                        rfc: if rfc.is_empty() { None } else { Some(rfc) },
-                        address: if address.is_empty() { None } else { Some(address) },
-                        city: if city.is_empty() { None } else { Some(city) },
-                        state: if state.is_empty() { None } else { Some(state) },
-                        postal_code: if postal_code.is_empty() { None } else { Some(postal_code) },
-                        credit_limit,
-                        current_balance,
-                        loyalty_points,
-                        notes: if notes.is_empty() { None } else { Some(notes) },
-                        is_active: is_active == 1,
-                        created_at,
-                    }
-                })
-                .collect();
+    let search = params.q.as_deref().and_then(search_expression);
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+    let offset = params.offset.unwrap_or(0).max(0);
 
-            Json(ApiResponse {
-                success: true,
-                data: Some(customers),
-                message: None,
-            })
+    let mut from = String::from("FROM customers c");
+    let mut conditions: Vec<&str> = Vec::new();
+    let mut bindings: Vec<String> = Vec::new();
+    if let Some(search) = &search {
+        from.push_str(" JOIN customers_fts f ON f.rowid = c.rowid");
+        conditions.push("customers_fts MATCH ?");
+        bindings.push(search.clone());
+    }
+    match params.is_active {
+        Some(true) => conditions.push("c.is_active = 1"),
+        Some(false) => conditions.push("c.is_active = 0"),
+        None => {}
+    }
+    match params.has_balance {
+        Some(true) => conditions.push("c.current_balance > 0"),
+        Some(false) => conditions.push("COALESCE(c.current_balance, 0) <= 0"),
+        None => {}
+    }
+    match params.over_credit_limit {
+        Some(true) => conditions.push("c.credit_limit > 0 AND c.current_balance > c.credit_limit"),
+        Some(false) => conditions.push("NOT (c.credit_limit > 0 AND c.current_balance > c.credit_limit)"),
+        None => {}
+    }
+    if let Some(tier) = &params.loyalty_tier {
+        conditions.push("c.loyalty_tier = ?");
+        bindings.push(tier.clone());
+    }
+    let filter = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+
+    let direction = if params.order.as_deref() == Some("desc") { "DESC" } else { "ASC" };
+    let order_by = match params.sort.as_deref() {
+        Some("created_at") => format!("c.created_at {}", direction),
+        Some("balance") => format!("c.current_balance {}", direction),
+        Some("loyalty_points") => format!("c.loyalty_points {}", direction),
+        Some("name") => format!("c.name COLLATE NOCASE {}", direction),
+        // bm25 rank: lower is a better match
+        _ if search.is_some() => format!("f.rank {}", direction),
+        _ => format!("c.name COLLATE NOCASE {}", direction),
+    };
+
+    let result = async {
+        let count_sql = format!("SELECT COUNT(*) {} {}", from, filter);
+        let mut count = sqlx::query_as::<_, (i64,)>(&count_sql);
+        for binding in &bindings {
+            count = count.bind(binding);
+        }
+        let (total,) = count.fetch_one(db.pool()).await?;
+
+        let sql = format!(
+            "SELECT {} {} {} ORDER BY {}, c.id LIMIT ? OFFSET ?",
+            CUSTOMER_COLUMNS, from, filter, order_by
+        );
+        let mut query = sqlx::query_as::<_, Customer>(&sql);
+        for binding in &bindings {
+            query = query.bind(binding);
         }
+        let customers = query.bind(limit).bind(offset).fetch_all(db.pool()).await?;
+
+        Ok::<_, sqlx::Error>(CustomerPage {
+            customers,
+            total,
+            limit,
+            offset,
+        })
+    }
+    .await;
+
+    match result {
+        Ok(page) => Json(ApiResponse {
+            success: true,
+            data: Some(page),
+            message: None,
+        }),
         Err(e) => Json(ApiResponse {
             success: false,
             data: None,
@@ -127,53 +247,27 @@ pub async fn list_customers(
     }
 }
 
+/// The customer with its fiscal details, active or not.
+pub async fn fetch_customer(pool: &sqlx::Pool<sqlx::Sqlite>, customer_id: &str) -> Result<Option<Customer>, sqlx::Error> {
+    sqlx::query_as(&format!("SELECT {} FROM customers c WHERE c.id = ?", CUSTOMER_COLUMNS))
+        .bind(customer_id)
+        .fetch_optional(pool)
+        .await
+}
+
 pub async fn get_customer(
     State(state): State<AppState>,
     Path(customer_id): Path<String>,
 ) -> Json<ApiResponse<Customer>> {
     let db = state.db.lock().await;
     
-    let result: Result<(String, String, String, String, String, String, String, String, String, f64, f64, i32, String, i32, String), sqlx::Error> = 
-        sqlx::query_as(
-            r#"
-            SELECT id, name, COALESCE(email, ''), COALESCE(phone, ''), COALESCE(rfc, ''),
-                   COALESCE(address, ''), COALESCE(city, ''), COALESCE(state, ''), COALESCE(postal_code, ''),
-                   credit_limit, current_balance, loyalty_points, COALESCE(notes, ''), is_active, created_at
-            FROM customers 
-            WHERE id = ?
-            "#
-        )
-        .bind(&customer_id)
-        .fetch_one(db.pool())
-        .await;
-
-    match result {
-        Ok((id, name, email, phone, rfc, address, city, state, postal_code, credit_limit, current_balance, loyalty_points, notes, is_active, created_at)) => {
-            let customer = Customer {
-                id,
-                name,
-                email: if email.is_empty() { None } else { Some(email) },
-                phone: if phone.is_empty() { None } else { Some(phone) },
-                rfc: if rfc.is_empty() { None } else { Some(rfc) },
-                address: if address.is_empty() { None } else { Some(address) },
-                city: if city.is_empty() { None } else { Some(city) },
-                state: if state.is_empty() { None } else { Some(state) },
-                postal_code: if postal_code.is_empty() { None } else { Some(postal_code) },
-                credit_limit,
-                current_balance,
-                loyalty_points,
-                notes: if notes.is_empty() { None } else { Some(notes) },
-                is_active: is_active == 1,
-                created_at,
-            };
-
-            Json(ApiResponse {
-                success: true,
-                data: Some(customer),
-                message: None,
-            })
-        }
-        Err(_) => Json(ApiResponse {
+    match fetch_customer(db.pool(), &customer_id).await {
+        Ok(Some(customer)) => Json(ApiResponse {
+            success: true,
+            data: Some(customer),
+            message: None,
+        }),
+        _ => Json(ApiResponse {
             success: false,
             data: None,
             message: Some("Cliente no encontrado".to_string()),
@@ -181,18 +275,70 @@ pub async fn get_customer(
     }
 }
 
+fn validate_birth_date(birth_date: &str) -> Option<FieldError> {
+    match chrono::NaiveDate::parse_from_str(birth_date, "%Y-%m-%d") {
+        Ok(date) if date <= chrono::Local::now().date_naive() => None,
+        Ok(_) => Some(FieldError {
+            field: "birth_date".to_string(),
+            message: "La fecha de nacimiento no puede ser futura".to_string(),
+        }),
+        Err(_) => Some(FieldError {
+            field: "birth_date".to_string(),
+            message: "Fecha de nacimiento inválida (YYYY-MM-DD)".to_string(),
+        }),
+    }
+}
+
+/// Trimmed, with empty values treated as missing.
+fn clean(value: &Option<String>) -> Option<String> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string)
+}
+
 pub async fn create_customer(
     State(state): State<AppState>,
-    Json(payload): Json<CreateCustomerRequest>,
-) -> Json<ApiResponse<Customer>> {
+    Json(mut payload): Json<CreateCustomerRequest>,
+) -> Result<Json<ApiResponse<Customer>>, Json<ValidationErrorResponse>> {
     let db = state.db.lock().await;
-    
+
+    payload.rfc = clean(&payload.rfc).map(|rfc| rfc.to_uppercase());
+    payload.postal_code = clean(&payload.postal_code);
+    payload.business_name = clean(&payload.business_name);
+    payload.tax_regime = clean(&payload.tax_regime);
+    payload.cfdi_use = clean(&payload.cfdi_use).map(|cfdi_use| cfdi_use.to_uppercase());
+    payload.birth_date = clean(&payload.birth_date);
+
+    let mut errors = validate_fiscal_data(&FiscalData {
+        rfc: payload.rfc.as_deref(),
+        postal_code: payload.postal_code.as_deref(),
+        business_name: payload.business_name.as_deref(),
+        tax_regime: payload.tax_regime.as_deref(),
+        cfdi_use: payload.cfdi_use.as_deref(),
+    });
+    if payload.name.trim().is_empty() {
+        errors.insert(0, FieldError {
+            field: "name".to_string(),
+            message: "El nombre es obligatorio".to_string(),
+        });
+    }
+    if let Some(birth_date) = &payload.birth_date {
+        errors.extend(validate_birth_date(birth_date));
+    }
+    if payload.credit_limit.is_some_and(|limit| limit < 0.0) {
+        errors.push(FieldError {
+            field: "credit_limit".to_string(),
+            message: "El límite de crédito no puede ser negativo".to_string(),
+        });
+    }
+    if !errors.is_empty() {
+        return Err(Json(ValidationErrorResponse::new(errors)));
+    }
+
     let customer_id = uuid::Uuid::new_v4().to_string();
     
     let result = sqlx::query(
         r#"
-        INSERT INTO customers (id, name, email, phone, rfc, address, city, state, postal_code, credit_limit, current_balance, loyalty_points, notes, is_active, created_at, updated_at)
-        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 0, 0, ?, 1, datetime('now'), datetime('now'))
+        INSERT INTO customers (id, name, email, phone, rfc, address, city, state, postal_code, business_name, tax_regime, cfdi_use, birth_date, credit_limit, current_balance, loyalty_points, notes, is_active, created_at, updated_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 0, 0, ?, 1, datetime('now'), datetime('now'))
         "#
     )
     .bind(&customer_id)
@@ -204,12 +350,16 @@ pub async fn create_customer(
     .bind(&payload.city)
     .bind(&payload.state)
     .bind(&payload.postal_code)
+    .bind(&payload.business_name)
+    .bind(&payload.tax_regime)
+    .bind(&payload.cfdi_use)
+    .bind(&payload.birth_date)
     .bind(payload.credit_limit.unwrap_or(0.0))
     .bind(&payload.notes)
     .execute(db.pool())
     .await;
 
-    match result {
+    Ok(match result {
         Ok(_) => {
             let customer = Customer {
                 id: customer_id,
@@ -221,9 +371,14 @@ pub async fn create_customer(
                 city: payload.city,
                 state: payload.state,
                 postal_code: payload.postal_code,
+                business_name: payload.business_name,
+                tax_regime: payload.tax_regime,
+                cfdi_use: payload.cfdi_use,
+                birth_date: payload.birth_date,
                 credit_limit: payload.credit_limit.unwrap_or(0.0),
                 current_balance: 0.0,
                 loyalty_points: 0,
+                loyalty_tier: None,
                 notes: payload.notes,
                 is_active: true,
                 created_at: chrono::Utc::now().to_rfc3339(),
@@ -240,15 +395,93 @@ pub async fn create_customer(
             data: None,
             message: Some(format!("Error al crear cliente: {}", e)),
         }),
-    }
+    })
 }
 
 pub async fn update_customer(
     State(state): State<AppState>,
     Path(customer_id): Path<String>,
-    Json(payload): Json<UpdateCustomerRequest>,
-) -> Json<ApiResponse<String>> {
+    Json(mut payload): Json<UpdateCustomerRequest>,
+) -> Result<Json<ApiResponse<String>>, Json<ValidationErrorResponse>> {
     let db = state.db.lock().await;
+
+    // Fiscal fields are validated together, so unchanged ones come from the stored record
+    let current: Option<(String, String, String, String, String, String)> = sqlx::query_as(
+        r#"
+        SELECT COALESCE(rfc, ''), COALESCE(postal_code, ''), COALESCE(business_name, ''),
+               COALESCE(tax_regime, ''), COALESCE(cfdi_use, ''), COALESCE(anonymized_at, '')
+        FROM customers WHERE id = ?
+        "#
+    )
+    .bind(&customer_id)
+    .fetch_optional(db.pool())
+    .await
+    .ok()
+    .flatten();
+    let Some((rfc, postal_code, business_name, tax_regime, cfdi_use, anonymized_at)) = current else {
+        return Ok(Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Cliente no encontrado".to_string()),
+        }));
+    };
+    if !anonymized_at.is_empty() {
+        return Ok(Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("El cliente fue anonimizado y ya no se puede modificar".to_string()),
+        }));
+    }
+
+    // Given fields are trimmed; an empty value clears the field
+    let merge = |update: &mut Option<String>, stored: String| match update {
+        Some(value) => {
+            *value = value.trim().to_string();
+            clean(update)
+        }
+        None => clean(&Some(stored)),
+    };
+    if let Some(rfc) = &mut payload.rfc {
+        *rfc = rfc.to_uppercase();
+    }
+    if let Some(cfdi_use) = &mut payload.cfdi_use {
+        *cfdi_use = cfdi_use.to_uppercase();
+    }
+    let fiscal = (
+        merge(&mut payload.rfc, rfc),
+        merge(&mut payload.postal_code, postal_code),
+        merge(&mut payload.business_name, business_name),
+        merge(&mut payload.tax_regime, tax_regime),
+        merge(&mut payload.cfdi_use, cfdi_use),
+    );
+    let mut errors = validate_fiscal_data(&FiscalData {
+        rfc: fiscal.0.as_deref(),
+        postal_code: fiscal.1.as_deref(),
+        business_name: fiscal.2.as_deref(),
+        tax_regime: fiscal.3.as_deref(),
+        cfdi_use: fiscal.4.as_deref(),
+    });
+    if payload.name.as_deref().is_some_and(|name| name.trim().is_empty()) {
+        errors.insert(0, FieldError {
+            field: "name".to_string(),
+            message: "El nombre es obligatorio".to_string(),
+        });
+    }
+    if let Some(birth_date) = &mut payload.birth_date {
+        *birth_date = birth_date.trim().to_string();
+        if !birth_date.is_empty() {
+            errors.extend(validate_birth_date(birth_date));
+        }
+    }
+    if payload.credit_limit.is_some_and(|limit| limit < 0.0) {
+        errors.push(FieldError {
+            field: "credit_limit".to_string(),
+            message: "El límite de crédito no puede ser negativo".to_string(),
+        });
+    }
+    if !errors.is_empty() {
+        return Err(Json(ValidationErrorResponse::new(errors)));
+    }
     
     let mut updates = Vec::new();
     let mut values: Vec<String> = Vec::new();
@@ -285,6 +518,22 @@ pub async fn update_customer(
         updates.push("postal_code = ?");
         values.push(postal_code.clone());
     }
+    if let Some(business_name) = &payload.business_name {
+        updates.push("business_name = ?");
+        values.push(business_name.clone());
+    }
+    if let Some(tax_regime) = &payload.tax_regime {
+        updates.push("tax_regime = ?");
+        values.push(tax_regime.clone());
+    }
+    if let Some(cfdi_use) = &payload.cfdi_use {
+        updates.push("cfdi_use = ?");
+        values.push(cfdi_use.clone());
+    }
+    if let Some(birth_date) = &payload.birth_date {
+        updates.push("birth_date = NULLIF(?, '')");
+        values.push(birth_date.clone());
+    }
     
     // FIX: Guardar credit_limit como String para el formato
     let credit_limit_str = payload.credit_limit.map(|cl| cl.to_string());
@@ -306,11 +555,11 @@ pub async fn update_customer(
     }
     
     if updates.is_empty() {
-        return Json(ApiResponse {
+        return Ok(Json(ApiResponse {
             success: false,
             data: None,
             message: Some("No hay cambios para actualizar".to_string()),
-        });
+        }));
     }
     
     let query_str = format!(
@@ -327,7 +576,7 @@ pub async fn update_customer(
     
     let result = query_builder.execute(db.pool()).await;
 
-    match result {
+    Ok(match result {
         Ok(_) => Json(ApiResponse {
             success: true,
             data: Some("Cliente actualizado".to_string()),
@@ -338,7 +587,7 @@ pub async fn update_customer(
             data: None,
             message: Some(format!("Error: {}", e)),
         }),
-    }
+    })
 }
 
 pub async fn delete_customer(
@@ -481,6 +730,7 @@ pub async fn get_customer_stats(
 #[derive(Deserialize)]
 pub struct AddLoyaltyPointsRequest {
     pub points: i32,
+    pub user_id: Option<String>,
 }
 
 pub async fn add_loyalty_points(
@@ -490,12 +740,11 @@ pub async fn add_loyalty_points(
 ) -> Json<ApiResponse<String>> {
     let db = state.db.lock().await;
     
-    let result = sqlx::query(
-        "UPDATE customers SET loyalty_points = loyalty_points + ?, updated_at = datetime('now') WHERE id = ?"
-    )
-    .bind(payload.points)
-    .bind(&customer_id)
-    .execute(db.pool())
+    let result = async {
+        let mut tx = db.pool().begin().await?;
+        record_points(&mut tx, &customer_id, payload.points as i64, "manual", None, payload.user_id.as_deref()).await?;
+        tx.commit().await
+    }
     .await;
 
     match result {
@@ -0,0 +1,246 @@
+use axum::{Json, extract::{Path, Query, State}};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use crate::api::AppState;
+use crate::api::auth::is_manager;
+use crate::api::settings::get_setting;
+use crate::models::ApiResponse;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Denomination {
+    /// 'bill' or 'coin'.
+    pub kind: String,
+    pub value: f64,
+}
+
+#[derive(Deserialize)]
+pub struct DenominationQuery {
+    /// Defaults to the store currency.
+    pub currency: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SetDenominationsRequest {
+    pub denominations: Vec<Denomination>,
+    pub user_id: String,
+}
+
+/// How many pieces of one denomination were counted.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DenominationCount {
+    pub kind: String,
+    pub value: f64,
+    pub count: i64,
+}
+
+/// A drawer count by denomination, as stored with the shift.
+#[derive(Serialize)]
+pub struct CashCount {
+    pub currency: String,
+    pub denominations: Vec<DenominationCount>,
+    pub total: f64,
+}
+
+pub async fn store_currency(conn: &mut SqliteConnection) -> Result<String, sqlx::Error> {
+    Ok(get_setting(conn, "currency").await?.unwrap_or_else(|| "MXN".to_string()))
+}
+
+async fn fetch_denominations(conn: &mut SqliteConnection, currency: &str) -> Result<Vec<Denomination>, sqlx::Error> {
+    let rows: Vec<(String, f64)> = sqlx::query_as(
+        "SELECT kind, value FROM currency_denominations WHERE currency = ? ORDER BY value DESC, kind"
+    )
+    .bind(currency)
+    .fetch_all(conn)
+    .await?;
+    Ok(rows.into_iter().map(|(kind, value)| Denomination { kind, value }).collect())
+}
+
+/// Checks a count against the currency's denominations and the declared balance; returns the counted total.
+pub async fn validate_count(
+    conn: &mut SqliteConnection,
+    currency: &str,
+    counts: &[DenominationCount],
+    declared: f64,
+) -> Result<f64, String> {
+    let denominations = fetch_denominations(conn, currency).await.map_err(|e| e.to_string())?;
+    if denominations.is_empty() {
+        return Err(format!("No hay denominaciones configuradas para {}", currency));
+    }
+
+    let mut total = 0.0;
+    for (i, count) in counts.iter().enumerate() {
+        let same = |other: &DenominationCount| other.kind == count.kind && (other.value - count.value).abs() < 1e-9;
+        if !denominations.iter().any(|d| d.kind == count.kind && (d.value - count.value).abs() < 1e-9) {
+            return Err(format!("Denominación no válida para {}: {} de {}", currency, count.kind, count.value));
+        }
+        if counts[..i].iter().any(same) {
+            return Err(format!("Denominación repetida: {} de {}", count.kind, count.value));
+        }
+        if count.count < 0 {
+            return Err("Las cantidades no pueden ser negativas".to_string());
+        }
+        total += count.value * count.count as f64;
+    }
+
+    if (total - declared).abs() > 0.005 {
+        return Err(format!(
+            "El conteo por denominación suma {:.2} pero el saldo declarado es {:.2}",
+            total, declared
+        ));
+    }
+    Ok(total)
+}
+
+/// Stores a shift's drawer count; `stage` is 'open' or 'close'.
+pub async fn save_count(
+    conn: &mut SqliteConnection,
+    shift_id: &str,
+    stage: &str,
+    currency: &str,
+    counts: &[DenominationCount],
+) -> Result<(), sqlx::Error> {
+    for count in counts.iter().filter(|count| count.count > 0) {
+        sqlx::query(
+            r#"
+            INSERT INTO shift_cash_counts (shift_id, stage, currency, kind, value, count)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(shift_id)
+        .bind(stage)
+        .bind(currency)
+        .bind(&count.kind)
+        .bind(count.value)
+        .bind(count.count)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+pub async fn fetch_count(
+    conn: &mut SqliteConnection,
+    shift_id: &str,
+    stage: &str,
+) -> Result<Option<CashCount>, sqlx::Error> {
+    let rows: Vec<(String, String, f64, i64)> = sqlx::query_as(
+        r#"
+        SELECT currency, kind, value, count
+        FROM shift_cash_counts
+        WHERE shift_id = ? AND stage = ?
+        ORDER BY value DESC, kind
+        "#
+    )
+    .bind(shift_id)
+    .bind(stage)
+    .fetch_all(conn)
+    .await?;
+
+    let Some(currency) = rows.first().map(|row| row.0.clone()) else {
+        return Ok(None);
+    };
+    let denominations: Vec<DenominationCount> = rows
+        .into_iter()
+        .map(|(_, kind, value, count)| DenominationCount { kind, value, count })
+        .collect();
+    let total = denominations.iter().map(|d| d.value * d.count as f64).sum();
+
+    Ok(Some(CashCount {
+        currency,
+        denominations,
+        total,
+    }))
+}
+
+pub async fn list_denominations(
+    State(state): State<AppState>,
+    Query(params): Query<DenominationQuery>,
+) -> Json<ApiResponse<Vec<Denomination>>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        let mut conn = db.pool().acquire().await?;
+        let currency = match params.currency {
+            Some(currency) => currency.to_uppercase(),
+            None => store_currency(&mut conn).await?,
+        };
+        fetch_denominations(&mut conn, &currency).await
+    }
+    .await;
+
+    match result {
+        Ok(denominations) => Json(ApiResponse {
+            success: true,
+            data: Some(denominations),
+            message: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+/// Replaces the bills and coins accepted for a currency.
+pub async fn set_denominations(
+    State(state): State<AppState>,
+    Path(currency): Path<String>,
+    Json(payload): Json<SetDenominationsRequest>,
+) -> Json<ApiResponse<Vec<Denomination>>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        if !is_manager(db.pool(), &payload.user_id).await {
+            return Err("Solo un gerente puede configurar denominaciones".to_string());
+        }
+        let currency = currency.trim().to_uppercase();
+        if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err("La moneda debe ser un código ISO de 3 letras".to_string());
+        }
+        if payload.denominations.is_empty() {
+            return Err("Se necesita al menos una denominación".to_string());
+        }
+        for denomination in &payload.denominations {
+            if denomination.kind != "bill" && denomination.kind != "coin" {
+                return Err(format!("Tipo de denominación inválido: {}", denomination.kind));
+            }
+            if denomination.value <= 0.0 {
+                return Err("El valor de la denominación debe ser mayor a cero".to_string());
+            }
+        }
+
+        let mut tx = db.pool().begin().await.map_err(|e| e.to_string())?;
+        sqlx::query("DELETE FROM currency_denominations WHERE currency = ?")
+            .bind(&currency)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        for denomination in &payload.denominations {
+            sqlx::query("INSERT OR IGNORE INTO currency_denominations (currency, kind, value) VALUES (?, ?, ?)")
+                .bind(&currency)
+                .bind(&denomination.kind)
+                .bind(denomination.value)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        let denominations = fetch_denominations(&mut tx, &currency).await.map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok(denominations)
+    }
+    .await;
+
+    match result {
+        Ok(denominations) => Json(ApiResponse {
+            success: true,
+            data: Some(denominations),
+            message: Some("Denominaciones actualizadas".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        }),
+    }
+}
@@ -0,0 +1,320 @@
+use axum::Json;
+use serde::Serialize;
+use crate::models::{ApiResponse, FieldError};
+
+/// SAT c_RegimenFiscal: code, description, for personas físicas, for personas morales.
+pub const TAX_REGIMES: [(&str, &str, bool, bool); 19] = [
+    ("601", "General de Ley Personas Morales", false, true),
+    ("603", "Personas Morales con Fines no Lucrativos", false, true),
+    ("605", "Sueldos y Salarios e Ingresos Asimilados a Salarios", true, false),
+    ("606", "Arrendamiento", true, false),
+    ("607", "Régimen de Enajenación o Adquisición de Bienes", true, false),
+    ("608", "Demás ingresos", true, false),
+    ("610", "Residentes en el Extranjero sin Establecimiento Permanente en México", true, true),
+    ("611", "Ingresos por Dividendos (socios y accionistas)", true, false),
+    ("612", "Personas Físicas con Actividades Empresariales y Profesionales", true, false),
+    ("614", "Ingresos por intereses", true, false),
+    ("615", "Régimen de los ingresos por obtención de premios", true, false),
+    ("616", "Sin obligaciones fiscales", true, false),
+    ("620", "Sociedades Cooperativas de Producción que optan por diferir sus ingresos", false, true),
+    ("621", "Incorporación Fiscal", true, false),
+    ("622", "Actividades Agrícolas, Ganaderas, Silvícolas y Pesqueras", false, true),
+    ("623", "Opcional para Grupos de Sociedades", false, true),
+    ("624", "Coordinados", false, true),
+    ("625", "Régimen de las Actividades Empresariales con ingresos a través de Plataformas Tecnológicas", true, false),
+    ("626", "Régimen Simplificado de Confianza", true, true),
+];
+
+const BUSINESS_REGIMES: &[&str] = &["601", "603", "606", "612", "620", "621", "622", "623", "624", "625", "626"];
+const DEDUCTION_REGIMES: &[&str] = &["605", "606", "607", "608", "611", "612", "614", "615", "625"];
+const ALL_REGIMES: &[&str] = &[
+    "601", "603", "605", "606", "607", "608", "610", "611", "612", "614", "615", "616", "620", "621", "622",
+    "623", "624", "625", "626",
+];
+
+/// SAT c_UsoCFDI (CFDI 4.0): code, description, físicas, morales, regimes it may be used with.
+pub const CFDI_USES: [(&str, &str, bool, bool, &[&str]); 24] = [
+    ("G01", "Adquisición de mercancías", true, true, BUSINESS_REGIMES),
+    ("G02", "Devoluciones, descuentos o bonificaciones", true, true, BUSINESS_REGIMES),
+    ("G03", "Gastos en general", true, true, BUSINESS_REGIMES),
+    ("I01", "Construcciones", true, true, BUSINESS_REGIMES),
+    ("I02", "Mobiliario y equipo de oficina por inversiones", true, true, BUSINESS_REGIMES),
+    ("I03", "Equipo de transporte", true, true, BUSINESS_REGIMES),
+    ("I04", "Equipo de cómputo y accesorios", true, true, BUSINESS_REGIMES),
+    ("I05", "Dados, troqueles, moldes, matrices y herramental", true, true, BUSINESS_REGIMES),
+    ("I06", "Comunicaciones telefónicas", true, true, BUSINESS_REGIMES),
+    ("I07", "Comunicaciones satelitales", true, true, BUSINESS_REGIMES),
+    ("I08", "Otra maquinaria y equipo", true, true, BUSINESS_REGIMES),
+    ("D01", "Honorarios médicos, dentales y gastos hospitalarios", true, false, DEDUCTION_REGIMES),
+    ("D02", "Gastos médicos por incapacidad o discapacidad", true, false, DEDUCTION_REGIMES),
+    ("D03", "Gastos funerales", true, false, DEDUCTION_REGIMES),
+    ("D04", "Donativos", true, false, DEDUCTION_REGIMES),
+    ("D05", "Intereses reales efectivamente pagados por créditos hipotecarios (casa habitación)", true, false, DEDUCTION_REGIMES),
+    ("D06", "Aportaciones voluntarias al SAR", true, false, DEDUCTION_REGIMES),
+    ("D07", "Primas por seguros de gastos médicos", true, false, DEDUCTION_REGIMES),
+    ("D08", "Gastos de transportación escolar obligatoria", true, false, DEDUCTION_REGIMES),
+    ("D09", "Depósitos en cuentas para el ahorro, primas que tengan como base planes de pensiones", true, false, DEDUCTION_REGIMES),
+    ("D10", "Pagos por servicios educativos (colegiaturas)", true, false, DEDUCTION_REGIMES),
+    ("S01", "Sin efectos fiscales", true, true, ALL_REGIMES),
+    ("CP01", "Pagos", true, true, ALL_REGIMES),
+    ("CN01", "Nómina", true, false, &["605"]),
+];
+
+/// RFCs for the general public and foreigners; they carry no real check digit.
+const GENERIC_RFCS: [&str; 2] = ["XAXX010101000", "XEXX010101000"];
+
+#[derive(Serialize)]
+pub struct TaxRegime {
+    pub code: String,
+    pub description: String,
+    pub individuals: bool,
+    pub companies: bool,
+}
+
+#[derive(Serialize)]
+pub struct CfdiUse {
+    pub code: String,
+    pub description: String,
+    pub individuals: bool,
+    pub companies: bool,
+    pub tax_regimes: Vec<String>,
+}
+
+/// Fiscal fields of a customer as they will be stored, after merging any update.
+pub struct FiscalData<'a> {
+    pub rfc: Option<&'a str>,
+    pub postal_code: Option<&'a str>,
+    pub business_name: Option<&'a str>,
+    pub tax_regime: Option<&'a str>,
+    pub cfdi_use: Option<&'a str>,
+}
+
+/// Value of an RFC character in the SAT check digit table.
+fn rfc_char_value(c: char) -> Option<u32> {
+    match c {
+        '0'..='9' => c.to_digit(10),
+        'A'..='N' => Some(c as u32 - 'A' as u32 + 10),
+        '&' => Some(24),
+        'O'..='Z' => Some(c as u32 - 'O' as u32 + 25),
+        ' ' => Some(37),
+        'Ñ' => Some(38),
+        _ => None,
+    }
+}
+
+/// Check digit over the first 12 characters; personas morales are padded with a leading space.
+pub fn rfc_check_digit(rfc: &str) -> Option<char> {
+    let padded: Vec<char> = if rfc.chars().count() == 12 {
+        std::iter::once(' ').chain(rfc.chars()).collect()
+    } else {
+        rfc.chars().collect()
+    };
+    if padded.len() != 13 {
+        return None;
+    }
+
+    let mut sum = 0;
+    for (i, c) in padded[..12].iter().enumerate() {
+        sum += rfc_char_value(*c)? * (13 - i as u32);
+    }
+    Some(match 11 - sum % 11 {
+        11 => '0',
+        10 => 'A',
+        digit => char::from_digit(digit, 10)?,
+    })
+}
+
+/// Whether the RFC belongs to a persona física (13 characters) or moral (12), or why it's invalid.
+pub fn validate_rfc(rfc: &str) -> Result<bool, String> {
+    if GENERIC_RFCS.contains(&rfc) {
+        return Ok(true);
+    }
+    let chars: Vec<char> = rfc.chars().collect();
+    let letters = match chars.len() {
+        13 => 4,
+        12 => 3,
+        _ => return Err("El RFC debe tener 13 caracteres (persona física) o 12 (persona moral)".to_string()),
+    };
+
+    let (name, rest) = chars.split_at(letters);
+    let (date, homoclave) = rest.split_at(6);
+    if !name.iter().all(|c| c.is_ascii_uppercase() || *c == 'Ñ' || *c == '&') {
+        return Err(format!("Los primeros {} caracteres del RFC deben ser letras", letters));
+    }
+    let date: String = date.iter().collect();
+    if chrono::NaiveDate::parse_from_str(&format!("20{}", date), "%Y%m%d").is_err() {
+        return Err("La fecha del RFC no es válida (AAMMDD)".to_string());
+    }
+    if !homoclave.iter().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()) {
+        return Err("La homoclave del RFC no es válida".to_string());
+    }
+    if rfc_check_digit(rfc) != chars.last().copied() {
+        return Err("El dígito verificador del RFC no es válido".to_string());
+    }
+    Ok(letters == 4)
+}
+
+pub fn validate_postal_code(postal_code: &str) -> Result<(), String> {
+    if postal_code.len() != 5 || !postal_code.chars().all(|c| c.is_ascii_digit()) || postal_code.starts_with("00") {
+        return Err("El código postal debe tener 5 dígitos".to_string());
+    }
+    Ok(())
+}
+
+/// Checks each fiscal field and how they fit together; empty when everything is valid.
+pub fn validate_fiscal_data(data: &FiscalData) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    let mut error = |field: &str, message: String| {
+        errors.push(FieldError {
+            field: field.to_string(),
+            message,
+        })
+    };
+
+    let individual = match data.rfc {
+        Some(rfc) => match validate_rfc(rfc) {
+            Ok(individual) => Some(individual),
+            Err(message) => {
+                error("rfc", message);
+                None
+            }
+        },
+        None => None,
+    };
+    if let Some(postal_code) = data.postal_code {
+        if let Err(message) = validate_postal_code(postal_code) {
+            error("postal_code", message);
+        }
+    }
+
+    // Invoicing needs the whole set
+    let invoicing = data.business_name.is_some() || data.tax_regime.is_some() || data.cfdi_use.is_some();
+    if invoicing {
+        if data.rfc.is_none() {
+            error("rfc", "El RFC es obligatorio para facturar".to_string());
+        }
+        if data.postal_code.is_none() {
+            error("postal_code", "El código postal es obligatorio para facturar".to_string());
+        }
+        if data.business_name.is_none() {
+            error("business_name", "La razón social es obligatoria para facturar".to_string());
+        }
+        if data.tax_regime.is_none() {
+            error("tax_regime", "El régimen fiscal es obligatorio para facturar".to_string());
+        }
+    }
+
+    let persona = |individual: bool| if individual { "personas físicas" } else { "personas morales" };
+    if let Some(code) = data.tax_regime {
+        match TAX_REGIMES.iter().find(|(regime, ..)| *regime == code) {
+            None => error("tax_regime", format!("Régimen fiscal desconocido: {}", code)),
+            Some((_, _, individuals, companies)) => {
+                if let Some(individual) = individual {
+                    if (individual && !individuals) || (!individual && !companies) {
+                        error("tax_regime", format!("El régimen {} no aplica a {}", code, persona(individual)));
+                    }
+                }
+            }
+        }
+    }
+    if let Some(code) = data.cfdi_use {
+        match CFDI_USES.iter().find(|(cfdi_use, ..)| *cfdi_use == code) {
+            None => error("cfdi_use", format!("Uso de CFDI desconocido: {}", code)),
+            Some((_, _, individuals, companies, regimes)) => {
+                if let Some(individual) = individual {
+                    if (individual && !individuals) || (!individual && !companies) {
+                        error("cfdi_use", format!("El uso {} no aplica a {}", code, persona(individual)));
+                    }
+                }
+                if let Some(regime) = data.tax_regime {
+                    if !regimes.contains(&regime) {
+                        error("cfdi_use", format!("El uso {} no es válido para el régimen {}", code, regime));
+                    }
+                }
+            }
+        }
+    }
+    errors
+}
+
+pub async fn list_tax_regimes() -> Json<ApiResponse<Vec<TaxRegime>>> {
+    Json(ApiResponse {
+        success: true,
+        data: Some(
+            TAX_REGIMES
+                .iter()
+                .map(|(code, description, individuals, companies)| TaxRegime {
+                    code: code.to_string(),
+                    description: description.to_string(),
+                    individuals: *individuals,
+                    companies: *companies,
+                })
+                .collect(),
+        ),
+        message: None,
+    })
+}
+
+pub async fn list_cfdi_uses() -> Json<ApiResponse<Vec<CfdiUse>>> {
+    Json(ApiResponse {
+        success: true,
+        data: Some(
+            CFDI_USES
+                .iter()
+                .map(|(code, description, individuals, companies, regimes)| CfdiUse {
+                    code: code.to_string(),
+                    description: description.to_string(),
+                    individuals: *individuals,
+                    companies: *companies,
+                    tax_regimes: regimes.iter().map(|regime| regime.to_string()).collect(),
+                })
+                .collect(),
+        ),
+        message: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_digit_of_known_rfcs() {
+        assert_eq!(rfc_check_digit("GODE561231GR8"), Some('8'));
+        assert_eq!(rfc_check_digit("CACX7605101P8"), Some('8'));
+        assert_eq!(rfc_check_digit("EKU9003173C9"), Some('9'));
+        assert_eq!(rfc_check_digit("IIA040805DZ4"), Some('4'));
+        assert_eq!(rfc_check_digit("URE180429TM6"), Some('6'));
+    }
+
+    #[test]
+    fn check_digit_needs_twelve_or_thirteen_valid_characters() {
+        assert_eq!(rfc_check_digit("GODE561231G"), None);
+        assert_eq!(rfc_check_digit("GODE561231GR8X"), None);
+        assert_eq!(rfc_check_digit("GODE561231G*8"), None);
+    }
+
+    #[test]
+    fn accepts_valid_rfcs() {
+        assert_eq!(validate_rfc("GODE561231GR8"), Ok(true));
+        assert_eq!(validate_rfc("CACX7605101P8"), Ok(true));
+        assert_eq!(validate_rfc("EKU9003173C9"), Ok(false));
+        assert_eq!(validate_rfc("URE180429TM6"), Ok(false));
+        assert_eq!(validate_rfc("XAXX010101000"), Ok(true));
+        assert_eq!(validate_rfc("XEXX010101000"), Ok(true));
+    }
+
+    #[test]
+    fn rejects_invalid_rfcs() {
+        // Wrong check digit
+        assert!(validate_rfc("GODE561231GR7").is_err());
+        assert!(validate_rfc("EKU9003173C8").is_err());
+        // Impossible date
+        assert!(validate_rfc("GODE561331GR8").is_err());
+        // Digits where the name letters go
+        assert!(validate_rfc("G0DE561231GR8").is_err());
+        // Wrong length
+        assert!(validate_rfc("GODE561231G").is_err());
+        assert!(validate_rfc("").is_err());
+    }
+}
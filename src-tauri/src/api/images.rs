@@ -0,0 +1,238 @@
+use axum::{
+    Json,
+    extract::{Multipart, Path, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use image::{DynamicImage, ImageFormat, codecs::jpeg::JpegEncoder, imageops::FilterType};
+use serde::Serialize;
+use std::io::Cursor;
+use std::path::PathBuf;
+use crate::api::AppState;
+use crate::models::ApiResponse;
+
+/// URL prefix under which stored images are served.
+pub const IMAGES_ROUTE: &str = "/api/images/";
+const IMAGE_MAX_SIZE: u32 = 1024;
+const THUMBNAIL_SIZE: u32 = 256;
+
+#[derive(Serialize)]
+pub struct ProductImage {
+    pub image_url: String,
+    pub thumbnail_url: String,
+}
+
+/// The thumbnail of a stored image; external URLs are used as they are.
+pub fn thumbnail_url(image_url: &str) -> String {
+    match image_url.strip_prefix(IMAGES_ROUTE).and_then(|file| file.rsplit_once('.')) {
+        Some((stem, extension)) => format!("{}{}_thumb.{}", IMAGES_ROUTE, stem, extension),
+        None => image_url.to_string(),
+    }
+}
+
+/// Files on disk behind a stored image URL (picture and thumbnail).
+fn stored_files(dir: &std::path::Path, image_url: &str) -> Vec<PathBuf> {
+    match image_url.strip_prefix(IMAGES_ROUTE) {
+        Some(file) => vec![dir.join(file), dir.join(&thumbnail_url(image_url)[IMAGES_ROUTE.len()..])],
+        None => Vec::new(),
+    }
+}
+
+/// PNG keeps transparency; everything else is stored as JPEG.
+fn encode(image: &DynamicImage) -> Result<(Vec<u8>, &'static str), String> {
+    let mut bytes = Vec::new();
+    if image.color().has_alpha() {
+        image
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .map_err(|e| e.to_string())?;
+        Ok((bytes, "png"))
+    } else {
+        JpegEncoder::new_with_quality(&mut bytes, 85)
+            .encode_image(&image.to_rgb8())
+            .map_err(|e| e.to_string())?;
+        Ok((bytes, "jpg"))
+    }
+}
+
+/// Resizes the upload to at most `IMAGE_MAX_SIZE` and encodes it with its thumbnail.
+fn process_upload(bytes: &[u8]) -> Result<(Vec<u8>, Vec<u8>, &'static str), String> {
+    let picture = image::load_from_memory(bytes)
+        .map_err(|_| "Formato de imagen no soportado (JPEG, PNG, WebP o GIF)".to_string())?;
+    let picture = if picture.width() > IMAGE_MAX_SIZE || picture.height() > IMAGE_MAX_SIZE {
+        picture.resize(IMAGE_MAX_SIZE, IMAGE_MAX_SIZE, FilterType::Lanczos3)
+    } else {
+        picture
+    };
+    let thumbnail = picture.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+
+    let (picture_bytes, extension) = encode(&picture)?;
+    let (thumbnail_bytes, _) = encode(&thumbnail)?;
+    Ok((picture_bytes, thumbnail_bytes, extension))
+}
+
+pub async fn upload_product_image(
+    State(state): State<AppState>,
+    Path(product_id): Path<String>,
+    mut multipart: Multipart,
+) -> Json<ApiResponse<ProductImage>> {
+    let result = async {
+        let mut upload: Option<Vec<u8>> = None;
+        while let Some(field) = multipart
+            .next_field()
+            .await
+            .map_err(|e| format!("Error al leer archivo: {}", e))?
+        {
+            if field.name() == Some("file") {
+                let bytes = field.bytes().await.map_err(|e| format!("Error al leer archivo: {}", e))?;
+                upload = Some(bytes.to_vec());
+            }
+        }
+        let bytes = upload.ok_or_else(|| "Falta el archivo (campo 'file')".to_string())?;
+
+        // The database is only held for the lookups, not while the image is processed
+        let dir = {
+            let db = state.db.lock().await;
+            sqlx::query_as::<_, (String,)>("SELECT id FROM products WHERE id = ?")
+                .bind(&product_id)
+                .fetch_optional(db.pool())
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| "Producto no encontrado".to_string())?;
+            db.images_dir()
+        };
+
+        let (picture_bytes, thumbnail_bytes, extension) = tokio::task::spawn_blocking(move || process_upload(&bytes))
+            .await
+            .map_err(|e| e.to_string())??;
+
+        // A new name per upload, so clients can cache images forever
+        let stem = format!("{}-{}", product_id, &uuid::Uuid::new_v4().simple().to_string()[..8]);
+        tokio::fs::create_dir_all(&dir).await.map_err(|e| e.to_string())?;
+        tokio::fs::write(dir.join(format!("{}.{}", stem, extension)), &picture_bytes)
+            .await
+            .map_err(|e| e.to_string())?;
+        tokio::fs::write(dir.join(format!("{}_thumb.{}", stem, extension)), &thumbnail_bytes)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let image_url = format!("{}{}.{}", IMAGES_ROUTE, stem, extension);
+        let db = state.db.lock().await;
+        let (current,): (String,) = sqlx::query_as("SELECT COALESCE(image_url, '') FROM products WHERE id = ?")
+            .bind(&product_id)
+            .fetch_optional(db.pool())
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Producto no encontrado".to_string())?;
+        sqlx::query("UPDATE products SET image_url = ?, updated_at = datetime('now') WHERE id = ?")
+            .bind(&image_url)
+            .bind(&product_id)
+            .execute(db.pool())
+            .await
+            .map_err(|e| e.to_string())?;
+
+        for file in stored_files(&dir, &current) {
+            let _ = tokio::fs::remove_file(file).await;
+        }
+
+        Ok::<_, String>(ProductImage {
+            thumbnail_url: thumbnail_url(&image_url),
+            image_url,
+        })
+    }
+    .await;
+
+    match result {
+        Ok(image) => Json(ApiResponse {
+            success: true,
+            data: Some(image),
+            message: Some("Imagen guardada".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        }),
+    }
+}
+
+pub async fn delete_product_image(
+    State(state): State<AppState>,
+    Path(product_id): Path<String>,
+) -> Json<ApiResponse<String>> {
+    let db = state.db.lock().await;
+
+    let current: Result<Option<(String,)>, sqlx::Error> =
+        sqlx::query_as("SELECT COALESCE(image_url, '') FROM products WHERE id = ?")
+            .bind(&product_id)
+            .fetch_optional(db.pool())
+            .await;
+
+    let current = match current {
+        Ok(Some((current,))) => current,
+        Ok(None) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some("Producto no encontrado".to_string()),
+            });
+        }
+        Err(e) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some(format!("Error: {}", e)),
+            });
+        }
+    };
+
+    let result = sqlx::query("UPDATE products SET image_url = NULL, updated_at = datetime('now') WHERE id = ?")
+        .bind(&product_id)
+        .execute(db.pool())
+        .await;
+
+    match result {
+        Ok(_) => {
+            for file in stored_files(&db.images_dir(), &current) {
+                let _ = tokio::fs::remove_file(file).await;
+            }
+            Json(ApiResponse {
+                success: true,
+                data: Some(product_id),
+                message: Some("Imagen eliminada".to_string()),
+            })
+        }
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn serve_image(
+    State(state): State<AppState>,
+    Path(file): Path<String>,
+) -> Response {
+    // Only plain file names from the images directory
+    if file.starts_with('.') || !file.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let content_type = match file.rsplit_once('.').map(|(_, extension)| extension) {
+        Some("jpg") => "image/jpeg",
+        Some("png") => "image/png",
+        _ => return StatusCode::NOT_FOUND.into_response(),
+    };
+
+    let path = state.db.lock().await.images_dir().join(&file);
+    match tokio::fs::read(path).await {
+        Ok(bytes) => (
+            [
+                (header::CONTENT_TYPE, content_type),
+                (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
@@ -1,8 +1,26 @@
-use axum::{Json, extract::{State, Path}};
+use axum::{Json, extract::{State, Path, Query}};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use crate::api::AppState;
+use crate::api::auth::is_manager;
+use crate::api::images::thumbnail_url;
+use crate::api::locations::{default_location_id, StockMovement};
+use crate::api::pricing::PriceUpdate;
+use crate::api::reservations::stock_reservation_summary;
+use crate::api::settings::get_setting;
+use crate::api::units::{resolve_quantity, unit_exists};
 use crate::models::{ApiResponse, Product};
 
+/// Every (ancestor_id, category_id) pair of the category tree, each category being its own ancestor.
+/// Prefix a query with it to filter or group by whole subtrees.
+pub const CATEGORY_TREE: &str = r#"
+    WITH RECURSIVE category_tree(ancestor_id, category_id) AS (
+        SELECT id, id FROM categories
+        UNION
+        SELECT t.ancestor_id, c.id FROM categories c JOIN category_tree t ON c.parent_id = t.category_id
+    )
+"#;
+
 #[derive(Serialize)]
 pub struct Category {
     pub id: String,
@@ -10,9 +28,61 @@ pub struct Category {
     pub description: Option<String>,
     pub color: Option<String>,
     pub icon: Option<String>,
+    pub parent_id: Option<String>,
     pub is_active: bool,
 }
 
+#[derive(Serialize)]
+pub struct CategoryNode {
+    #[serde(flatten)]
+    pub category: Category,
+    /// Active products in this category and all its subcategories.
+    pub product_count: i32,
+    pub children: Vec<CategoryNode>,
+}
+
+#[derive(Deserialize)]
+pub struct MoveCategoryRequest {
+    /// New parent; `None` makes it a top-level category.
+    pub parent_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateCategoryRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub color: Option<String>,
+    pub icon: Option<String>,
+    /// New parent; an empty string makes it a top-level category.
+    pub parent_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct DeactivateCategoryQuery {
+    /// Category that takes over the products still assigned; required when there are any.
+    pub reassign_to: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MergeCategoryRequest {
+    pub target_id: String,
+    pub user_id: String,
+}
+
+#[derive(Serialize)]
+pub struct CategoryMergeResult {
+    pub source_id: String,
+    pub target_id: String,
+    pub products_moved: u64,
+    pub subcategories_moved: u64,
+}
+
+#[derive(Deserialize)]
+pub struct ProductListQuery {
+    /// Only products in this category or any of its subcategories.
+    pub category_id: Option<String>,
+}
+
 #[derive(Deserialize)]
 pub struct CreateProductRequest {
     pub sku: String,
@@ -22,11 +92,20 @@ pub struct CreateProductRequest {
     pub category_id: Option<String>,
     pub price: f64,
     pub cost: f64,
-    pub stock: i32,
-    pub min_stock: i32,
-    pub max_stock: Option<i32>,
+    pub stock: f64,
+    pub min_stock: f64,
+    pub max_stock: Option<f64>,
     pub unit: String,
     pub tax_rate: Option<f64>,
+    /// Preferred supplier for replenishment.
+    pub supplier_id: Option<String>,
+    /// Net content (e.g. 600 mL) used for the unit price on shelf labels.
+    pub content_quantity: Option<f64>,
+    pub content_unit: Option<String>,
+    /// Scale PLU for variable-weight barcodes.
+    pub plu: Option<String>,
+    /// Lets checkout sell more than is available.
+    pub allow_negative_stock: Option<bool>,
 }
 
 #[derive(Deserialize)]
@@ -36,19 +115,47 @@ pub struct UpdateProductRequest {
     pub category_id: Option<String>,
     pub price: Option<f64>,
     pub cost: Option<f64>,
-    pub min_stock: Option<i32>,
-    pub max_stock: Option<i32>,
+    pub min_stock: Option<f64>,
+    pub max_stock: Option<f64>,
     pub unit: Option<String>,
+    pub supplier_id: Option<String>,
+    pub content_quantity: Option<f64>,
+    pub content_unit: Option<String>,
+    pub plu: Option<String>,
+    pub allow_negative_stock: Option<bool>,
     pub is_active: Option<bool>,
+    /// Recorded in the price history when price or cost change.
+    pub user_id: Option<String>,
+}
+
+/// Reason codes for outgoing adjustments, with their display names.
+pub const ADJUSTMENT_REASONS: [(&str, &str); 6] = [
+    ("damaged", "Dañado"),
+    ("expired", "Caducado"),
+    ("theft", "Robo"),
+    ("internal_use", "Consumo interno"),
+    ("supplier_return", "Devolución a proveedor"),
+    ("sample", "Muestra"),
+];
+
+#[derive(Serialize)]
+pub struct AdjustmentReason {
+    pub code: String,
+    pub name: String,
 }
 
 #[derive(Deserialize)]
 pub struct StockAdjustmentRequest {
     pub product_id: String,
-    pub quantity: i32,
+    pub quantity: f64,
+    /// Unit the quantity is expressed in; defaults to the product's unit.
+    pub unit: Option<String>,
     pub adjustment_type: String, // 'in' or 'out'
+    /// Required on outgoing adjustments; one of `ADJUSTMENT_REASONS`.
+    pub reason_code: Option<String>,
     pub notes: Option<String>,
     pub user_id: String,
+    pub location_id: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -56,8 +163,10 @@ pub struct InventoryMovement {
     pub id: String,
     pub product_id: String,
     pub product_name: String,
+    pub location_name: Option<String>,
     pub movement_type: String,
     pub quantity: f64,
+    pub reason_code: Option<String>,
     pub reference_id: Option<String>,
     pub notes: Option<String>,
     pub user_name: String,
@@ -75,11 +184,17 @@ pub struct ProductWithCategory {
     pub category_name: Option<String>,
     pub price: f64,
     pub cost: f64,
-    pub stock: i32,
-    pub min_stock: i32,
-    pub max_stock: Option<i32>,
+    pub stock: f64,
+    /// Same as `stock`; `available` is what is left once active reservations are set aside.
+    pub on_hand: f64,
+    pub reserved: f64,
+    pub available: f64,
+    pub allow_negative_stock: bool,
+    pub min_stock: f64,
+    pub max_stock: Option<f64>,
     pub unit: String,
     pub image_url: Option<String>,
+    pub thumbnail_url: Option<String>,
     pub is_active: bool,
     pub tax_rate: f64,
 }
@@ -90,12 +205,24 @@ pub async fn create_product(
 ) -> Json<ApiResponse<Product>> {
     let db = state.db.lock().await;
     
+    let known_unit = match db.pool().acquire().await {
+        Ok(mut conn) => unit_exists(&mut conn, &payload.unit).await.unwrap_or(false),
+        Err(_) => false,
+    };
+    if !known_unit {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Unidad desconocida: {}", payload.unit)),
+        });
+    }
+
     let product_id = uuid::Uuid::new_v4().to_string();
     
     let result = sqlx::query(
         r#"
-        INSERT INTO products (id, sku, barcode, name, description, category_id, price, cost, stock, min_stock, max_stock, unit, tax_rate, is_active, created_at, updated_at)
-        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1, datetime('now'), datetime('now'))
+        INSERT INTO products (id, sku, barcode, name, description, category_id, price, cost, stock, min_stock, max_stock, unit, tax_rate, supplier_id, content_quantity, content_unit, plu, allow_negative_stock, is_active, created_at, updated_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1, datetime('now'), datetime('now'))
         "#
     )
     .bind(&product_id)
@@ -111,11 +238,25 @@ pub async fn create_product(
     .bind(payload.max_stock)
     .bind(&payload.unit)
     .bind(payload.tax_rate.unwrap_or(0.16))
+    .bind(&payload.supplier_id)
+    .bind(payload.content_quantity)
+    .bind(&payload.content_unit)
+    .bind(&payload.plu)
+    .bind(if payload.allow_negative_stock.unwrap_or(false) { 1 } else { 0 })
     .execute(db.pool())
     .await;
 
     match result {
         Ok(_) => {
+            // Opening stock lives at the default location
+            let _ = sqlx::query(
+                "INSERT INTO product_stock (product_id, location_id, quantity) SELECT ?, id, ? FROM locations WHERE is_default = 1"
+            )
+            .bind(&product_id)
+            .bind(payload.stock)
+            .execute(db.pool())
+            .await;
+
             let product = Product {
                 id: product_id,
                 sku: payload.sku,
@@ -152,7 +293,21 @@ pub async fn update_product(
     Json(payload): Json<UpdateProductRequest>,
 ) -> Json<ApiResponse<String>> {
     let db = state.db.lock().await;
-    
+
+    if let Some(unit) = &payload.unit {
+        let known_unit = match db.pool().acquire().await {
+            Ok(mut conn) => unit_exists(&mut conn, unit).await.unwrap_or(false),
+            Err(_) => false,
+        };
+        if !known_unit {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some(format!("Unidad desconocida: {}", unit)),
+            });
+        }
+    }
+
     let mut query = String::from("UPDATE products SET updated_at = datetime('now')");
     let mut bindings: Vec<String> = Vec::new();
     
@@ -168,12 +323,6 @@ pub async fn update_product(
         query.push_str(", category_id = ?");
         bindings.push(cat_id.clone());
     }
-    if let Some(price) = payload.price {
-        query.push_str(&format!(", price = {}", price));
-    }
-    if let Some(cost) = payload.cost {
-        query.push_str(&format!(", cost = {}", cost));
-    }
     if let Some(min_stock) = payload.min_stock {
         query.push_str(&format!(", min_stock = {}", min_stock));
     }
@@ -184,17 +333,57 @@ pub async fn update_product(
         query.push_str(", unit = ?");
         bindings.push(unit.clone());
     }
+    if let Some(supplier_id) = &payload.supplier_id {
+        query.push_str(", supplier_id = ?");
+        bindings.push(supplier_id.clone());
+    }
+    if let Some(content_quantity) = payload.content_quantity {
+        query.push_str(&format!(", content_quantity = {}", content_quantity));
+    }
+    if let Some(content_unit) = &payload.content_unit {
+        query.push_str(", content_unit = ?");
+        bindings.push(content_unit.clone());
+    }
+    if let Some(plu) = &payload.plu {
+        query.push_str(", plu = ?");
+        bindings.push(plu.clone());
+    }
+    if let Some(allow_negative_stock) = payload.allow_negative_stock {
+        query.push_str(&format!(", allow_negative_stock = {}", if allow_negative_stock { 1 } else { 0 }));
+    }
     if let Some(is_active) = payload.is_active {
         query.push_str(&format!(", is_active = {}", if is_active { 1 } else { 0 }));
     }
     
     query.push_str(" WHERE id = ?");
-    bindings.push(product_id);
+    bindings.push(product_id.clone());
     
-    let result = sqlx::query(&query)
-        .bind(&bindings[0])
-        .execute(db.pool())
-        .await;
+    let result = async {
+        let mut tx = db.pool().begin().await?;
+
+        let mut update = sqlx::query(&query);
+        for binding in &bindings {
+            update = update.bind(binding);
+        }
+        update.execute(&mut *tx).await?;
+
+        // Price and cost go through the history log
+        if payload.price.is_some() || payload.cost.is_some() {
+            PriceUpdate {
+                product_id: &product_id,
+                price: payload.price,
+                cost: payload.cost,
+                source: "manual",
+                reference_id: None,
+                user_id: payload.user_id.as_deref(),
+            }
+            .apply(&mut tx)
+            .await?;
+        }
+
+        tx.commit().await
+    }
+    .await;
 
     match result {
         Ok(_) => Json(ApiResponse {
@@ -239,30 +428,38 @@ pub async fn delete_product(
 
 pub async fn list_products_with_categories(
     State(state): State<AppState>,
+    Query(params): Query<ProductListQuery>,
 ) -> Json<ApiResponse<Vec<ProductWithCategory>>> {
     let db = state.db.lock().await;
     
-    // FIX: Cambiar tipo de max_stock de String a i32
-    let result: Result<Vec<(String, String, String, String, String, String, String, f64, f64, i32, i32, i32, String, String, i32, f64)>, sqlx::Error> = 
-        sqlx::query_as(
+    let result: Result<Vec<(String, String, String, String, String, String, String, f64, f64, f64, f64, f64, String, String, i32, f64)>, sqlx::Error> = 
+        sqlx::query_as(&format!(
             r#"
+            {}
             SELECT p.id, p.sku, COALESCE(p.barcode, ''), p.name, COALESCE(p.description, ''), 
                    COALESCE(p.category_id, ''), COALESCE(c.name, ''),
-                   p.price, p.cost, p.stock, p.min_stock, COALESCE(p.max_stock, 0),
+                   p.price, p.cost, p.stock, p.min_stock, COALESCE(p.max_stock, 0.0),
                    p.unit, COALESCE(p.image_url, ''), p.is_active, p.tax_rate
             FROM products p
             LEFT JOIN categories c ON p.category_id = c.id
+            WHERE ? IS NULL OR p.category_id IN (SELECT category_id FROM category_tree WHERE ancestor_id = ?)
             ORDER BY p.name
-            "#
-        )
+            "#,
+            CATEGORY_TREE
+        ))
+        .bind(&params.category_id)
+        .bind(&params.category_id)
         .fetch_all(db.pool())
         .await;
 
+    let reservations = stock_reservation_summary(db.pool()).await.unwrap_or_default();
+
     match result {
         Ok(rows) => {
             let products: Vec<ProductWithCategory> = rows
                 .into_iter()
                 .map(|(id, sku, barcode, name, description, category_id, category_name, price, cost, stock, min_stock, max_stock, unit, image_url, is_active, tax_rate)| {
+                    let (reserved, allow_negative_stock) = reservations.get(&id).copied().unwrap_or((0.0, false));
                     ProductWithCategory {
                         id,
                         sku,
@@ -274,9 +471,14 @@ pub async fn list_products_with_categories(
                         price,
                         cost,
                         stock,
+                        on_hand: stock,
+                        reserved,
+                        available: stock - reserved,
+                        allow_negative_stock,
                         min_stock,
-                        max_stock: if max_stock == 0 { None } else { Some(max_stock) },
+                        max_stock: if max_stock == 0.0 { None } else { Some(max_stock) },
                         unit,
+                        thumbnail_url: if image_url.is_empty() { None } else { Some(thumbnail_url(&image_url)) },
                         image_url: if image_url.is_empty() { None } else { Some(image_url) },
                         is_active: is_active == 1,
                         tax_rate,
@@ -307,48 +509,107 @@ pub async fn adjust_stock(
     let movement_type = match payload.adjustment_type.as_str() {
         "in" => "adjustment_in",
         "out" => "adjustment_out",
-        _ => "adjustment",
+        other => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some(format!("Tipo de ajuste inválido: '{}' (use in u out)", other)),
+            });
+        }
     };
     
-    let quantity_change = if payload.adjustment_type == "in" {
-        payload.quantity
-    } else {
-        -payload.quantity
+    if payload.quantity <= 0.0 {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("La cantidad debe ser mayor a cero".to_string()),
+        });
+    }
+
+    let reason_code = match (movement_type, payload.reason_code.as_deref()) {
+        ("adjustment_out", Some(code)) if ADJUSTMENT_REASONS.iter().any(|(c, _)| *c == code) => Some(code),
+        ("adjustment_out", _) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some("Indica un motivo válido para la salida de inventario".to_string()),
+            });
+        }
+        _ => None,
     };
+    let manager = is_manager(db.pool(), &payload.user_id).await;
     
-    // Update stock
-    let result = sqlx::query(
-        "UPDATE products SET stock = stock + ?, updated_at = datetime('now') WHERE id = ?"
-    )
-    .bind(quantity_change)
-    .bind(&payload.product_id)
-    .execute(db.pool())
-    .await;
+    let result = async {
+        let mut tx = db.pool().begin().await.map_err(|e| e.to_string())?;
 
-    match result {
-        Ok(_) => {
-            // Record movement
-            let _ = sqlx::query(
-                r#"
-                INSERT INTO inventory_movements (id, product_id, type, quantity, notes, user_id, created_at)
-                VALUES (?, ?, ?, ?, ?, ?, datetime('now'))
-                "#
-            )
-            .bind(uuid::Uuid::new_v4().to_string())
-            .bind(&payload.product_id)
-            .bind(movement_type)
-            .bind(payload.quantity as f64)
-            .bind(&payload.notes)
-            .bind(&payload.user_id)
-            .execute(db.pool())
-            .await;
+        let resolved = resolve_quantity(&mut tx, &payload.product_id, payload.unit.as_deref(), payload.quantity).await?;
 
-            Json(ApiResponse {
-                success: true,
-                data: Some("Stock ajustado".to_string()),
-                message: Some("Stock actualizado exitosamente".to_string()),
+        if reason_code.is_some() && !manager {
+            let threshold = get_setting(&mut tx, "adjustment_approval_threshold")
+                .await
+                .map_err(|e| e.to_string())?
+                .and_then(|value| value.parse::<f64>().ok())
+                .unwrap_or(f64::INFINITY);
+            let (cost,): (f64,) = sqlx::query_as("SELECT cost FROM products WHERE id = ?")
+                .bind(&payload.product_id)
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            let value = resolved.base_quantity * cost;
+            if value > threshold {
+                return Err(format!(
+                    "Salidas mayores a ${:.2} a costo requieren un gerente (este ajuste: ${:.2})",
+                    threshold, value
+                ));
+            }
+        }
+        let quantity_change = if movement_type == "adjustment_in" {
+            resolved.base_quantity
+        } else {
+            -resolved.base_quantity
+        };
+
+        let location_id = match &payload.location_id {
+            Some(location_id) => location_id.clone(),
+            None => default_location_id(&mut tx).await.map_err(|e| e.to_string())?,
+        };
+
+        let notes = if resolved.factor == 1.0 {
+            payload.notes.clone()
+        } else {
+            let conversion = format!("{} {} × {}", payload.quantity, resolved.unit, resolved.factor);
+            Some(match &payload.notes {
+                Some(notes) => format!("{} ({})", notes, conversion),
+                None => conversion,
             })
+        };
+
+        StockMovement {
+            product_id: &payload.product_id,
+            location_id: &location_id,
+            movement_type,
+            delta: quantity_change,
+            quantity: resolved.base_quantity,
+            reference_id: None,
+            notes: notes.as_deref(),
+            user_id: &payload.user_id,
+            reason_code,
         }
+        .post(&mut tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        tx.commit().await.map_err(|e| e.to_string())
+    }
+    .await;
+
+    match result {
+        Ok(_) => Json(ApiResponse {
+            success: true,
+            data: Some("Stock ajustado".to_string()),
+            message: Some("Stock actualizado exitosamente".to_string()),
+        }),
         Err(e) => Json(ApiResponse {
             success: false,
             data: None,
@@ -357,20 +618,37 @@ pub async fn adjust_stock(
     }
 }
 
+pub async fn list_adjustment_reasons() -> Json<ApiResponse<Vec<AdjustmentReason>>> {
+    Json(ApiResponse {
+        success: true,
+        data: Some(
+            ADJUSTMENT_REASONS
+                .iter()
+                .map(|(code, name)| AdjustmentReason {
+                    code: code.to_string(),
+                    name: name.to_string(),
+                })
+                .collect(),
+        ),
+        message: None,
+    })
+}
+
 pub async fn list_movements(
     State(state): State<AppState>,
 ) -> Json<ApiResponse<Vec<InventoryMovement>>> {
     let db = state.db.lock().await;
     
-    let result: Result<Vec<(String, String, String, String, f64, String, String, String, String)>, sqlx::Error> = 
+    let result: Result<Vec<(String, String, String, String, String, f64, String, String, String, String, String)>, sqlx::Error> = 
         sqlx::query_as(
             r#"
-            SELECT m.id, m.product_id, p.name, m.type, m.quantity, 
-                   COALESCE(m.reference_id, ''), COALESCE(m.notes, ''),
+            SELECT m.id, m.product_id, p.name, COALESCE(l.name, ''), m.type, m.quantity, 
+                   COALESCE(m.reason_code, ''), COALESCE(m.reference_id, ''), COALESCE(m.notes, ''),
                    u.full_name, m.created_at
             FROM inventory_movements m
             JOIN products p ON m.product_id = p.id
             JOIN users u ON m.user_id = u.id
+            LEFT JOIN locations l ON m.location_id = l.id
             ORDER BY m.created_at DESC
             LIMIT 100
             "#
@@ -382,13 +660,15 @@ pub async fn list_movements(
         Ok(rows) => {
             let movements: Vec<InventoryMovement> = rows
                 .into_iter()
-                .map(|(id, product_id, product_name, movement_type, quantity, reference_id, notes, user_name, created_at)| {
+                .map(|(id, product_id, product_name, location_name, movement_type, quantity, reason_code, reference_id, notes, user_name, created_at)| {
                     InventoryMovement {
                         id,
                         product_id,
                         product_name,
+                        location_name: if location_name.is_empty() { None } else { Some(location_name) },
                         movement_type,
                         quantity,
+                        reason_code: if reason_code.is_empty() { None } else { Some(reason_code) },
                         reference_id: if reference_id.is_empty() { None } else { Some(reference_id) },
                         notes: if notes.is_empty() { None } else { Some(notes) },
                         user_name,
@@ -416,26 +696,11 @@ pub async fn list_categories(
 ) -> Json<ApiResponse<Vec<Category>>> {
     let db = state.db.lock().await;
     
-    let result: Result<Vec<(String, String, String, String, String, i32)>, sqlx::Error> = 
-        sqlx::query_as(
-            "SELECT id, name, COALESCE(description, ''), COALESCE(color, ''), COALESCE(icon, ''), is_active FROM categories ORDER BY name"
-        )
-        .fetch_all(db.pool())
-        .await;
+    let result = fetch_categories(db.pool()).await;
 
     match result {
         Ok(rows) => {
-            let categories: Vec<Category> = rows
-                .into_iter()
-                .map(|(id, name, description, color, icon, is_active)| Category {
-                    id,
-                    name,
-                    description: if description.is_empty() { None } else { Some(description) },
-                    color: if color.is_empty() { None } else { Some(color) },
-                    icon: if icon.is_empty() { None } else { Some(icon) },
-                    is_active: is_active == 1,
-                })
-                .collect();
+            let categories: Vec<Category> = rows.into_iter().map(|(category, _)| category).collect();
 
             Json(ApiResponse {
                 success: true,
@@ -457,6 +722,161 @@ pub struct CreateCategoryRequest {
     pub description: Option<String>,
     pub color: Option<String>,
     pub icon: Option<String>,
+    pub parent_id: Option<String>,
+}
+
+type CategoryRow = (String, String, String, String, String, String, i32, i32);
+
+/// All categories with the number of active products directly in each.
+async fn fetch_categories(pool: &sqlx::Pool<sqlx::Sqlite>) -> Result<Vec<(Category, i32)>, sqlx::Error> {
+    let rows: Vec<CategoryRow> = sqlx::query_as(
+        r#"
+        SELECT c.id, c.name, COALESCE(c.description, ''), COALESCE(c.color, ''), COALESCE(c.icon, ''),
+               COALESCE(c.parent_id, ''), c.is_active,
+               (SELECT COUNT(*) FROM products p WHERE p.category_id = c.id AND p.is_active = 1)
+        FROM categories c
+        ORDER BY c.name
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(id, name, description, color, icon, parent_id, is_active, product_count)| {
+            (
+                Category {
+                    id,
+                    name,
+                    description: if description.is_empty() { None } else { Some(description) },
+                    color: if color.is_empty() { None } else { Some(color) },
+                    icon: if icon.is_empty() { None } else { Some(icon) },
+                    parent_id: if parent_id.is_empty() { None } else { Some(parent_id) },
+                    is_active: is_active == 1,
+                },
+                product_count,
+            )
+        })
+        .collect())
+}
+
+fn build_category_nodes(
+    parent_id: Option<&str>,
+    children: &mut HashMap<Option<String>, Vec<(Category, i32)>>,
+) -> Vec<CategoryNode> {
+    children
+        .remove(&parent_id.map(str::to_string))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(category, own_products)| {
+            let nodes = build_category_nodes(Some(&category.id), children);
+            CategoryNode {
+                product_count: own_products + nodes.iter().map(|n| n.product_count).sum::<i32>(),
+                category,
+                children: nodes,
+            }
+        })
+        .collect()
+}
+
+pub async fn get_category_tree(
+    State(state): State<AppState>,
+) -> Json<ApiResponse<Vec<CategoryNode>>> {
+    let db = state.db.lock().await;
+
+    match fetch_categories(db.pool()).await {
+        Ok(rows) => {
+            let ids: HashSet<String> = rows.iter().filter(|(c, _)| c.is_active).map(|(c, _)| c.id.clone()).collect();
+            let mut children: HashMap<Option<String>, Vec<(Category, i32)>> = HashMap::new();
+            for (category, product_count) in rows.into_iter().filter(|(c, _)| c.is_active) {
+                // Categories whose parent is gone are shown at the top level
+                let parent = category.parent_id.clone().filter(|p| ids.contains(p));
+                children.entry(parent).or_default().push((category, product_count));
+            }
+
+            Json(ApiResponse {
+                success: true,
+                data: Some(build_category_nodes(None, &mut children)),
+                message: None,
+            })
+        }
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+async fn category_exists(pool: &sqlx::Pool<sqlx::Sqlite>, category_id: &str) -> bool {
+    sqlx::query_as::<_, (i32,)>("SELECT COUNT(*) FROM categories WHERE id = ?")
+        .bind(category_id)
+        .fetch_one(pool)
+        .await
+        .map(|(count,)| count > 0)
+        .unwrap_or(false)
+}
+
+/// Whether `category_id` is `ancestor_id` or one of its descendants.
+async fn in_category_subtree(pool: &sqlx::Pool<sqlx::Sqlite>, ancestor_id: &str, category_id: &str) -> bool {
+    sqlx::query_as::<_, (i32,)>(&format!(
+        "{} SELECT COUNT(*) FROM category_tree WHERE ancestor_id = ? AND category_id = ?",
+        CATEGORY_TREE
+    ))
+    .bind(ancestor_id)
+    .bind(category_id)
+    .fetch_one(pool)
+    .await
+    .map_or(true, |(count,)| count > 0)
+}
+
+pub async fn move_category(
+    State(state): State<AppState>,
+    Path(category_id): Path<String>,
+    Json(payload): Json<MoveCategoryRequest>,
+) -> Json<ApiResponse<String>> {
+    let db = state.db.lock().await;
+
+    let error = |message: &str| {
+        Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(message.to_string()),
+        })
+    };
+
+    if !category_exists(db.pool(), &category_id).await {
+        return error("Categoría no encontrada");
+    }
+    if let Some(parent_id) = &payload.parent_id {
+        if !category_exists(db.pool(), parent_id).await {
+            return error("Categoría padre no encontrada");
+        }
+
+        // The new parent can't be the category itself or anything below it
+        if in_category_subtree(db.pool(), &category_id, parent_id).await {
+            return error("No se puede mover una categoría dentro de sí misma o de una subcategoría");
+        }
+    }
+
+    let result = sqlx::query("UPDATE categories SET parent_id = ? WHERE id = ?")
+        .bind(&payload.parent_id)
+        .bind(&category_id)
+        .execute(db.pool())
+        .await;
+
+    match result {
+        Ok(_) => Json(ApiResponse {
+            success: true,
+            data: Some(category_id),
+            message: Some("Categoría movida exitosamente".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
 }
 
 pub async fn create_category(
@@ -464,13 +884,23 @@ pub async fn create_category(
     Json(payload): Json<CreateCategoryRequest>,
 ) -> Json<ApiResponse<Category>> {
     let db = state.db.lock().await;
+
+    if let Some(parent_id) = &payload.parent_id {
+        if !category_exists(db.pool(), parent_id).await {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some("Categoría padre no encontrada".to_string()),
+            });
+        }
+    }
     
     let category_id = uuid::Uuid::new_v4().to_string();
     
     let result = sqlx::query(
         r#"
-        INSERT INTO categories (id, name, description, color, icon, is_active, created_at)
-        VALUES (?, ?, ?, ?, ?, 1, datetime('now'))
+        INSERT INTO categories (id, name, description, color, icon, parent_id, is_active, created_at)
+        VALUES (?, ?, ?, ?, ?, ?, 1, datetime('now'))
         "#
     )
     .bind(&category_id)
@@ -478,6 +908,7 @@ pub async fn create_category(
     .bind(&payload.description)
     .bind(&payload.color)
     .bind(&payload.icon)
+    .bind(&payload.parent_id)
     .execute(db.pool())
     .await;
 
@@ -489,6 +920,7 @@ pub async fn create_category(
                 description: payload.description,
                 color: payload.color,
                 icon: payload.icon,
+                parent_id: payload.parent_id,
                 is_active: true,
             };
 
@@ -506,18 +938,285 @@ pub async fn create_category(
     }
 }
 
+pub async fn update_category(
+    State(state): State<AppState>,
+    Path(category_id): Path<String>,
+    Json(payload): Json<UpdateCategoryRequest>,
+) -> Json<ApiResponse<String>> {
+    let db = state.db.lock().await;
+
+    let error = |message: &str| {
+        Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(message.to_string()),
+        })
+    };
+
+    if !category_exists(db.pool(), &category_id).await {
+        return error("Categoría no encontrada");
+    }
+    if payload.name.as_deref().is_some_and(|name| name.trim().is_empty()) {
+        return error("El nombre no puede estar vacío");
+    }
+
+    let mut query = String::from("UPDATE categories SET id = id");
+    let mut bindings: Vec<Option<String>> = Vec::new();
+
+    if let Some(name) = &payload.name {
+        query.push_str(", name = ?");
+        bindings.push(Some(name.trim().to_string()));
+    }
+    if let Some(description) = &payload.description {
+        query.push_str(", description = ?");
+        bindings.push(Some(description.clone()));
+    }
+    if let Some(color) = &payload.color {
+        query.push_str(", color = ?");
+        bindings.push(Some(color.clone()));
+    }
+    if let Some(icon) = &payload.icon {
+        query.push_str(", icon = ?");
+        bindings.push(Some(icon.clone()));
+    }
+    if let Some(parent_id) = &payload.parent_id {
+        if parent_id.is_empty() {
+            query.push_str(", parent_id = ?");
+            bindings.push(None);
+        } else if !category_exists(db.pool(), parent_id).await {
+            return error("Categoría padre no encontrada");
+        } else if in_category_subtree(db.pool(), &category_id, parent_id).await {
+            return error("No se puede mover una categoría dentro de sí misma o de una subcategoría");
+        } else {
+            query.push_str(", parent_id = ?");
+            bindings.push(Some(parent_id.clone()));
+        }
+    }
+
+    query.push_str(" WHERE id = ?");
+    bindings.push(Some(category_id.clone()));
+
+    let mut update = sqlx::query(&query);
+    for binding in &bindings {
+        update = update.bind(binding);
+    }
+
+    match update.execute(db.pool()).await {
+        Ok(_) => Json(ApiResponse {
+            success: true,
+            data: Some(category_id),
+            message: Some("Categoría actualizada exitosamente".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+/// Soft-deletes a category. Its subcategories move up to its parent, and products still
+/// assigned to it must be handed to another category.
+pub async fn deactivate_category(
+    State(state): State<AppState>,
+    Path(category_id): Path<String>,
+    Query(params): Query<DeactivateCategoryQuery>,
+) -> Json<ApiResponse<String>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        let mut tx = db.pool().begin().await.map_err(|e| e.to_string())?;
+
+        let (parent_id, is_active): (String, i32) = sqlx::query_as(
+            "SELECT COALESCE(parent_id, ''), is_active FROM categories WHERE id = ?"
+        )
+        .bind(&category_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Categoría no encontrada".to_string())?;
+        if is_active == 0 {
+            return Err("La categoría ya está desactivada".to_string());
+        }
+
+        let (assigned,): (i32,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM products WHERE category_id = ? AND is_active = 1"
+        )
+        .bind(&category_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        match &params.reassign_to {
+            Some(target_id) => {
+                let target: Option<(i32,)> = sqlx::query_as("SELECT is_active FROM categories WHERE id = ?")
+                    .bind(target_id)
+                    .fetch_optional(&mut *tx)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                if target_id == &category_id || target.is_none_or(|(active,)| active == 0) {
+                    return Err("La categoría destino no es válida".to_string());
+                }
+
+                sqlx::query("UPDATE products SET category_id = ?, updated_at = datetime('now') WHERE category_id = ?")
+                    .bind(target_id)
+                    .bind(&category_id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            None if assigned > 0 => {
+                return Err(format!(
+                    "La categoría tiene {} productos activos; indica a qué categoría reasignarlos",
+                    assigned
+                ));
+            }
+            None => {}
+        }
+
+        sqlx::query("UPDATE categories SET parent_id = ? WHERE parent_id = ?")
+            .bind(if parent_id.is_empty() { None } else { Some(&parent_id) })
+            .bind(&category_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        sqlx::query("UPDATE categories SET is_active = 0 WHERE id = ?")
+            .bind(&category_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        tx.commit().await.map_err(|e| e.to_string())
+    }
+    .await;
+
+    match result {
+        Ok(_) => Json(ApiResponse {
+            success: true,
+            data: Some(category_id),
+            message: Some("Categoría desactivada exitosamente".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        }),
+    }
+}
+
+/// Folds a category into another: products and subcategories move to the target and the
+/// source is deactivated. Sales keep the category they were made under.
+pub async fn merge_category(
+    State(state): State<AppState>,
+    Path(source_id): Path<String>,
+    Json(payload): Json<MergeCategoryRequest>,
+) -> Json<ApiResponse<CategoryMergeResult>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        if source_id == payload.target_id {
+            return Err("No se puede fusionar una categoría consigo misma".to_string());
+        }
+        if !category_exists(db.pool(), &source_id).await {
+            return Err("Categoría no encontrada".to_string());
+        }
+        let target: Option<(i32,)> = sqlx::query_as("SELECT is_active FROM categories WHERE id = ?")
+            .bind(&payload.target_id)
+            .fetch_optional(db.pool())
+            .await
+            .map_err(|e| e.to_string())?;
+        if target.is_none_or(|(active,)| active == 0) {
+            return Err("La categoría destino no es válida".to_string());
+        }
+        // Its subcategories would end up below themselves
+        if in_category_subtree(db.pool(), &source_id, &payload.target_id).await {
+            return Err("No se puede fusionar una categoría con una de sus subcategorías".to_string());
+        }
+
+        let mut tx = db.pool().begin().await.map_err(|e| e.to_string())?;
+
+        let products_moved = sqlx::query(
+            "UPDATE products SET category_id = ?, updated_at = datetime('now') WHERE category_id = ?"
+        )
+        .bind(&payload.target_id)
+        .bind(&source_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .rows_affected();
+
+        let subcategories_moved = sqlx::query("UPDATE categories SET parent_id = ? WHERE parent_id = ?")
+            .bind(&payload.target_id)
+            .bind(&source_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?
+            .rows_affected();
+
+        sqlx::query("UPDATE categories SET is_active = 0 WHERE id = ?")
+            .bind(&source_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO audit_logs (id, user_id, action, entity_type, entity_id, changes, created_at)
+            VALUES (?, ?, 'merge', 'category', ?, ?, datetime('now'))
+            "#
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(&payload.user_id)
+        .bind(&source_id)
+        .bind(
+            serde_json::json!({
+                "target_id": payload.target_id,
+                "products_moved": products_moved,
+                "subcategories_moved": subcategories_moved,
+            })
+            .to_string(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        Ok(CategoryMergeResult {
+            source_id: source_id.clone(),
+            target_id: payload.target_id.clone(),
+            products_moved,
+            subcategories_moved,
+        })
+    }
+    .await;
+
+    match result {
+        Ok(merge) => Json(ApiResponse {
+            success: true,
+            data: Some(merge),
+            message: Some("Categorías fusionadas exitosamente".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        }),
+    }
+}
+
 pub async fn get_low_stock_products(
     State(state): State<AppState>,
 ) -> Json<ApiResponse<Vec<ProductWithCategory>>> {
     let db = state.db.lock().await;
     
-    // FIX: Cambiar tipo de max_stock de String a i32
-    let result: Result<Vec<(String, String, String, String, String, String, String, f64, f64, i32, i32, i32, String, String, i32, f64)>, sqlx::Error> = 
+    let result: Result<Vec<(String, String, String, String, String, String, String, f64, f64, f64, f64, f64, String, String, i32, f64)>, sqlx::Error> = 
         sqlx::query_as(
             r#"
             SELECT p.id, p.sku, COALESCE(p.barcode, ''), p.name, COALESCE(p.description, ''), 
                    COALESCE(p.category_id, ''), COALESCE(c.name, ''),
-                   p.price, p.cost, p.stock, p.min_stock, COALESCE(p.max_stock, 0),
+                   p.price, p.cost, p.stock, p.min_stock, COALESCE(p.max_stock, 0.0),
                    p.unit, COALESCE(p.image_url, ''), p.is_active, p.tax_rate
             FROM products p
             LEFT JOIN categories c ON p.category_id = c.id
@@ -528,11 +1227,14 @@ pub async fn get_low_stock_products(
         .fetch_all(db.pool())
         .await;
 
+    let reservations = stock_reservation_summary(db.pool()).await.unwrap_or_default();
+
     match result {
         Ok(rows) => {
             let products: Vec<ProductWithCategory> = rows
                 .into_iter()
                 .map(|(id, sku, barcode, name, description, category_id, category_name, price, cost, stock, min_stock, max_stock, unit, image_url, is_active, tax_rate)| {
+                    let (reserved, allow_negative_stock) = reservations.get(&id).copied().unwrap_or((0.0, false));
                     ProductWithCategory {
                         id,
                         sku,
@@ -544,9 +1246,14 @@ pub async fn get_low_stock_products(
                         price,
                         cost,
                         stock,
+                        on_hand: stock,
+                        reserved,
+                        available: stock - reserved,
+                        allow_negative_stock,
                         min_stock,
-                        max_stock: if max_stock == 0 { None } else { Some(max_stock) },
+                        max_stock: if max_stock == 0.0 { None } else { Some(max_stock) },
                         unit,
+                        thumbnail_url: if image_url.is_empty() { None } else { Some(thumbnail_url(&image_url)) },
                         image_url: if image_url.is_empty() { None } else { Some(image_url) },
                         is_active: is_active == 1,
                         tax_rate,
@@ -0,0 +1,581 @@
+use axum::{Json, extract::{State, Path, Query}};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use crate::api::AppState;
+use crate::api::locations::{default_location_id, location_quantity, StockMovement};
+use crate::api::pricing::PriceUpdate;
+use crate::api::purchasing::weighted_average_cost;
+use crate::api::units::resolve_quantity;
+use crate::models::ApiResponse;
+
+#[derive(Serialize)]
+pub struct KitComponent {
+    pub component_id: String,
+    pub sku: String,
+    pub name: String,
+    pub unit: String,
+    /// Base units of the component consumed per kit.
+    pub quantity: f64,
+    pub available: f64,
+    pub cost: f64,
+}
+
+#[derive(Serialize)]
+pub struct KitDetail {
+    pub kit_id: String,
+    pub sku: String,
+    pub name: String,
+    pub location_id: String,
+    /// Kits already assembled and on hand at the location.
+    pub assembled_stock: f64,
+    /// Kits that can still be made from the components on hand.
+    pub buildable: f64,
+    pub available: f64,
+    pub component_cost: f64,
+    pub components: Vec<KitComponent>,
+}
+
+#[derive(Deserialize)]
+pub struct KitQuery {
+    pub location_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SetComponentsRequest {
+    /// Replaces the whole recipe; an empty list turns the kit back into a regular product.
+    pub components: Vec<ComponentRequest>,
+}
+
+#[derive(Deserialize)]
+pub struct ComponentRequest {
+    pub product_id: String,
+    /// Quantity per kit, in `unit` or the component's own unit.
+    pub quantity: f64,
+    pub unit: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AssemblyRequest {
+    pub quantity: f64,
+    pub user_id: String,
+    pub location_id: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct KitAssembly {
+    pub id: String,
+    pub assembly_number: String,
+    pub operation: String,
+    pub quantity: f64,
+    pub kit: KitDetail,
+}
+
+/// Recipe of a kit as (component_id, component name, base units per kit).
+pub async fn kit_components(
+    conn: &mut SqliteConnection,
+    kit_id: &str,
+) -> Result<Vec<(String, String, f64)>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        SELECT pc.component_id, p.name, pc.quantity
+        FROM product_components pc
+        JOIN products p ON pc.component_id = p.id
+        WHERE pc.kit_id = ?
+        ORDER BY p.name
+        "#
+    )
+    .bind(kit_id)
+    .fetch_all(conn)
+    .await
+}
+
+/// Takes a sold product out of stock. Kits use their assembled stock first and consume
+/// components by recipe for the rest.
+pub async fn consume_for_sale(
+    conn: &mut SqliteConnection,
+    product_id: &str,
+    location_id: &str,
+    quantity: f64,
+    sale_id: &str,
+    sale_number: &str,
+    user_id: &str,
+) -> Result<(), sqlx::Error> {
+    let components = kit_components(&mut *conn, product_id).await?;
+
+    let from_stock = if components.is_empty() {
+        quantity
+    } else {
+        location_quantity(&mut *conn, product_id, location_id)
+            .await?
+            .max(0.0)
+            .min(quantity)
+    };
+
+    if from_stock > 0.0 || components.is_empty() {
+        StockMovement {
+            product_id,
+            location_id,
+            movement_type: "sale",
+            delta: -from_stock,
+            quantity: from_stock,
+            reference_id: Some(sale_id),
+            notes: Some(sale_number),
+            user_id,
+            reason_code: None,
+        }
+        .post(&mut *conn)
+        .await?;
+    }
+
+    let to_build = quantity - from_stock;
+    if to_build <= 0.0 {
+        return Ok(());
+    }
+
+    let (kit_name,): (String,) = sqlx::query_as("SELECT name FROM products WHERE id = ?")
+        .bind(product_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    for (component_id, _, per_kit) in &components {
+        let used = per_kit * to_build;
+        StockMovement {
+            product_id: component_id,
+            location_id,
+            movement_type: "sale",
+            delta: -used,
+            quantity: used,
+            reference_id: Some(sale_id),
+            notes: Some(&format!("{} (kit {})", sale_number, kit_name)),
+            user_id,
+            reason_code: None,
+        }
+        .post(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+async fn fetch_kit(
+    conn: &mut SqliteConnection,
+    kit_id: &str,
+    location_id: &str,
+) -> Result<KitDetail, sqlx::Error> {
+    let (sku, name, allows_fraction): (String, String, i32) = sqlx::query_as(
+        r#"
+        SELECT p.sku, p.name, COALESCE(u.allows_fraction, 1)
+        FROM products p
+        LEFT JOIN units u ON u.code = p.unit
+        WHERE p.id = ?
+        "#
+    )
+    .bind(kit_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let rows: Vec<(String, String, String, String, f64, f64, f64)> = sqlx::query_as(
+        r#"
+        SELECT pc.component_id, p.sku, p.name, p.unit, pc.quantity, p.cost,
+               COALESCE((SELECT ps.quantity FROM product_stock ps
+                         WHERE ps.product_id = pc.component_id AND ps.location_id = ?), 0.0)
+        FROM product_components pc
+        JOIN products p ON pc.component_id = p.id
+        WHERE pc.kit_id = ?
+        ORDER BY p.name
+        "#
+    )
+    .bind(location_id)
+    .bind(kit_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let components: Vec<KitComponent> = rows
+        .into_iter()
+        .map(|(component_id, sku, name, unit, quantity, cost, available)| KitComponent {
+            component_id,
+            sku,
+            name,
+            unit,
+            quantity,
+            available,
+            cost,
+        })
+        .collect();
+
+    // The scarcest component limits how many kits can be made
+    let buildable = components
+        .iter()
+        .map(|c| (c.available / c.quantity).max(0.0))
+        .fold(None, |min: Option<f64>, n| Some(min.map_or(n, |m| m.min(n))))
+        .map(|n| if allows_fraction == 1 { n } else { n.floor() })
+        .unwrap_or(0.0);
+    let component_cost = components.iter().fold(0.0, |total, c| total + c.quantity * c.cost);
+
+    let assembled_stock = location_quantity(&mut *conn, kit_id, location_id).await?;
+
+    Ok(KitDetail {
+        kit_id: kit_id.to_string(),
+        sku,
+        name,
+        location_id: location_id.to_string(),
+        assembled_stock,
+        buildable,
+        available: assembled_stock.max(0.0) + buildable,
+        component_cost,
+        components,
+    })
+}
+
+async fn resolve_location(
+    conn: &mut SqliteConnection,
+    location_id: &Option<String>,
+) -> Result<String, sqlx::Error> {
+    match location_id {
+        Some(location_id) => Ok(location_id.clone()),
+        None => default_location_id(conn).await,
+    }
+}
+
+pub async fn list_kits(
+    State(state): State<AppState>,
+    Query(params): Query<KitQuery>,
+) -> Json<ApiResponse<Vec<KitDetail>>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        let mut conn = db.pool().acquire().await?;
+        let location_id = resolve_location(&mut conn, &params.location_id).await?;
+
+        let kit_ids: Vec<(String,)> = sqlx::query_as(
+            r#"
+            SELECT DISTINCT pc.kit_id FROM product_components pc
+            JOIN products p ON pc.kit_id = p.id
+            WHERE p.is_active = 1
+            ORDER BY p.name
+            "#
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let mut kits = Vec::new();
+        for (kit_id,) in kit_ids {
+            kits.push(fetch_kit(&mut conn, &kit_id, &location_id).await?);
+        }
+        Ok::<_, sqlx::Error>(kits)
+    }
+    .await;
+
+    match result {
+        Ok(kits) => Json(ApiResponse {
+            success: true,
+            data: Some(kits),
+            message: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn get_kit_components(
+    State(state): State<AppState>,
+    Path(kit_id): Path<String>,
+    Query(params): Query<KitQuery>,
+) -> Json<ApiResponse<KitDetail>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        let mut conn = db.pool().acquire().await?;
+        let location_id = resolve_location(&mut conn, &params.location_id).await?;
+        fetch_kit(&mut conn, &kit_id, &location_id).await
+    }
+    .await;
+
+    match result {
+        Ok(kit) => Json(ApiResponse {
+            success: true,
+            data: Some(kit),
+            message: None,
+        }),
+        Err(sqlx::Error::RowNotFound) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Producto no encontrado".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn set_kit_components(
+    State(state): State<AppState>,
+    Path(kit_id): Path<String>,
+    Json(payload): Json<SetComponentsRequest>,
+) -> Json<ApiResponse<KitDetail>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        let mut tx = db.pool().begin().await.map_err(|e| e.to_string())?;
+
+        let (used_in_kits,): (i32,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM product_components WHERE component_id = ?"
+        )
+        .bind(&kit_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        if used_in_kits > 0 && !payload.components.is_empty() {
+            return Err("El producto es componente de otro kit; no se permiten kits anidados".to_string());
+        }
+
+        sqlx::query("DELETE FROM product_components WHERE kit_id = ?")
+            .bind(&kit_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        for component in &payload.components {
+            if component.quantity <= 0.0 {
+                return Err("Las cantidades de los componentes deben ser positivas".to_string());
+            }
+            if component.product_id == kit_id {
+                return Err("Un kit no puede contenerse a sí mismo".to_string());
+            }
+            if !kit_components(&mut tx, &component.product_id).await.map_err(|e| e.to_string())?.is_empty() {
+                return Err("Un componente no puede ser otro kit".to_string());
+            }
+
+            let resolved = resolve_quantity(&mut tx, &component.product_id, component.unit.as_deref(), component.quantity).await?;
+
+            sqlx::query(
+                r#"
+                INSERT INTO product_components (id, kit_id, component_id, quantity, created_at)
+                VALUES (?, ?, ?, ?, datetime('now'))
+                "#
+            )
+            .bind(uuid::Uuid::new_v4().to_string())
+            .bind(&kit_id)
+            .bind(&component.product_id)
+            .bind(resolved.base_quantity)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::Database(ref db_err) if db_err.message().contains("UNIQUE") => {
+                    "Componente repetido en la receta".to_string()
+                }
+                e => e.to_string(),
+            })?;
+        }
+
+        let location_id = default_location_id(&mut tx).await.map_err(|e| e.to_string())?;
+        let kit = fetch_kit(&mut tx, &kit_id, &location_id)
+            .await
+            .map_err(|_| "Producto no encontrado".to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok(kit)
+    }
+    .await;
+
+    match result {
+        Ok(kit) => Json(ApiResponse {
+            success: true,
+            data: Some(kit),
+            message: Some("Receta guardada".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        }),
+    }
+}
+
+pub async fn assemble_kit(
+    State(state): State<AppState>,
+    Path(kit_id): Path<String>,
+    Json(payload): Json<AssemblyRequest>,
+) -> Json<ApiResponse<KitAssembly>> {
+    run_assembly(state, kit_id, payload, "assemble").await
+}
+
+pub async fn disassemble_kit(
+    State(state): State<AppState>,
+    Path(kit_id): Path<String>,
+    Json(payload): Json<AssemblyRequest>,
+) -> Json<ApiResponse<KitAssembly>> {
+    run_assembly(state, kit_id, payload, "disassemble").await
+}
+
+async fn run_assembly(
+    state: AppState,
+    kit_id: String,
+    payload: AssemblyRequest,
+    operation: &str,
+) -> Json<ApiResponse<KitAssembly>> {
+    let db = state.db.lock().await;
+
+    if payload.quantity <= 0.0 {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("La cantidad debe ser mayor a cero".to_string()),
+        });
+    }
+
+    let assembling = operation == "assemble";
+    let assembly_id = uuid::Uuid::new_v4().to_string();
+    let assembly_number = format!(
+        "{}-{}",
+        if assembling { "ASM" } else { "DIS" },
+        uuid::Uuid::new_v4().simple().to_string()[..8].to_uppercase()
+    );
+
+    let result = async {
+        let mut tx = db.pool().begin().await.map_err(|e| e.to_string())?;
+
+        let location_id = resolve_location(&mut tx, &payload.location_id).await.map_err(|e| e.to_string())?;
+        let (location_name,): (String,) = sqlx::query_as("SELECT name FROM locations WHERE id = ?")
+            .bind(&location_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Ubicación no encontrada".to_string())?;
+
+        // Rejects fractional kits for whole-unit products
+        resolve_quantity(&mut tx, &kit_id, None, payload.quantity).await?;
+
+        let kit = fetch_kit(&mut tx, &kit_id, &location_id)
+            .await
+            .map_err(|_| "Producto no encontrado".to_string())?;
+        if kit.components.is_empty() {
+            return Err(format!("{} no tiene receta", kit.name));
+        }
+
+        if assembling {
+            for component in &kit.components {
+                let needed = component.quantity * payload.quantity;
+                if needed > component.available + 1e-9 {
+                    return Err(format!(
+                        "Stock insuficiente de {} en {} (disponible: {}, requerido: {})",
+                        component.name, location_name, component.available, needed
+                    ));
+                }
+            }
+        } else if payload.quantity > kit.assembled_stock + 1e-9 {
+            return Err(format!(
+                "Stock insuficiente de {} en {} (disponible: {})",
+                kit.name, location_name, kit.assembled_stock
+            ));
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO kit_assemblies (id, assembly_number, kit_id, location_id, operation, quantity, user_id, notes, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, datetime('now'))
+            "#
+        )
+        .bind(&assembly_id)
+        .bind(&assembly_number)
+        .bind(&kit_id)
+        .bind(&location_id)
+        .bind(operation)
+        .bind(payload.quantity)
+        .bind(&payload.user_id)
+        .bind(&payload.notes)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let notes = format!(
+            "{} {} de {}",
+            if assembling { "Ensamble" } else { "Desensamble" },
+            assembly_number,
+            kit.name
+        );
+        let sign = if assembling { -1.0 } else { 1.0 };
+
+        for component in &kit.components {
+            let quantity = component.quantity * payload.quantity;
+            StockMovement {
+                product_id: &component.component_id,
+                location_id: &location_id,
+                movement_type: if assembling { "assembly_out" } else { "disassembly_in" },
+                delta: sign * quantity,
+                quantity,
+                reference_id: Some(&assembly_id),
+                notes: Some(&notes),
+                user_id: &payload.user_id,
+                reason_code: None,
+            }
+            .post(&mut tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+
+        if assembling {
+            // Assembled kits carry the cost of the components that went into them
+            let (stock, cost): (f64, f64) = sqlx::query_as("SELECT stock, cost FROM products WHERE id = ?")
+                .bind(&kit_id)
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            PriceUpdate {
+                product_id: &kit_id,
+                price: None,
+                cost: Some(weighted_average_cost(stock, cost, payload.quantity, kit.component_cost)),
+                source: "assembly",
+                reference_id: Some(&assembly_id),
+                user_id: Some(&payload.user_id),
+            }
+            .apply(&mut tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+
+        StockMovement {
+            product_id: &kit_id,
+            location_id: &location_id,
+            movement_type: if assembling { "assembly_in" } else { "disassembly_out" },
+            delta: -sign * payload.quantity,
+            quantity: payload.quantity,
+            reference_id: Some(&assembly_id),
+            notes: Some(&notes),
+            user_id: &payload.user_id,
+            reason_code: None,
+        }
+        .post(&mut tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let kit = fetch_kit(&mut tx, &kit_id, &location_id).await.map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok(kit)
+    }
+    .await;
+
+    match result {
+        Ok(kit) => Json(ApiResponse {
+            success: true,
+            data: Some(KitAssembly {
+                id: assembly_id,
+                assembly_number,
+                operation: operation.to_string(),
+                quantity: payload.quantity,
+                kit,
+            }),
+            message: Some(if assembling { "Kits ensamblados" } else { "Kits desensamblados" }.to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        }),
+    }
+}
@@ -0,0 +1,505 @@
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use printpdf::path::PaintMode;
+use printpdf::{BuiltinFont, IndirectFontRef, Mm, PdfDocument, PdfLayerReference, Rect};
+use serde::{Deserialize, Serialize};
+use crate::api::AppState;
+use crate::api::settings::get_setting;
+use crate::models::ApiResponse;
+
+// EAN-13 module patterns for the left-hand odd (L) and even (G) sets and the right-hand set (R)
+const EAN_L: [&str; 10] = [
+    "0001101", "0011001", "0010011", "0111101", "0100011",
+    "0110001", "0101111", "0111011", "0110111", "0001011",
+];
+const EAN_G: [&str; 10] = [
+    "0100111", "0110011", "0011011", "0100001", "0011101",
+    "0111001", "0000101", "0010001", "0001001", "0010111",
+];
+const EAN_R: [&str; 10] = [
+    "1110010", "1100110", "1101100", "1000010", "1011100",
+    "1001110", "1010000", "1000100", "1001000", "1110100",
+];
+// L/G choice for the left half, encoded by the first digit
+const EAN_PARITY: [&str; 10] = [
+    "LLLLLL", "LLGLGG", "LLGGLG", "LLGGGL", "LGLLGG",
+    "LGGLLG", "LGGGLL", "LGLGLG", "LGLGGL", "LGGLGL",
+];
+
+// A4 sheet of 3 x 8 labels
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const LABEL_WIDTH: f32 = 70.0;
+const LABEL_HEIGHT: f32 = 37.0;
+const LABEL_COLUMNS: usize = 3;
+const LABEL_ROWS: usize = 8;
+
+#[derive(Deserialize)]
+pub struct CheckDigitQuery {
+    pub code: String,
+    /// 'ean13' (default), 'upca' or 'ean8'.
+    pub symbology: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CheckDigitResult {
+    pub symbology: String,
+    pub check_digit: u32,
+    pub barcode: String,
+    /// Whether the submitted code already carried a correct check digit.
+    pub valid: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct AssignBarcodesRequest {
+    /// Products to number; defaults to every active product without a barcode.
+    pub product_ids: Option<Vec<String>>,
+}
+
+#[derive(Serialize)]
+pub struct AssignedBarcode {
+    pub product_id: String,
+    pub sku: String,
+    pub name: String,
+    pub barcode: String,
+}
+
+#[derive(Deserialize)]
+pub struct LabelRequest {
+    pub product_ids: Option<Vec<String>>,
+    /// Reprint mode: products whose price changed on or after this date (YYYY-MM-DD).
+    pub price_changed_since: Option<String>,
+    /// 'pdf' (default) or 'zpl'.
+    pub format: Option<String>,
+    pub copies: Option<u32>,
+}
+
+struct Label {
+    sku: String,
+    name: String,
+    barcode: Option<String>,
+    price: f64,
+    unit_price: Option<String>,
+}
+
+/// GS1 check digit (EAN-13, EAN-8, UPC-A): digits are weighted 3 and 1 from the right.
+pub fn gs1_check_digit(payload: &str) -> Option<u32> {
+    if payload.is_empty() || !payload.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let sum: u32 = payload
+        .chars()
+        .rev()
+        .enumerate()
+        .map(|(i, c)| {
+            let digit = c.to_digit(10).unwrap_or(0);
+            if i % 2 == 0 { digit * 3 } else { digit }
+        })
+        .sum();
+    Some((10 - sum % 10) % 10)
+}
+
+pub fn is_valid_gs1(code: &str) -> bool {
+    code.len() >= 2
+        && code.is_ascii()
+        && gs1_check_digit(&code[..code.len() - 1])
+            .is_some_and(|digit| code.ends_with(char::from_digit(digit, 10).unwrap_or('x')))
+}
+
+/// The 13-digit form of an EAN-13 or UPC-A code, if it is one.
+fn as_ean13(barcode: &str) -> Option<String> {
+    match barcode.len() {
+        13 if is_valid_gs1(barcode) => Some(barcode.to_string()),
+        12 if is_valid_gs1(barcode) => Some(format!("0{}", barcode)),
+        _ => None,
+    }
+}
+
+/// The 95 bar modules of an EAN-13 code ('1' = bar).
+fn ean13_modules(code: &str) -> String {
+    let digits: Vec<usize> = code.chars().filter_map(|c| c.to_digit(10)).map(|d| d as usize).collect();
+    let parity = EAN_PARITY[digits[0]];
+
+    let mut modules = String::from("101");
+    for (digit, set) in digits[1..7].iter().zip(parity.chars()) {
+        modules.push_str(if set == 'L' { EAN_L[*digit] } else { EAN_G[*digit] });
+    }
+    modules.push_str("01010");
+    for digit in &digits[7..13] {
+        modules.push_str(EAN_R[*digit]);
+    }
+    modules.push_str("101");
+    modules
+}
+
+fn money(amount: f64) -> String {
+    format!("${:.2}", amount)
+}
+
+pub async fn get_check_digit(
+    Query(params): Query<CheckDigitQuery>,
+) -> Json<ApiResponse<CheckDigitResult>> {
+    let symbology = params.symbology.unwrap_or_else(|| "ean13".to_string());
+    let payload_length = match symbology.as_str() {
+        "ean13" => 12,
+        "upca" => 11,
+        "ean8" => 7,
+        _ => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some("Simbología inválida (ean13, upca o ean8)".to_string()),
+            });
+        }
+    };
+
+    let code = params.code.trim();
+    let (payload, submitted) = if code.len() == payload_length {
+        (code, None)
+    } else if code.len() == payload_length + 1 {
+        (&code[..payload_length], code.chars().last())
+    } else {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Se esperaban {} dígitos", payload_length)),
+        });
+    };
+
+    match gs1_check_digit(payload) {
+        Some(check_digit) => Json(ApiResponse {
+            success: true,
+            data: Some(CheckDigitResult {
+                symbology,
+                check_digit,
+                barcode: format!("{}{}", payload, check_digit),
+                valid: submitted.map(|c| c.to_digit(10) == Some(check_digit)),
+            }),
+            message: None,
+        }),
+        None => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("El código solo puede contener dígitos".to_string()),
+        }),
+    }
+}
+
+/// Numbers products without a barcode with internal EAN-13 codes under the store prefix.
+pub async fn assign_barcodes(
+    State(state): State<AppState>,
+    Json(payload): Json<AssignBarcodesRequest>,
+) -> Json<ApiResponse<Vec<AssignedBarcode>>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        let mut tx = db.pool().begin().await.map_err(|e| e.to_string())?;
+
+        let prefix = get_setting(&mut tx, "barcode_prefix")
+            .await
+            .map_err(|e| e.to_string())?
+            .unwrap_or_default();
+        if prefix.is_empty() || prefix.len() >= 12 || !prefix.chars().all(|c| c.is_ascii_digit()) {
+            return Err(format!("Prefijo de códigos inválido: '{}'", prefix));
+        }
+        let sequence_digits = 12 - prefix.len();
+
+        let used: Vec<(String,)> = sqlx::query_as(
+            "SELECT barcode FROM products WHERE barcode LIKE ? || '%' AND length(barcode) = 13 AND barcode NOT GLOB '*[^0-9]*'"
+        )
+        .bind(&prefix)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        let mut next = used
+            .iter()
+            .filter_map(|(barcode,)| barcode.get(prefix.len()..12)?.parse::<u64>().ok())
+            .max()
+            .map_or(1, |last| last + 1);
+
+        let candidates: Vec<(String, String, String)> = sqlx::query_as(
+            r#"
+            SELECT id, sku, name FROM products
+            WHERE (barcode IS NULL OR barcode = '') AND is_active = 1
+            ORDER BY sku
+            "#
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let mut assigned = Vec::new();
+        for (product_id, sku, name) in candidates {
+            if payload.product_ids.as_ref().is_some_and(|ids| !ids.contains(&product_id)) {
+                continue;
+            }
+            if next >= 10u64.pow(sequence_digits as u32) {
+                return Err(format!("Se agotaron los códigos del prefijo {}", prefix));
+            }
+
+            let body = format!("{}{:0width$}", prefix, next, width = sequence_digits);
+            let barcode = format!("{}{}", body, gs1_check_digit(&body).unwrap_or(0));
+            next += 1;
+
+            sqlx::query("UPDATE products SET barcode = ?, updated_at = datetime('now') WHERE id = ?")
+                .bind(&barcode)
+                .bind(&product_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            assigned.push(AssignedBarcode {
+                product_id,
+                sku,
+                name,
+                barcode,
+            });
+        }
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok(assigned)
+    }
+    .await;
+
+    match result {
+        Ok(assigned) => Json(ApiResponse {
+            success: true,
+            message: Some(format!("{} códigos asignados", assigned.len())),
+            data: Some(assigned),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        }),
+    }
+}
+
+type LabelRow = (String, String, String, String, f64, f64, String, f64, String, f64);
+
+async fn load_labels(
+    pool: &sqlx::Pool<sqlx::Sqlite>,
+    payload: &LabelRequest,
+) -> Result<Vec<Label>, sqlx::Error> {
+    let rows: Vec<LabelRow> = sqlx::query_as(
+        r#"
+        SELECT p.id, p.sku, p.name, COALESCE(p.barcode, ''), p.price,
+               COALESCE(p.content_quantity, 0.0), COALESCE(cu.dimension, ''), COALESCE(cu.to_base, 1.0),
+               COALESCE(u.dimension, ''), COALESCE(u.to_base, 1.0)
+        FROM products p
+        LEFT JOIN units u ON u.code = p.unit
+        LEFT JOIN units cu ON cu.code = p.content_unit
+        WHERE p.is_active = 1
+          AND (? IS NULL OR p.price_updated_at >= ?)
+        ORDER BY p.name
+        "#
+    )
+    .bind(&payload.price_changed_since)
+    .bind(&payload.price_changed_since)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter(|row| payload.product_ids.as_ref().is_none_or(|ids| ids.contains(&row.0)))
+        .map(|(_, sku, name, barcode, price, content_quantity, content_dimension, content_to_base, unit_dimension, unit_to_base)| {
+            let per = |dimension: &str| match dimension {
+                "mass" => Some("kg"),
+                "volume" => Some("L"),
+                "length" => Some("m"),
+                "count" => Some("pza"),
+                _ => None,
+            };
+            // Unit price per kg, L, m or piece, from the net content or from the selling unit itself
+            let unit_price = if content_quantity > 0.0 {
+                per(&content_dimension).map(|per| format!("{} / {}", money(price / (content_quantity * content_to_base)), per))
+            } else if ["mass", "volume", "length"].contains(&unit_dimension.as_str()) {
+                per(&unit_dimension).map(|per| format!("{} / {}", money(price / unit_to_base), per))
+            } else {
+                None
+            };
+
+            Label {
+                sku,
+                name,
+                barcode: if barcode.is_empty() { None } else { Some(barcode) },
+                price,
+                unit_price,
+            }
+        })
+        .collect())
+}
+
+fn draw_label(layer: &PdfLayerReference, regular: &IndirectFontRef, bold: &IndirectFontRef, label: &Label, x: f32, y: f32) {
+    let name: String = label.name.chars().take(34).collect();
+    layer.use_text(name, 9.0, Mm(x + 3.0), Mm(y + LABEL_HEIGHT - 7.0), regular);
+    layer.use_text(money(label.price), 20.0, Mm(x + 3.0), Mm(y + LABEL_HEIGHT - 17.0), bold);
+    if let Some(unit_price) = &label.unit_price {
+        layer.use_text(unit_price.as_str(), 7.0, Mm(x + 3.0), Mm(y + LABEL_HEIGHT - 22.0), regular);
+    }
+    layer.use_text(label.sku.as_str(), 7.0, Mm(x + 3.0), Mm(y + 4.0), regular);
+
+    let Some(barcode) = &label.barcode else { return };
+    match as_ean13(barcode) {
+        Some(code) => {
+            let module = 0.33;
+            let left = x + LABEL_WIDTH - 3.0 - 95.0 * module;
+            for (i, bar) in ean13_modules(&code).chars().enumerate() {
+                if bar == '1' {
+                    let bar_x = left + i as f32 * module;
+                    layer.add_rect(
+                        Rect::new(Mm(bar_x), Mm(y + 7.0), Mm(bar_x + module), Mm(y + 17.0))
+                            .with_mode(PaintMode::Fill),
+                    );
+                }
+            }
+            layer.use_text(code, 7.0, Mm(left + 4.0), Mm(y + 4.0), regular);
+        }
+        // Other symbologies are printed as text only
+        None => layer.use_text(barcode.as_str(), 8.0, Mm(x + 36.0), Mm(y + 4.0), regular),
+    }
+}
+
+fn render_pdf(labels: &[Label], copies: u32) -> Result<Vec<u8>, String> {
+    let (doc, page, layer) = PdfDocument::new("Etiquetas", Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Etiquetas");
+    let regular = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(|e| e.to_string())?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold).map_err(|e| e.to_string())?;
+
+    let margin_x = (PAGE_WIDTH - LABEL_COLUMNS as f32 * LABEL_WIDTH) / 2.0;
+    let margin_y = (PAGE_HEIGHT - LABEL_ROWS as f32 * LABEL_HEIGHT) / 2.0;
+    let per_page = LABEL_COLUMNS * LABEL_ROWS;
+
+    let mut current = doc.get_page(page).get_layer(layer);
+    let copies = labels.iter().flat_map(|label| std::iter::repeat_n(label, copies as usize));
+    for (i, label) in copies.enumerate() {
+        let slot = i % per_page;
+        if i > 0 && slot == 0 {
+            let (page, layer) = doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Etiquetas");
+            current = doc.get_page(page).get_layer(layer);
+        }
+        let column = slot % LABEL_COLUMNS;
+        let row = slot / LABEL_COLUMNS;
+        let x = margin_x + column as f32 * LABEL_WIDTH;
+        let y = PAGE_HEIGHT - margin_y - (row + 1) as f32 * LABEL_HEIGHT;
+        draw_label(&current, &regular, &bold, label, x, y);
+    }
+
+    doc.save_to_bytes().map_err(|e| e.to_string())
+}
+
+/// ZPL for 2" x 1.25" labels at 203 dpi.
+fn render_zpl(labels: &[Label], copies: u32) -> String {
+    // '^' and '~' start ZPL commands
+    let clean = |text: &str| text.replace(['^', '~'], " ");
+
+    let mut zpl = String::new();
+    for label in labels {
+        zpl.push_str("^XA\n^CI28\n^PW406\n^LL254\n");
+        zpl.push_str(&format!("^FO15,15^A0N,26,26^FB380,1,0,L^FD{}^FS\n", clean(&label.name)));
+        zpl.push_str(&format!("^FO15,48^A0N,56,56^FD{}^FS\n", money(label.price)));
+        if let Some(unit_price) = &label.unit_price {
+            zpl.push_str(&format!("^FO15,108^A0N,22,22^FD{}^FS\n", unit_price));
+        }
+        zpl.push_str(&format!("^FO280,108^A0N,20,20^FD{}^FS\n", clean(&label.sku)));
+        match label.barcode.as_deref().map(|b| (b, as_ean13(b))) {
+            // ^BE takes the 12 data digits and adds the check digit itself
+            Some((_, Some(code))) => zpl.push_str(&format!("^FO40,138^BY2^BEN,70,Y,N^FD{}^FS\n", &code[..12])),
+            Some((barcode, None)) => zpl.push_str(&format!("^FO40,138^BY2^BCN,70,Y,N,N^FD{}^FS\n", clean(barcode))),
+            None => {}
+        }
+        zpl.push_str(&format!("^PQ{}\n^XZ\n", copies));
+    }
+    zpl
+}
+
+pub async fn print_labels(
+    State(state): State<AppState>,
+    Json(payload): Json<LabelRequest>,
+) -> Response {
+    let db = state.db.lock().await;
+
+    let error = |message: String| {
+        Json(ApiResponse::<String> {
+            success: false,
+            data: None,
+            message: Some(message),
+        })
+        .into_response()
+    };
+
+    if payload.product_ids.is_none() && payload.price_changed_since.is_none() {
+        return error("Indica los productos o la fecha de cambio de precio".to_string());
+    }
+
+    let labels = match load_labels(db.pool(), &payload).await {
+        Ok(labels) if labels.is_empty() => return error("No hay productos para etiquetar".to_string()),
+        Ok(labels) => labels,
+        Err(e) => return error(format!("Error: {}", e)),
+    };
+    let copies = payload.copies.unwrap_or(1).max(1);
+
+    if payload.format.as_deref() == Some("zpl") {
+        return (
+            [
+                (header::CONTENT_TYPE, "text/plain; charset=utf-8".to_string()),
+                (header::CONTENT_DISPOSITION, "attachment; filename=\"etiquetas.zpl\"".to_string()),
+            ],
+            render_zpl(&labels, copies),
+        )
+            .into_response();
+    }
+
+    match render_pdf(&labels, copies) {
+        Ok(bytes) => (
+            [
+                (header::CONTENT_TYPE, "application/pdf".to_string()),
+                (header::CONTENT_DISPOSITION, "attachment; filename=\"etiquetas.pdf\"".to_string()),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(e) => error(format!("Error al generar etiquetas: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_digit_of_known_codes() {
+        assert_eq!(gs1_check_digit("400638133393"), Some(1));
+        assert_eq!(gs1_check_digit("590123412345"), Some(7));
+        assert_eq!(gs1_check_digit("03600029145"), Some(2));
+        assert_eq!(gs1_check_digit("9638507"), Some(4));
+    }
+
+    #[test]
+    fn check_digit_rejects_non_digits() {
+        assert_eq!(gs1_check_digit(""), None);
+        assert_eq!(gs1_check_digit("40063813339A"), None);
+        assert_eq!(gs1_check_digit("４００６３８１３３３９３"), None);
+    }
+
+    #[test]
+    fn validates_complete_codes() {
+        assert!(is_valid_gs1("4006381333931"));
+        assert!(is_valid_gs1("036000291452"));
+        assert!(!is_valid_gs1("4006381333932"));
+        assert!(!is_valid_gs1("5901234123458"));
+        assert!(!is_valid_gs1("1"));
+        assert!(!is_valid_gs1("400638133393é"));
+    }
+
+    #[test]
+    fn upc_a_is_padded_to_ean13() {
+        assert_eq!(as_ean13("036000291452").as_deref(), Some("0036000291452"));
+        assert_eq!(as_ean13("4006381333931").as_deref(), Some("4006381333931"));
+        assert_eq!(as_ean13("4006381333930"), None);
+        assert_eq!(ean13_modules("4006381333931").len(), 95);
+    }
+}
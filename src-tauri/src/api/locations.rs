@@ -0,0 +1,801 @@
+use axum::{Json, extract::{State, Path}};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use crate::api::AppState;
+use crate::models::ApiResponse;
+
+#[derive(Serialize)]
+pub struct Location {
+    pub id: String,
+    pub name: String,
+    pub location_type: String,
+    pub address: Option<String>,
+    pub is_default: bool,
+    pub is_active: bool,
+}
+
+#[derive(Deserialize)]
+pub struct CreateLocationRequest {
+    pub name: String,
+    pub location_type: String, // 'store', 'warehouse' or 'branch'
+    pub address: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct LocationStock {
+    pub location_id: String,
+    pub location_name: String,
+    pub quantity: f64,
+}
+
+#[derive(Serialize)]
+pub struct StockTransfer {
+    pub id: String,
+    pub transfer_number: String,
+    pub from_location_id: String,
+    pub from_location_name: String,
+    pub to_location_id: String,
+    pub to_location_name: String,
+    pub status: String,
+    pub requested_by: String,
+    pub shipped_at: Option<String>,
+    pub received_at: Option<String>,
+    pub notes: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Serialize)]
+pub struct StockTransferItem {
+    pub id: String,
+    pub product_id: String,
+    pub product_name: String,
+    pub quantity_requested: f64,
+    pub quantity_shipped: f64,
+    pub quantity_received: f64,
+}
+
+#[derive(Serialize)]
+pub struct StockTransferDetail {
+    pub transfer: StockTransfer,
+    pub items: Vec<StockTransferItem>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateTransferRequest {
+    pub from_location_id: String,
+    pub to_location_id: String,
+    pub user_id: String,
+    pub notes: Option<String>,
+    pub items: Vec<TransferItemRequest>,
+}
+
+#[derive(Deserialize)]
+pub struct TransferItemRequest {
+    pub product_id: String,
+    pub quantity: f64,
+}
+
+#[derive(Deserialize)]
+pub struct TransferStepRequest {
+    pub user_id: String,
+    /// Quantities actually shipped or received; defaults to the previous step's quantities.
+    pub items: Option<Vec<TransferItemRequest>>,
+}
+
+/// A stock change at one location, recorded in `inventory_movements`.
+pub struct StockMovement<'a> {
+    pub product_id: &'a str,
+    pub location_id: &'a str,
+    pub movement_type: &'a str,
+    /// Units added to (or removed from, when negative) the location.
+    pub delta: f64,
+    /// Quantity stored in the movement row.
+    pub quantity: f64,
+    pub reference_id: Option<&'a str>,
+    pub notes: Option<&'a str>,
+    pub user_id: &'a str,
+    /// Why stock left on an outgoing adjustment (see `inventory::ADJUSTMENT_REASONS`).
+    pub reason_code: Option<&'a str>,
+}
+
+impl StockMovement<'_> {
+    /// Applies the change to the location and to the product's total stock, then records the movement.
+    pub async fn post(&self, conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+        if self.delta != 0.0 {
+            sqlx::query(
+                r#"
+                INSERT INTO product_stock (product_id, location_id, quantity)
+                VALUES (?, ?, ?)
+                ON CONFLICT(product_id, location_id) DO UPDATE SET quantity = quantity + excluded.quantity
+                "#
+            )
+            .bind(self.product_id)
+            .bind(self.location_id)
+            .bind(self.delta)
+            .execute(&mut *conn)
+            .await?;
+
+            sqlx::query(
+                "UPDATE products SET stock = stock + ?, updated_at = datetime('now') WHERE id = ?"
+            )
+            .bind(self.delta)
+            .bind(self.product_id)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO inventory_movements (id, product_id, location_id, type, quantity, unit_cost, reason_code, reference_id, notes, user_id, created_at)
+            VALUES (?, ?, ?, ?, ?, (SELECT cost FROM products WHERE id = ?), ?, ?, ?, ?, datetime('now'))
+            "#
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(self.product_id)
+        .bind(self.location_id)
+        .bind(self.movement_type)
+        .bind(self.quantity)
+        .bind(self.product_id)
+        .bind(self.reason_code)
+        .bind(self.reference_id)
+        .bind(self.notes)
+        .bind(self.user_id)
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+}
+
+pub async fn default_location_id(conn: &mut SqliteConnection) -> Result<String, sqlx::Error> {
+    sqlx::query_as::<_, (String,)>("SELECT id FROM locations WHERE is_default = 1 LIMIT 1")
+        .fetch_one(conn)
+        .await
+        .map(|t| t.0)
+}
+
+/// Location whose stock a shift's sales draw from, falling back to the default location.
+pub async fn shift_location_id(
+    conn: &mut SqliteConnection,
+    shift_id: Option<&str>,
+) -> Result<String, sqlx::Error> {
+    if let Some(shift_id) = shift_id {
+        let location: Option<(String,)> = sqlx::query_as(
+            r#"
+            SELECT r.location_id FROM shifts s
+            JOIN cash_registers r ON s.register_id = r.id
+            WHERE s.id = ? AND r.location_id IS NOT NULL
+            "#
+        )
+        .bind(shift_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        if let Some((location_id,)) = location {
+            return Ok(location_id);
+        }
+    }
+    default_location_id(conn).await
+}
+
+pub async fn location_quantity(
+    conn: &mut SqliteConnection,
+    product_id: &str,
+    location_id: &str,
+) -> Result<f64, sqlx::Error> {
+    sqlx::query_as::<_, (f64,)>(
+        "SELECT COALESCE((SELECT quantity FROM product_stock WHERE product_id = ? AND location_id = ?), 0.0)"
+    )
+    .bind(product_id)
+    .bind(location_id)
+    .fetch_one(conn)
+    .await
+    .map(|t| t.0)
+}
+
+type LocationRow = (String, String, String, String, i32, i32);
+
+pub async fn list_locations(
+    State(state): State<AppState>,
+) -> Json<ApiResponse<Vec<Location>>> {
+    let db = state.db.lock().await;
+
+    let result: Result<Vec<LocationRow>, sqlx::Error> =
+        sqlx::query_as(
+            "SELECT id, name, location_type, COALESCE(address, ''), is_default, is_active FROM locations ORDER BY is_default DESC, name"
+        )
+        .fetch_all(db.pool())
+        .await;
+
+    match result {
+        Ok(rows) => {
+            let locations: Vec<Location> = rows
+                .into_iter()
+                .map(|(id, name, location_type, address, is_default, is_active)| Location {
+                    id,
+                    name,
+                    location_type,
+                    address: if address.is_empty() { None } else { Some(address) },
+                    is_default: is_default == 1,
+                    is_active: is_active == 1,
+                })
+                .collect();
+
+            Json(ApiResponse {
+                success: true,
+                data: Some(locations),
+                message: None,
+            })
+        }
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn create_location(
+    State(state): State<AppState>,
+    Json(payload): Json<CreateLocationRequest>,
+) -> Json<ApiResponse<Location>> {
+    let db = state.db.lock().await;
+
+    if !["store", "warehouse", "branch"].contains(&payload.location_type.as_str()) {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Tipo de ubicación inválido (store, warehouse o branch)".to_string()),
+        });
+    }
+
+    let location_id = uuid::Uuid::new_v4().to_string();
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO locations (id, name, location_type, address, is_default, is_active, created_at)
+        VALUES (?, ?, ?, ?, 0, 1, datetime('now'))
+        "#
+    )
+    .bind(&location_id)
+    .bind(&payload.name)
+    .bind(&payload.location_type)
+    .bind(&payload.address)
+    .execute(db.pool())
+    .await;
+
+    match result {
+        Ok(_) => Json(ApiResponse {
+            success: true,
+            data: Some(Location {
+                id: location_id,
+                name: payload.name,
+                location_type: payload.location_type,
+                address: payload.address,
+                is_default: false,
+                is_active: true,
+            }),
+            message: Some("Ubicación creada exitosamente".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error al crear ubicación: {}", e)),
+        }),
+    }
+}
+
+type ProductLocationRow = (String, String, f64);
+
+pub async fn get_product_stock_by_location(
+    State(state): State<AppState>,
+    Path(product_id): Path<String>,
+) -> Json<ApiResponse<Vec<LocationStock>>> {
+    let db = state.db.lock().await;
+
+    let result: Result<Vec<ProductLocationRow>, sqlx::Error> = sqlx::query_as(
+        r#"
+        SELECT l.id, l.name, COALESCE(ps.quantity, 0.0)
+        FROM locations l
+        LEFT JOIN product_stock ps ON ps.location_id = l.id AND ps.product_id = ?
+        WHERE l.is_active = 1
+        ORDER BY l.is_default DESC, l.name
+        "#
+    )
+    .bind(&product_id)
+    .fetch_all(db.pool())
+    .await;
+
+    match result {
+        Ok(rows) => Json(ApiResponse {
+            success: true,
+            data: Some(
+                rows.into_iter()
+                    .map(|(location_id, location_name, quantity)| LocationStock {
+                        location_id,
+                        location_name,
+                        quantity,
+                    })
+                    .collect(),
+            ),
+            message: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+type TransferRow = (String, String, String, String, String, String, String, String, String, String, String, String);
+type TransferItemRow = (String, String, String, f64, f64, f64);
+
+async fn fetch_transfer(
+    conn: &mut SqliteConnection,
+    transfer_id: &str,
+) -> Result<StockTransferDetail, sqlx::Error> {
+    let (id, transfer_number, from_location_id, from_location_name, to_location_id, to_location_name, status, requested_by, shipped_at, received_at, notes, created_at): TransferRow =
+        sqlx::query_as(
+            r#"
+            SELECT t.id, t.transfer_number, t.from_location_id, lf.name, t.to_location_id, lt.name,
+                   t.status, t.requested_by, COALESCE(t.shipped_at, ''), COALESCE(t.received_at, ''),
+                   COALESCE(t.notes, ''), t.created_at
+            FROM stock_transfers t
+            JOIN locations lf ON t.from_location_id = lf.id
+            JOIN locations lt ON t.to_location_id = lt.id
+            WHERE t.id = ?
+            "#
+        )
+        .bind(transfer_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    let rows: Vec<TransferItemRow> = sqlx::query_as(
+        r#"
+        SELECT i.id, i.product_id, p.name, i.quantity_requested, i.quantity_shipped, i.quantity_received
+        FROM stock_transfer_items i
+        JOIN products p ON i.product_id = p.id
+        WHERE i.transfer_id = ?
+        ORDER BY p.name
+        "#
+    )
+    .bind(transfer_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(StockTransferDetail {
+        transfer: StockTransfer {
+            id,
+            transfer_number,
+            from_location_id,
+            from_location_name,
+            to_location_id,
+            to_location_name,
+            status,
+            requested_by,
+            shipped_at: if shipped_at.is_empty() { None } else { Some(shipped_at) },
+            received_at: if received_at.is_empty() { None } else { Some(received_at) },
+            notes: if notes.is_empty() { None } else { Some(notes) },
+            created_at,
+        },
+        items: rows
+            .into_iter()
+            .map(|(id, product_id, product_name, quantity_requested, quantity_shipped, quantity_received)| {
+                StockTransferItem {
+                    id,
+                    product_id,
+                    product_name,
+                    quantity_requested,
+                    quantity_shipped,
+                    quantity_received,
+                }
+            })
+            .collect(),
+    })
+}
+
+pub async fn list_transfers(
+    State(state): State<AppState>,
+) -> Json<ApiResponse<Vec<StockTransfer>>> {
+    let db = state.db.lock().await;
+
+    let result: Result<Vec<TransferRow>, sqlx::Error> =
+        sqlx::query_as(
+            r#"
+            SELECT t.id, t.transfer_number, t.from_location_id, lf.name, t.to_location_id, lt.name,
+                   t.status, t.requested_by, COALESCE(t.shipped_at, ''), COALESCE(t.received_at, ''),
+                   COALESCE(t.notes, ''), t.created_at
+            FROM stock_transfers t
+            JOIN locations lf ON t.from_location_id = lf.id
+            JOIN locations lt ON t.to_location_id = lt.id
+            ORDER BY t.created_at DESC
+            LIMIT 100
+            "#
+        )
+        .fetch_all(db.pool())
+        .await;
+
+    match result {
+        Ok(rows) => {
+            let transfers: Vec<StockTransfer> = rows
+                .into_iter()
+                .map(|(id, transfer_number, from_location_id, from_location_name, to_location_id, to_location_name, status, requested_by, shipped_at, received_at, notes, created_at)| {
+                    StockTransfer {
+                        id,
+                        transfer_number,
+                        from_location_id,
+                        from_location_name,
+                        to_location_id,
+                        to_location_name,
+                        status,
+                        requested_by,
+                        shipped_at: if shipped_at.is_empty() { None } else { Some(shipped_at) },
+                        received_at: if received_at.is_empty() { None } else { Some(received_at) },
+                        notes: if notes.is_empty() { None } else { Some(notes) },
+                        created_at,
+                    }
+                })
+                .collect();
+
+            Json(ApiResponse {
+                success: true,
+                data: Some(transfers),
+                message: None,
+            })
+        }
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn get_transfer(
+    State(state): State<AppState>,
+    Path(transfer_id): Path<String>,
+) -> Json<ApiResponse<StockTransferDetail>> {
+    let db = state.db.lock().await;
+
+    let result = match db.pool().acquire().await {
+        Ok(mut conn) => fetch_transfer(&mut conn, &transfer_id).await,
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(detail) => Json(ApiResponse {
+            success: true,
+            data: Some(detail),
+            message: None,
+        }),
+        Err(_) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Traspaso no encontrado".to_string()),
+        }),
+    }
+}
+
+pub async fn create_transfer(
+    State(state): State<AppState>,
+    Json(payload): Json<CreateTransferRequest>,
+) -> Json<ApiResponse<StockTransferDetail>> {
+    let db = state.db.lock().await;
+
+    if payload.from_location_id == payload.to_location_id {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("El origen y el destino deben ser distintos".to_string()),
+        });
+    }
+    if payload.items.is_empty() || payload.items.iter().any(|item| item.quantity <= 0.0) {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("El traspaso debe tener productos con cantidades positivas".to_string()),
+        });
+    }
+    // Ship and receive steps address lines by product, so each product gets one line
+    if payload
+        .items
+        .iter()
+        .enumerate()
+        .any(|(i, item)| payload.items[..i].iter().any(|other| other.product_id == item.product_id))
+    {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Cada producto puede aparecer una sola vez en el traspaso".to_string()),
+        });
+    }
+
+    let transfer_id = uuid::Uuid::new_v4().to_string();
+    let transfer_number = format!("TRF-{}", uuid::Uuid::new_v4().simple().to_string()[..8].to_uppercase());
+
+    let result = async {
+        let mut tx = db.pool().begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO stock_transfers (id, transfer_number, from_location_id, to_location_id, status, requested_by, notes, created_at)
+            VALUES (?, ?, ?, ?, 'requested', ?, ?, datetime('now'))
+            "#
+        )
+        .bind(&transfer_id)
+        .bind(&transfer_number)
+        .bind(&payload.from_location_id)
+        .bind(&payload.to_location_id)
+        .bind(&payload.user_id)
+        .bind(&payload.notes)
+        .execute(&mut *tx)
+        .await?;
+
+        for item in &payload.items {
+            sqlx::query(
+                "INSERT INTO stock_transfer_items (id, transfer_id, product_id, quantity_requested) VALUES (?, ?, ?, ?)"
+            )
+            .bind(uuid::Uuid::new_v4().to_string())
+            .bind(&transfer_id)
+            .bind(&item.product_id)
+            .bind(item.quantity)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let detail = fetch_transfer(&mut tx, &transfer_id).await?;
+        tx.commit().await?;
+        Ok::<StockTransferDetail, sqlx::Error>(detail)
+    }
+    .await;
+
+    match result {
+        Ok(detail) => Json(ApiResponse {
+            success: true,
+            data: Some(detail),
+            message: Some(format!("Traspaso {} solicitado", transfer_number)),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error al crear traspaso: {}", e)),
+        }),
+    }
+}
+
+/// Quantity for `product_id` in a ship/receive request, or `fallback` when the request has no items.
+fn step_quantity(items: &Option<Vec<TransferItemRequest>>, product_id: &str, fallback: f64) -> f64 {
+    match items {
+        Some(items) => items
+            .iter()
+            .filter(|item| item.product_id == product_id)
+            .map(|item| item.quantity)
+            .sum(),
+        None => fallback,
+    }
+}
+
+pub async fn ship_transfer(
+    State(state): State<AppState>,
+    Path(transfer_id): Path<String>,
+    Json(payload): Json<TransferStepRequest>,
+) -> Json<ApiResponse<StockTransferDetail>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        let mut tx = db.pool().begin().await.map_err(|e| e.to_string())?;
+
+        let detail = fetch_transfer(&mut tx, &transfer_id)
+            .await
+            .map_err(|_| "Traspaso no encontrado".to_string())?;
+        if detail.transfer.status != "requested" {
+            return Err("Solo se pueden enviar traspasos solicitados".to_string());
+        }
+
+        for item in &detail.items {
+            let quantity = step_quantity(&payload.items, &item.product_id, item.quantity_requested);
+            if quantity < 0.0 {
+                return Err("Las cantidades no pueden ser negativas".to_string());
+            }
+
+            let available = location_quantity(&mut tx, &item.product_id, &detail.transfer.from_location_id)
+                .await
+                .map_err(|e| e.to_string())?;
+            if quantity > available {
+                return Err(format!(
+                    "Stock insuficiente de {} en {} (disponible: {})",
+                    item.product_name, detail.transfer.from_location_name, available
+                ));
+            }
+
+            sqlx::query("UPDATE stock_transfer_items SET quantity_shipped = ? WHERE id = ?")
+                .bind(quantity)
+                .bind(&item.id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            if quantity > 0.0 {
+                StockMovement {
+                    product_id: &item.product_id,
+                    location_id: &detail.transfer.from_location_id,
+                    movement_type: "transfer_out",
+                    delta: -quantity,
+                    quantity,
+                    reference_id: Some(&transfer_id),
+                    notes: Some(&format!("Traspaso {} hacia {}", detail.transfer.transfer_number, detail.transfer.to_location_name)),
+                    user_id: &payload.user_id,
+                    reason_code: None,
+                }
+                .post(&mut tx)
+                .await
+                .map_err(|e| e.to_string())?;
+            }
+        }
+
+        sqlx::query(
+            "UPDATE stock_transfers SET status = 'in_transit', shipped_by = ?, shipped_at = datetime('now') WHERE id = ?"
+        )
+        .bind(&payload.user_id)
+        .bind(&transfer_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let detail = fetch_transfer(&mut tx, &transfer_id).await.map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok(detail)
+    }
+    .await;
+
+    match result {
+        Ok(detail) => Json(ApiResponse {
+            success: true,
+            data: Some(detail),
+            message: Some("Traspaso enviado".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        }),
+    }
+}
+
+pub async fn receive_transfer(
+    State(state): State<AppState>,
+    Path(transfer_id): Path<String>,
+    Json(payload): Json<TransferStepRequest>,
+) -> Json<ApiResponse<StockTransferDetail>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        let mut tx = db.pool().begin().await.map_err(|e| e.to_string())?;
+
+        let detail = fetch_transfer(&mut tx, &transfer_id)
+            .await
+            .map_err(|_| "Traspaso no encontrado".to_string())?;
+        if detail.transfer.status != "in_transit" {
+            return Err("Solo se pueden recibir traspasos en tránsito".to_string());
+        }
+
+        for item in &detail.items {
+            let quantity = step_quantity(&payload.items, &item.product_id, item.quantity_shipped);
+            if quantity < 0.0 {
+                return Err("Las cantidades no pueden ser negativas".to_string());
+            }
+            // Nothing can arrive that the origin wasn't debited for
+            if quantity > item.quantity_shipped {
+                return Err(format!(
+                    "No se puede recibir más {} del que se envió ({})",
+                    item.product_name, item.quantity_shipped
+                ));
+            }
+
+            sqlx::query("UPDATE stock_transfer_items SET quantity_received = ? WHERE id = ?")
+                .bind(quantity)
+                .bind(&item.id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            // The destination takes in everything shipped and writes off what didn't arrive
+            if item.quantity_shipped > 0.0 {
+                StockMovement {
+                    product_id: &item.product_id,
+                    location_id: &detail.transfer.to_location_id,
+                    movement_type: "transfer_in",
+                    delta: item.quantity_shipped,
+                    quantity: item.quantity_shipped,
+                    reference_id: Some(&transfer_id),
+                    notes: Some(&format!("Traspaso {} desde {}", detail.transfer.transfer_number, detail.transfer.from_location_name)),
+                    user_id: &payload.user_id,
+                    reason_code: None,
+                }
+                .post(&mut tx)
+                .await
+                .map_err(|e| e.to_string())?;
+            }
+            let shortfall = item.quantity_shipped - quantity;
+            if shortfall > 0.0 {
+                StockMovement {
+                    product_id: &item.product_id,
+                    location_id: &detail.transfer.to_location_id,
+                    movement_type: "transfer_loss",
+                    delta: -shortfall,
+                    quantity: shortfall,
+                    reference_id: Some(&transfer_id),
+                    notes: Some(&format!(
+                        "Faltante en traspaso {} (enviado: {}, recibido: {})",
+                        detail.transfer.transfer_number, item.quantity_shipped, quantity
+                    )),
+                    user_id: &payload.user_id,
+                    reason_code: None,
+                }
+                .post(&mut tx)
+                .await
+                .map_err(|e| e.to_string())?;
+            }
+        }
+
+        sqlx::query(
+            "UPDATE stock_transfers SET status = 'received', received_by = ?, received_at = datetime('now') WHERE id = ?"
+        )
+        .bind(&payload.user_id)
+        .bind(&transfer_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let detail = fetch_transfer(&mut tx, &transfer_id).await.map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok(detail)
+    }
+    .await;
+
+    match result {
+        Ok(detail) => Json(ApiResponse {
+            success: true,
+            data: Some(detail),
+            message: Some("Traspaso recibido".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        }),
+    }
+}
+
+pub async fn cancel_transfer(
+    State(state): State<AppState>,
+    Path(transfer_id): Path<String>,
+) -> Json<ApiResponse<String>> {
+    let db = state.db.lock().await;
+
+    // Shipped transfers already moved stock and must be received instead
+    let result = sqlx::query(
+        "UPDATE stock_transfers SET status = 'cancelled' WHERE id = ? AND status = 'requested'"
+    )
+    .bind(&transfer_id)
+    .execute(db.pool())
+    .await;
+
+    match result {
+        Ok(r) if r.rows_affected() > 0 => Json(ApiResponse {
+            success: true,
+            data: Some("Traspaso cancelado".to_string()),
+            message: Some("Traspaso cancelado exitosamente".to_string()),
+        }),
+        Ok(_) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Solo se pueden cancelar traspasos solicitados".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
@@ -0,0 +1,843 @@
+use axum::{Json, extract::{Path, Query, State}};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use std::collections::HashMap;
+use crate::api::AppState;
+use crate::api::auth::is_manager;
+use crate::api::settings::get_setting;
+use crate::models::ApiResponse;
+
+/// Tiers are earned over the last year of activity.
+const WINDOW: &str = "datetime('now', '-1 year')";
+
+#[derive(Serialize)]
+pub struct LoyaltyTier {
+    pub id: String,
+    pub name: String,
+    /// Higher ranks are better tiers.
+    pub rank: i64,
+    /// A customer qualifies by meeting either threshold; a tier with neither is the base tier.
+    pub min_annual_spend: Option<f64>,
+    pub min_annual_points: Option<i64>,
+    pub points_multiplier: f64,
+    pub discount_percent: f64,
+    /// Products with a tier price.
+    pub price_count: i64,
+    pub member_count: i64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct TierRequest {
+    pub name: String,
+    pub rank: i64,
+    pub min_annual_spend: Option<f64>,
+    pub min_annual_points: Option<i64>,
+    /// Defaults to 1.
+    pub points_multiplier: Option<f64>,
+    pub discount_percent: Option<f64>,
+    pub user_id: String,
+}
+
+#[derive(Serialize)]
+pub struct TierPrice {
+    pub product_id: String,
+    pub sku: String,
+    pub product_name: String,
+    pub list_price: f64,
+    pub price: f64,
+}
+
+#[derive(Deserialize)]
+pub struct TierPriceInput {
+    pub product_id: String,
+    /// Removes the product from the tier's price list when null.
+    pub price: Option<f64>,
+}
+
+#[derive(Deserialize)]
+pub struct SetTierPricesRequest {
+    pub prices: Vec<TierPriceInput>,
+    pub user_id: String,
+}
+
+#[derive(Serialize)]
+pub struct TierChange {
+    pub id: String,
+    pub customer_id: String,
+    pub customer_name: String,
+    pub from_tier: Option<String>,
+    pub to_tier: Option<String>,
+    pub annual_spend: f64,
+    pub annual_points: i64,
+    pub created_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct TierChangeQuery {
+    pub customer_id: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct ManagerRequest {
+    pub user_id: String,
+}
+
+#[derive(Serialize)]
+pub struct EvaluationSummary {
+    pub evaluated: usize,
+    pub upgraded: usize,
+    pub downgraded: usize,
+}
+
+#[derive(Serialize)]
+pub struct CustomerLoyalty {
+    pub customer_id: String,
+    pub loyalty_points: i64,
+    pub tier: Option<LoyaltyTier>,
+    pub annual_spend: f64,
+    pub annual_points: i64,
+    pub next_tier: Option<String>,
+    /// What's still missing for the next tier, by each of its thresholds.
+    pub spend_to_next_tier: Option<f64>,
+    pub points_to_next_tier: Option<i64>,
+}
+
+/// Benefits of the customer's tier that checkout applies.
+pub struct TierBenefits {
+    pub tier_id: String,
+    pub tier: String,
+    pub points_multiplier: f64,
+    pub discount_percent: f64,
+}
+
+type TierRow = (String, String, i64, Option<f64>, Option<i64>, f64, f64, i64, i64, String, String);
+
+const TIER_COLUMNS: &str = r#"
+    t.id, t.name, t.rank, t.min_annual_spend, t.min_annual_points, t.points_multiplier, t.discount_percent,
+    (SELECT COUNT(*) FROM loyalty_tier_prices p WHERE p.tier_id = t.id),
+    (SELECT COUNT(*) FROM customers c WHERE c.loyalty_tier = t.name AND c.is_active = 1),
+    t.created_at, t.updated_at
+"#;
+
+fn tier_from_row(row: TierRow) -> LoyaltyTier {
+    let (id, name, rank, min_annual_spend, min_annual_points, points_multiplier, discount_percent, price_count, member_count, created_at, updated_at) = row;
+    LoyaltyTier {
+        id,
+        name,
+        rank,
+        min_annual_spend,
+        min_annual_points,
+        points_multiplier,
+        discount_percent,
+        price_count,
+        member_count,
+        created_at,
+        updated_at,
+    }
+}
+
+async fn fetch_tiers(pool: &Pool<Sqlite>) -> Result<Vec<LoyaltyTier>, sqlx::Error> {
+    let rows: Vec<TierRow> = sqlx::query_as(&format!(
+        "SELECT {} FROM loyalty_tiers t ORDER BY t.rank DESC",
+        TIER_COLUMNS
+    ))
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(tier_from_row).collect())
+}
+
+fn qualifies(tier: &LoyaltyTier, annual_spend: f64, annual_points: i64) -> bool {
+    match (tier.min_annual_spend, tier.min_annual_points) {
+        (None, None) => true,
+        (spend, points) => {
+            spend.is_some_and(|min| annual_spend >= min) || points.is_some_and(|min| annual_points >= min)
+        }
+    }
+}
+
+/// Points a purchase earns: the base rate per peso times the tier multiplier, rounded down.
+pub async fn points_for_purchase(
+    conn: &mut SqliteConnection,
+    total: f64,
+    points_multiplier: f64,
+) -> Result<i64, sqlx::Error> {
+    let rate: f64 = get_setting(conn, "loyalty_points_rate")
+        .await?
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0.0);
+    Ok((total * rate * points_multiplier).floor().max(0.0) as i64)
+}
+
+/// Adds (or takes, when negative) points from a customer and records why in the ledger.
+pub async fn record_points(
+    conn: &mut SqliteConnection,
+    customer_id: &str,
+    points: i64,
+    reason: &str,
+    sale_id: Option<&str>,
+    user_id: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    if points == 0 {
+        return Ok(());
+    }
+    sqlx::query(
+        "UPDATE customers SET loyalty_points = COALESCE(loyalty_points, 0) + ?, updated_at = datetime('now') WHERE id = ?"
+    )
+    .bind(points)
+    .bind(customer_id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO loyalty_points_ledger (id, customer_id, points, reason, sale_id, user_id, created_at)
+        VALUES (?, ?, ?, ?, ?, ?, datetime('now'))
+        "#
+    )
+    .bind(uuid::Uuid::new_v4().to_string())
+    .bind(customer_id)
+    .bind(points)
+    .bind(reason)
+    .bind(sale_id)
+    .bind(user_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+pub async fn customer_benefits(
+    conn: &mut SqliteConnection,
+    customer_id: &str,
+) -> Result<Option<TierBenefits>, sqlx::Error> {
+    let row: Option<(String, String, f64, f64)> = sqlx::query_as(
+        r#"
+        SELECT t.id, t.name, t.points_multiplier, t.discount_percent
+        FROM customers c
+        JOIN loyalty_tiers t ON t.name = c.loyalty_tier
+        WHERE c.id = ? AND c.is_active = 1
+        "#
+    )
+    .bind(customer_id)
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(row.map(|(tier_id, tier, points_multiplier, discount_percent)| TierBenefits {
+        tier_id,
+        tier,
+        points_multiplier,
+        discount_percent,
+    }))
+}
+
+pub async fn tier_price(
+    conn: &mut SqliteConnection,
+    tier_id: &str,
+    product_id: &str,
+) -> Result<Option<f64>, sqlx::Error> {
+    sqlx::query_as::<_, (f64,)>("SELECT price FROM loyalty_tier_prices WHERE tier_id = ? AND product_id = ?")
+        .bind(tier_id)
+        .bind(product_id)
+        .fetch_optional(conn)
+        .await
+        .map(|row| row.map(|t| t.0))
+}
+
+/// Moves every active customer to the best tier their last year qualifies them for,
+/// logging each change.
+pub async fn evaluate_tiers(pool: &Pool<Sqlite>) -> Result<EvaluationSummary, sqlx::Error> {
+    let tiers = fetch_tiers(pool).await?;
+    let rank: HashMap<&str, i64> = tiers.iter().map(|tier| (tier.name.as_str(), tier.rank)).collect();
+
+    let customers: Vec<(String, String, f64, i64)> = sqlx::query_as(&format!(
+        r#"
+        SELECT c.id, COALESCE(c.loyalty_tier, ''), COALESCE(s.spend, 0.0), COALESCE(l.points, 0)
+        FROM customers c
+        LEFT JOIN (
+            SELECT customer_id, SUM(total) AS spend FROM sales
+            WHERE status = 'completed' AND created_at >= {window}
+            GROUP BY customer_id
+        ) s ON s.customer_id = c.id
+        LEFT JOIN (
+            SELECT customer_id, SUM(points) AS points FROM loyalty_points_ledger
+            WHERE points > 0 AND created_at >= {window}
+            GROUP BY customer_id
+        ) l ON l.customer_id = c.id
+        WHERE c.is_active = 1
+        "#,
+        window = WINDOW
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    let mut summary = EvaluationSummary {
+        evaluated: customers.len(),
+        upgraded: 0,
+        downgraded: 0,
+    };
+    let mut tx = pool.begin().await?;
+    for (customer_id, current, annual_spend, annual_points) in customers {
+        let earned = tiers.iter().find(|tier| qualifies(tier, annual_spend, annual_points));
+        let earned_name = earned.map(|tier| tier.name.as_str()).unwrap_or("");
+        if earned_name == current {
+            continue;
+        }
+        // A tier that no longer exists ranks below any other
+        let current_rank = rank.get(current.as_str()).copied().unwrap_or(i64::MIN);
+        if earned.map_or(i64::MIN, |tier| tier.rank) > current_rank {
+            summary.upgraded += 1;
+        } else {
+            summary.downgraded += 1;
+        }
+
+        sqlx::query("UPDATE customers SET loyalty_tier = NULLIF(?, ''), updated_at = datetime('now') WHERE id = ?")
+            .bind(earned_name)
+            .bind(&customer_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO loyalty_tier_changes (id, customer_id, from_tier, to_tier, annual_spend, annual_points, created_at)
+            VALUES (?, ?, NULLIF(?, ''), NULLIF(?, ''), ?, ?, datetime('now'))
+            "#
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(&customer_id)
+        .bind(&current)
+        .bind(earned_name)
+        .bind(annual_spend)
+        .bind(annual_points)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(summary)
+}
+
+/// Runs the tier evaluation once a day, the first time the scheduler ticks on a new local date.
+pub async fn evaluate_tiers_if_due(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
+    let mut conn = pool.acquire().await?;
+    let last = get_setting(&mut conn, "loyalty_tiers_evaluated_on").await?;
+    let (today,): (String,) = sqlx::query_as("SELECT date('now', 'localtime')")
+        .fetch_one(&mut *conn)
+        .await?;
+    drop(conn);
+    if last.as_deref() == Some(today.as_str()) {
+        return Ok(());
+    }
+
+    evaluate_tiers(pool).await?;
+    sqlx::query(
+        r#"
+        INSERT INTO settings (key, value, updated_at) VALUES ('loyalty_tiers_evaluated_on', ?, datetime('now'))
+        ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at
+        "#
+    )
+    .bind(&today)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+fn validate_tier(payload: &TierRequest) -> Result<(), String> {
+    if payload.name.trim().is_empty() {
+        return Err("El nombre del nivel es obligatorio".to_string());
+    }
+    if payload.min_annual_spend.is_some_and(|spend| spend < 0.0)
+        || payload.min_annual_points.is_some_and(|points| points < 0)
+    {
+        return Err("Los requisitos del nivel no pueden ser negativos".to_string());
+    }
+    if payload.points_multiplier.is_some_and(|multiplier| multiplier < 0.0) {
+        return Err("El multiplicador de puntos no puede ser negativo".to_string());
+    }
+    if payload.discount_percent.is_some_and(|percent| !(0.0..=100.0).contains(&percent)) {
+        return Err("El descuento debe estar entre 0 y 100%".to_string());
+    }
+    Ok(())
+}
+
+async fn fetch_tier(pool: &Pool<Sqlite>, tier_id: &str) -> Result<Option<LoyaltyTier>, sqlx::Error> {
+    let row: Option<TierRow> = sqlx::query_as(&format!(
+        "SELECT {} FROM loyalty_tiers t WHERE t.id = ?",
+        TIER_COLUMNS
+    ))
+    .bind(tier_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(tier_from_row))
+}
+
+pub async fn list_tiers(
+    State(state): State<AppState>,
+) -> Json<ApiResponse<Vec<LoyaltyTier>>> {
+    let db = state.db.lock().await;
+
+    match fetch_tiers(db.pool()).await {
+        Ok(tiers) => Json(ApiResponse {
+            success: true,
+            data: Some(tiers),
+            message: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn create_tier(
+    State(state): State<AppState>,
+    Json(payload): Json<TierRequest>,
+) -> Json<ApiResponse<LoyaltyTier>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        if !is_manager(db.pool(), &payload.user_id).await {
+            return Err("Solo un gerente puede configurar niveles de lealtad".to_string());
+        }
+        validate_tier(&payload)?;
+
+        let tier_id = uuid::Uuid::new_v4().to_string();
+        sqlx::query(
+            r#"
+            INSERT INTO loyalty_tiers (id, name, rank, min_annual_spend, min_annual_points, points_multiplier, discount_percent, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, datetime('now'), datetime('now'))
+            "#
+        )
+        .bind(&tier_id)
+        .bind(payload.name.trim())
+        .bind(payload.rank)
+        .bind(payload.min_annual_spend)
+        .bind(payload.min_annual_points)
+        .bind(payload.points_multiplier.unwrap_or(1.0))
+        .bind(payload.discount_percent.unwrap_or(0.0))
+        .execute(db.pool())
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref err) if err.is_unique_violation() => {
+                format!("Ya existe un nivel llamado {}", payload.name.trim())
+            }
+            e => e.to_string(),
+        })?;
+
+        fetch_tier(db.pool(), &tier_id)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Nivel no encontrado".to_string())
+    }
+    .await;
+
+    match result {
+        Ok(tier) => Json(ApiResponse {
+            success: true,
+            data: Some(tier),
+            message: Some("Nivel creado".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        }),
+    }
+}
+
+pub async fn update_tier(
+    State(state): State<AppState>,
+    Path(tier_id): Path<String>,
+    Json(payload): Json<TierRequest>,
+) -> Json<ApiResponse<LoyaltyTier>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        if !is_manager(db.pool(), &payload.user_id).await {
+            return Err("Solo un gerente puede configurar niveles de lealtad".to_string());
+        }
+        validate_tier(&payload)?;
+
+        let mut tx = db.pool().begin().await.map_err(|e| e.to_string())?;
+        let current: Option<(String,)> = sqlx::query_as("SELECT name FROM loyalty_tiers WHERE id = ?")
+            .bind(&tier_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        let (current_name,) = current.ok_or_else(|| "Nivel no encontrado".to_string())?;
+
+        sqlx::query(
+            r#"
+            UPDATE loyalty_tiers SET name = ?, rank = ?, min_annual_spend = ?, min_annual_points = ?,
+                points_multiplier = ?, discount_percent = ?, updated_at = datetime('now')
+            WHERE id = ?
+            "#
+        )
+        .bind(payload.name.trim())
+        .bind(payload.rank)
+        .bind(payload.min_annual_spend)
+        .bind(payload.min_annual_points)
+        .bind(payload.points_multiplier.unwrap_or(1.0))
+        .bind(payload.discount_percent.unwrap_or(0.0))
+        .bind(&tier_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref err) if err.is_unique_violation() => {
+                format!("Ya existe un nivel llamado {}", payload.name.trim())
+            }
+            e => e.to_string(),
+        })?;
+
+        // Customers hold the tier by name
+        sqlx::query("UPDATE customers SET loyalty_tier = ? WHERE loyalty_tier = ?")
+            .bind(payload.name.trim())
+            .bind(&current_name)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        fetch_tier(db.pool(), &tier_id)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Nivel no encontrado".to_string())
+    }
+    .await;
+
+    match result {
+        Ok(tier) => Json(ApiResponse {
+            success: true,
+            data: Some(tier),
+            message: Some("Nivel actualizado".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        }),
+    }
+}
+
+pub async fn delete_tier(
+    State(state): State<AppState>,
+    Path(tier_id): Path<String>,
+    Query(payload): Query<ManagerRequest>,
+) -> Json<ApiResponse<String>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        if !is_manager(db.pool(), &payload.user_id).await {
+            return Err("Solo un gerente puede configurar niveles de lealtad".to_string());
+        }
+
+        let mut tx = db.pool().begin().await.map_err(|e| e.to_string())?;
+        let tier: Option<(String,)> = sqlx::query_as("SELECT name FROM loyalty_tiers WHERE id = ?")
+            .bind(&tier_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        let (name,) = tier.ok_or_else(|| "Nivel no encontrado".to_string())?;
+
+        sqlx::query("DELETE FROM loyalty_tier_prices WHERE tier_id = ?")
+            .bind(&tier_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        sqlx::query("DELETE FROM loyalty_tiers WHERE id = ?")
+            .bind(&tier_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        // Members move to whatever tier they qualify for now
+        evaluate_tiers(db.pool()).await.map_err(|e| e.to_string())?;
+        Ok(name)
+    }
+    .await;
+
+    match result {
+        Ok(name) => Json(ApiResponse {
+            success: true,
+            data: Some(tier_id),
+            message: Some(format!("Nivel {} eliminado", name)),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        }),
+    }
+}
+
+type TierPriceRow = (String, String, String, f64, f64);
+
+pub async fn get_tier_prices(
+    State(state): State<AppState>,
+    Path(tier_id): Path<String>,
+) -> Json<ApiResponse<Vec<TierPrice>>> {
+    let db = state.db.lock().await;
+
+    let result: Result<Vec<TierPriceRow>, sqlx::Error> = sqlx::query_as(
+        r#"
+        SELECT p.id, p.sku, p.name, p.price, tp.price
+        FROM loyalty_tier_prices tp
+        JOIN products p ON p.id = tp.product_id
+        WHERE tp.tier_id = ?
+        ORDER BY p.name
+        "#
+    )
+    .bind(&tier_id)
+    .fetch_all(db.pool())
+    .await;
+
+    match result {
+        Ok(rows) => Json(ApiResponse {
+            success: true,
+            data: Some(
+                rows.into_iter()
+                    .map(|(product_id, sku, product_name, list_price, price)| TierPrice {
+                        product_id,
+                        sku,
+                        product_name,
+                        list_price,
+                        price,
+                    })
+                    .collect(),
+            ),
+            message: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn set_tier_prices(
+    State(state): State<AppState>,
+    Path(tier_id): Path<String>,
+    Json(payload): Json<SetTierPricesRequest>,
+) -> Json<ApiResponse<usize>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        if !is_manager(db.pool(), &payload.user_id).await {
+            return Err("Solo un gerente puede configurar niveles de lealtad".to_string());
+        }
+        if payload.prices.iter().any(|input| input.price.is_some_and(|price| price < 0.0)) {
+            return Err("Los precios no pueden ser negativos".to_string());
+        }
+
+        let mut tx = db.pool().begin().await.map_err(|e| e.to_string())?;
+        let exists: Option<(String,)> = sqlx::query_as("SELECT id FROM loyalty_tiers WHERE id = ?")
+            .bind(&tier_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        if exists.is_none() {
+            return Err("Nivel no encontrado".to_string());
+        }
+
+        for input in &payload.prices {
+            match input.price {
+                Some(price) => {
+                    sqlx::query(
+                        r#"
+                        INSERT INTO loyalty_tier_prices (tier_id, product_id, price, updated_at)
+                        VALUES (?, ?, ?, datetime('now'))
+                        ON CONFLICT(tier_id, product_id) DO UPDATE SET price = excluded.price, updated_at = excluded.updated_at
+                        "#
+                    )
+                    .bind(&tier_id)
+                    .bind(&input.product_id)
+                    .bind(price)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                }
+                None => {
+                    sqlx::query("DELETE FROM loyalty_tier_prices WHERE tier_id = ? AND product_id = ?")
+                        .bind(&tier_id)
+                        .bind(&input.product_id)
+                        .execute(&mut *tx)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                }
+            }
+        }
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok(payload.prices.len())
+    }
+    .await;
+
+    match result {
+        Ok(count) => Json(ApiResponse {
+            success: true,
+            data: Some(count),
+            message: Some("Lista de precios actualizada".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        }),
+    }
+}
+
+pub async fn run_tier_evaluation(
+    State(state): State<AppState>,
+    Json(payload): Json<ManagerRequest>,
+) -> Json<ApiResponse<EvaluationSummary>> {
+    let db = state.db.lock().await;
+
+    if !is_manager(db.pool(), &payload.user_id).await {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Solo un gerente puede reevaluar los niveles".to_string()),
+        });
+    }
+
+    match evaluate_tiers(db.pool()).await {
+        Ok(summary) => Json(ApiResponse {
+            success: true,
+            message: Some(format!(
+                "{} clientes suben de nivel y {} bajan",
+                summary.upgraded, summary.downgraded
+            )),
+            data: Some(summary),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+type TierChangeRow = (String, String, String, String, String, f64, i64, String);
+
+pub async fn list_tier_changes(
+    State(state): State<AppState>,
+    Query(params): Query<TierChangeQuery>,
+) -> Json<ApiResponse<Vec<TierChange>>> {
+    let db = state.db.lock().await;
+
+    let result: Result<Vec<TierChangeRow>, sqlx::Error> = sqlx::query_as(
+        r#"
+        SELECT h.id, h.customer_id, c.name, COALESCE(h.from_tier, ''), COALESCE(h.to_tier, ''),
+               h.annual_spend, h.annual_points, h.created_at
+        FROM loyalty_tier_changes h
+        JOIN customers c ON c.id = h.customer_id
+        WHERE (? IS NULL OR h.customer_id = ?)
+        ORDER BY h.created_at DESC
+        LIMIT ?
+        "#
+    )
+    .bind(&params.customer_id)
+    .bind(&params.customer_id)
+    .bind(params.limit.unwrap_or(100).clamp(1, 1000))
+    .fetch_all(db.pool())
+    .await;
+
+    match result {
+        Ok(rows) => Json(ApiResponse {
+            success: true,
+            data: Some(
+                rows.into_iter()
+                    .map(|(id, customer_id, customer_name, from_tier, to_tier, annual_spend, annual_points, created_at)| TierChange {
+                        id,
+                        customer_id,
+                        customer_name,
+                        from_tier: if from_tier.is_empty() { None } else { Some(from_tier) },
+                        to_tier: if to_tier.is_empty() { None } else { Some(to_tier) },
+                        annual_spend,
+                        annual_points,
+                        created_at,
+                    })
+                    .collect(),
+            ),
+            message: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn get_customer_loyalty(
+    State(state): State<AppState>,
+    Path(customer_id): Path<String>,
+) -> Json<ApiResponse<CustomerLoyalty>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        let customer: Option<(i64, String, f64, i64)> = sqlx::query_as(&format!(
+            r#"
+            SELECT COALESCE(c.loyalty_points, 0), COALESCE(c.loyalty_tier, ''),
+                   (SELECT COALESCE(SUM(total), 0.0) FROM sales
+                    WHERE customer_id = c.id AND status = 'completed' AND created_at >= {window}),
+                   (SELECT COALESCE(SUM(points), 0) FROM loyalty_points_ledger
+                    WHERE customer_id = c.id AND points > 0 AND created_at >= {window})
+            FROM customers c
+            WHERE c.id = ?
+            "#,
+            window = WINDOW
+        ))
+        .bind(&customer_id)
+        .fetch_optional(db.pool())
+        .await?;
+        let Some((loyalty_points, tier_name, annual_spend, annual_points)) = customer else {
+            return Ok(None);
+        };
+
+        let tiers = fetch_tiers(db.pool()).await?;
+        let current_rank = tiers.iter().find(|tier| tier.name == tier_name).map(|tier| tier.rank);
+        // Tiers come best first, so the closest one above is the last that ranks higher
+        let next = tiers.iter().rev().find(|tier| current_rank.is_none_or(|rank| tier.rank > rank));
+        let (next_tier, spend_to_next_tier, points_to_next_tier) = match next {
+            Some(tier) => (
+                Some(tier.name.clone()),
+                tier.min_annual_spend.map(|min| (min - annual_spend).max(0.0)),
+                tier.min_annual_points.map(|min| (min - annual_points).max(0)),
+            ),
+            None => (None, None, None),
+        };
+        let tier = tiers.into_iter().find(|tier| tier.name == tier_name);
+
+        Ok::<_, sqlx::Error>(Some(CustomerLoyalty {
+            customer_id: customer_id.clone(),
+            loyalty_points,
+            tier,
+            annual_spend,
+            annual_points,
+            next_tier,
+            spend_to_next_tier,
+            points_to_next_tier,
+        }))
+    }
+    .await;
+
+    match result {
+        Ok(Some(loyalty)) => Json(ApiResponse {
+            success: true,
+            data: Some(loyalty),
+            message: None,
+        }),
+        Ok(None) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Cliente no encontrado".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
@@ -1,4 +1,5 @@
 use axum::{
+    extract::DefaultBodyLimit,
     routing::{get, post, put, delete},
     Router,
     Json,
@@ -10,10 +11,31 @@ use crate::db::Database;
 use crate::models::ApiResponse;
 
 pub mod auth;
+pub mod barcodes;
+pub mod catalog;
+pub mod customer_duplicates;
+pub mod customer_privacy;
 pub mod customers;
+pub mod denominations;
+pub mod fiscal;
+pub mod cash_movements;
 pub mod cash_register;
+pub mod counts;
+pub mod images;
 pub mod inventory;
+pub mod kits;
+pub mod labels;
+pub mod locations;
+pub mod loyalty;
+pub mod pricing;
+pub mod purchasing;
+pub mod replenishment;
 pub mod reports;
+pub mod reservations;
+pub mod sales;
+pub mod segments;
+pub mod settings;
+pub mod units;
 
 #[derive(Clone)]
 pub struct AppState {
@@ -22,6 +44,26 @@ pub struct AppState {
 
 
 pub async fn start_server(db: Arc<Mutex<Database>>) -> anyhow::Result<()> {
+    // Scheduled price changes take effect and reservations expire within a minute of their date;
+    // loyalty tiers are reevaluated once a day
+    let scheduler_db = db.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            let db = scheduler_db.lock().await;
+            if let Err(e) = pricing::apply_due_price_changes(db.pool()).await {
+                eprintln!("Error al aplicar cambios de precio programados: {}", e);
+            }
+            if let Err(e) = reservations::expire_reservations(db.pool()).await {
+                eprintln!("Error al vencer reservas de stock: {}", e);
+            }
+            if let Err(e) = loyalty::evaluate_tiers_if_due(db.pool()).await {
+                eprintln!("Error al evaluar niveles de lealtad: {}", e);
+            }
+        }
+    });
+
     let state = AppState { db };
 
     let app = Router::new()
@@ -35,26 +77,127 @@ pub async fn start_server(db: Arc<Mutex<Database>>) -> anyhow::Result<()> {
         .route("/api/customers/:id/purchases", get(customers::get_customer_purchases))
         .route("/api/customers/:id/stats", get(customers::get_customer_stats))
         .route("/api/customers/:id/loyalty-points", post(customers::add_loyalty_points))
+        .route("/api/customers/:id/loyalty", get(loyalty::get_customer_loyalty))
+        .route("/api/customers/:id/export", get(customer_privacy::export_customer_data))
+        .route("/api/customers/:id/anonymize", post(customer_privacy::anonymize_customer))
+        .route("/api/loyalty/tiers", get(loyalty::list_tiers))
+        .route("/api/loyalty/tiers", post(loyalty::create_tier))
+        .route("/api/loyalty/tiers/:id", put(loyalty::update_tier))
+        .route("/api/loyalty/tiers/:id", delete(loyalty::delete_tier))
+        .route("/api/loyalty/tiers/:id/prices", get(loyalty::get_tier_prices))
+        .route("/api/loyalty/tiers/:id/prices", put(loyalty::set_tier_prices))
+        .route("/api/loyalty/evaluate", post(loyalty::run_tier_evaluation))
+        .route("/api/loyalty/tier-changes", get(loyalty::list_tier_changes))
+        .route("/api/customers/duplicates", get(customer_duplicates::find_duplicate_customers))
+        .route("/api/customers/:id/merge", post(customer_duplicates::merge_customer))
+        .route("/api/customers/rfm", get(segments::get_rfm_scores))
+        .route("/api/customers/segments", get(segments::list_segments))
+        .route("/api/customers/segments", post(segments::create_segment))
+        .route("/api/customers/segments/:id", put(segments::update_segment))
+        .route("/api/customers/segments/:id", delete(segments::delete_segment))
+        .route("/api/customers/segments/:id/members", get(segments::get_segment_members))
+        .route("/api/customers/segments/:id/export", get(segments::export_segment_members))
+        .route("/api/sat/tax-regimes", get(fiscal::list_tax_regimes))
+        .route("/api/sat/cfdi-uses", get(fiscal::list_cfdi_uses))
         .route("/api/cash-registers", get(cash_register::list_registers))
+        .route("/api/cash-registers/:id", put(cash_register::update_register))
         .route("/api/shifts", get(cash_register::list_shifts))
         .route("/api/shifts/open", post(cash_register::open_shift))
         .route("/api/shifts/:id/close", post(cash_register::close_shift))
+        .route("/api/shifts/:id/summary", get(cash_register::get_shift_summary))
+        .route("/api/shifts/:id/review", post(cash_register::review_shift))
+        .route("/api/shifts/:id/cash-movements", get(cash_movements::list_cash_movements))
+        .route("/api/shifts/:id/cash-movements", post(cash_movements::create_cash_movement))
+        .route("/api/denominations", get(denominations::list_denominations))
+        .route("/api/denominations/:currency", put(denominations::set_denominations))
         .route("/api/shifts/current/:user_id", get(cash_register::get_current_shift))
         .route("/api/inventory/products", get(inventory::list_products_with_categories))
         .route("/api/inventory/products", post(inventory::create_product))
         .route("/api/inventory/products/:id", put(inventory::update_product))
         .route("/api/inventory/products/:id", delete(inventory::delete_product))
         .route("/api/inventory/products/low-stock", get(inventory::get_low_stock_products))
+        .route("/api/inventory/products/export", get(catalog::export_products))
+        .route(
+            "/api/inventory/products/import",
+            post(catalog::import_products).layer(DefaultBodyLimit::max(20 * 1024 * 1024)),
+        )
+        .route("/api/barcodes/resolve", get(barcodes::resolve_barcode_handler))
+        .route("/api/barcodes/scale-layouts", get(barcodes::list_scale_layouts))
+        .route("/api/barcodes/scale-layouts/:prefix", put(barcodes::update_scale_layout))
+        .route("/api/barcodes/check-digit", get(labels::get_check_digit))
+        .route("/api/barcodes/assign", post(labels::assign_barcodes))
+        .route("/api/labels", post(labels::print_labels))
+        .route("/api/settings", get(settings::list_settings))
+        .route("/api/settings/:key", put(settings::update_setting))
+        .route("/api/inventory/reorder-suggestions", get(replenishment::get_reorder_suggestions))
+        .route("/api/inventory/products/:id/price-history", get(pricing::get_price_history))
+        .route("/api/inventory/products/:id/scheduled-prices", post(pricing::schedule_price_change))
+        .route("/api/inventory/scheduled-prices/:id", delete(pricing::cancel_scheduled_price_change))
+        .route(
+            "/api/inventory/products/:id/image",
+            post(images::upload_product_image).layer(DefaultBodyLimit::max(10 * 1024 * 1024)),
+        )
+        .route("/api/inventory/products/:id/image", delete(images::delete_product_image))
+        .route("/api/images/:file", get(images::serve_image))
+        .route("/api/inventory/products/:id/stock", get(locations::get_product_stock_by_location))
+        .route("/api/inventory/products/:id/units", get(units::list_product_units))
+        .route("/api/inventory/products/:id/units", post(units::set_product_unit))
+        .route("/api/inventory/products/:id/units/:unit", delete(units::delete_product_unit))
+        .route("/api/inventory/products/:id/components", get(kits::get_kit_components))
+        .route("/api/inventory/products/:id/components", put(kits::set_kit_components))
+        .route("/api/inventory/kits", get(kits::list_kits))
+        .route("/api/inventory/kits/:id/assemble", post(kits::assemble_kit))
+        .route("/api/inventory/kits/:id/disassemble", post(kits::disassemble_kit))
         .route("/api/inventory/stock/adjust", post(inventory::adjust_stock))
+        .route("/api/inventory/reservations", get(reservations::list_reservations))
+        .route("/api/inventory/reservations", post(reservations::create_reservation))
+        .route("/api/inventory/reservations/:id/release", post(reservations::release_reservation))
+        .route("/api/inventory/adjustment-reasons", get(inventory::list_adjustment_reasons))
         .route("/api/inventory/movements", get(inventory::list_movements))
         .route("/api/inventory/categories", get(inventory::list_categories))
         .route("/api/inventory/categories", post(inventory::create_category))
+        .route("/api/inventory/categories/tree", get(inventory::get_category_tree))
+        .route("/api/inventory/categories/:id", put(inventory::update_category))
+        .route("/api/inventory/categories/:id", delete(inventory::deactivate_category))
+        .route("/api/inventory/categories/:id/parent", put(inventory::move_category))
+        .route("/api/inventory/categories/:id/merge", post(inventory::merge_category))
+        .route("/api/units", get(units::list_units))
+        .route("/api/units", post(units::create_unit))
+        .route("/api/inventory/counts", get(counts::list_counts))
+        .route("/api/inventory/counts", post(counts::create_count))
+        .route("/api/inventory/counts/:id", get(counts::get_count))
+        .route("/api/inventory/counts/:id/start", post(counts::start_count))
+        .route("/api/inventory/counts/:id/entries", post(counts::add_count_entries))
+        .route("/api/inventory/counts/:id/approve", post(counts::approve_count))
+        .route("/api/inventory/counts/:id/cancel", post(counts::cancel_count))
+        .route("/api/inventory/abc-classification", get(counts::get_abc_classification))
+        .route("/api/locations", get(locations::list_locations))
+        .route("/api/locations", post(locations::create_location))
+        .route("/api/transfers", get(locations::list_transfers))
+        .route("/api/transfers", post(locations::create_transfer))
+        .route("/api/transfers/:id", get(locations::get_transfer))
+        .route("/api/transfers/:id/ship", post(locations::ship_transfer))
+        .route("/api/transfers/:id/receive", post(locations::receive_transfer))
+        .route("/api/transfers/:id/cancel", post(locations::cancel_transfer))
+        .route("/api/sales", post(sales::create_sale))
+        .route("/api/suppliers", get(purchasing::list_suppliers))
+        .route("/api/suppliers", post(purchasing::create_supplier))
+        .route("/api/purchase-orders", get(purchasing::list_purchase_orders))
+        .route("/api/purchase-orders", post(purchasing::create_purchase_order))
+        .route("/api/purchase-orders/drafts", post(replenishment::create_draft_orders))
+        .route("/api/purchase-orders/:id", get(purchasing::get_purchase_order))
+        .route("/api/purchase-orders/:id/confirm", post(purchasing::confirm_purchase_order))
+        .route("/api/purchase-orders/:id/receive", post(purchasing::receive_purchase_order))
+        .route("/api/purchase-orders/:id/discrepancies", get(purchasing::get_purchase_order_discrepancies))
         .route("/api/reports/sales/summary", get(reports::get_sales_summary))
         .route("/api/reports/sales/top-products", get(reports::get_top_products))
         .route("/api/reports/sales/by-day", get(reports::get_sales_by_day))
         .route("/api/reports/sales/by-hour", get(reports::get_sales_by_hour))
         .route("/api/reports/sales/by-payment-method", get(reports::get_sales_by_payment_method))
         .route("/api/reports/inventory/value", get(reports::get_inventory_value))
+        .route("/api/reports/inventory/by-location", get(reports::get_inventory_by_location))
+        .route("/api/reports/inventory/by-category", get(reports::get_inventory_value_by_category))
+        .route("/api/reports/inventory/shrinkage", get(reports::get_shrinkage_report))
         .route("/api/reports/sales/by-category", get(reports::get_category_sales))
         .route("/api/reports/users/performance", get(reports::get_user_performance))
         .layer(CorsLayer::new().allow_origin(Any).allow_methods(Any).allow_headers(Any))
@@ -0,0 +1,356 @@
+use axum::{Json, extract::{State, Path}};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use crate::api::AppState;
+use crate::models::ApiResponse;
+
+#[derive(Serialize)]
+pub struct PriceChange {
+    pub id: String,
+    pub field: String,
+    pub old_value: f64,
+    pub new_value: f64,
+    pub source: String,
+    pub reference_id: Option<String>,
+    pub user_id: Option<String>,
+    pub username: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Serialize)]
+pub struct ScheduledPriceChange {
+    pub id: String,
+    pub product_id: String,
+    pub price: Option<f64>,
+    pub cost: Option<f64>,
+    pub effective_at: String,
+    pub status: String,
+    pub user_id: String,
+    pub created_at: String,
+    pub applied_at: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PriceTimeline {
+    pub product_id: String,
+    pub price: f64,
+    pub cost: f64,
+    /// Newest first.
+    pub changes: Vec<PriceChange>,
+    pub scheduled: Vec<ScheduledPriceChange>,
+}
+
+#[derive(Deserialize)]
+pub struct SchedulePriceRequest {
+    pub price: Option<f64>,
+    pub cost: Option<f64>,
+    /// 'YYYY-MM-DD' or 'YYYY-MM-DD HH:MM:SS' (UTC, like every timestamp in the database).
+    pub effective_at: String,
+    pub user_id: String,
+}
+
+/// A price and/or cost change; each value that actually changes is logged to
+/// `product_price_history`, so this is the single path for repricing a product.
+pub struct PriceUpdate<'a> {
+    pub product_id: &'a str,
+    pub price: Option<f64>,
+    pub cost: Option<f64>,
+    /// 'manual', 'import', 'purchase', 'assembly' or 'scheduled'.
+    pub source: &'a str,
+    pub reference_id: Option<&'a str>,
+    pub user_id: Option<&'a str>,
+}
+
+impl PriceUpdate<'_> {
+    pub async fn apply(&self, conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+        let Some((price, cost)): Option<(f64, f64)> = sqlx::query_as("SELECT price, cost FROM products WHERE id = ?")
+            .bind(self.product_id)
+            .fetch_optional(&mut *conn)
+            .await?
+        else {
+            return Err(sqlx::Error::RowNotFound);
+        };
+
+        for (field, old_value, new_value) in [("price", price, self.price), ("cost", cost, self.cost)] {
+            let Some(new_value) = new_value else { continue };
+            if (new_value - old_value).abs() < 1e-9 {
+                continue;
+            }
+
+            sqlx::query(
+                r#"
+                INSERT INTO product_price_history (id, product_id, field, old_value, new_value, source, reference_id, user_id, created_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, datetime('now'))
+                "#
+            )
+            .bind(uuid::Uuid::new_v4().to_string())
+            .bind(self.product_id)
+            .bind(field)
+            .bind(old_value)
+            .bind(new_value)
+            .bind(self.source)
+            .bind(self.reference_id)
+            .bind(self.user_id)
+            .execute(&mut *conn)
+            .await?;
+
+            // Shelf labels are reprinted for products whose price changed
+            let update = if field == "price" {
+                "UPDATE products SET price = ?, price_updated_at = datetime('now'), updated_at = datetime('now') WHERE id = ?"
+            } else {
+                "UPDATE products SET cost = ?, updated_at = datetime('now') WHERE id = ?"
+            };
+            sqlx::query(update)
+                .bind(new_value)
+                .bind(self.product_id)
+                .execute(&mut *conn)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+type DueChangeRow = (String, String, Option<f64>, Option<f64>, String);
+
+/// Applies pending scheduled changes whose date has arrived; returns how many were applied.
+pub async fn apply_due_price_changes(pool: &Pool<Sqlite>) -> Result<usize, sqlx::Error> {
+    let due: Vec<DueChangeRow> = sqlx::query_as(
+        r#"
+        SELECT id, product_id, price, cost, user_id
+        FROM scheduled_price_changes
+        WHERE status = 'pending' AND effective_at <= datetime('now')
+        ORDER BY effective_at, created_at
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for (schedule_id, product_id, price, cost, user_id) in &due {
+        let mut tx = pool.begin().await?;
+
+        PriceUpdate {
+            product_id,
+            price: *price,
+            cost: *cost,
+            source: "scheduled",
+            reference_id: Some(schedule_id),
+            user_id: Some(user_id),
+        }
+        .apply(&mut tx)
+        .await?;
+
+        sqlx::query(
+            "UPDATE scheduled_price_changes SET status = 'applied', applied_at = datetime('now') WHERE id = ?"
+        )
+        .bind(schedule_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+    }
+    Ok(due.len())
+}
+
+type PriceHistoryRow = (String, String, f64, f64, String, String, String, String, String);
+
+pub async fn get_price_history(
+    State(state): State<AppState>,
+    Path(product_id): Path<String>,
+) -> Json<ApiResponse<PriceTimeline>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        let (price, cost): (f64, f64) = sqlx::query_as("SELECT price, cost FROM products WHERE id = ?")
+            .bind(&product_id)
+            .fetch_optional(db.pool())
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Producto no encontrado".to_string())?;
+
+        let changes: Vec<PriceHistoryRow> = sqlx::query_as(
+            r#"
+            SELECT h.id, h.field, h.old_value, h.new_value, h.source,
+                   COALESCE(h.reference_id, ''), COALESCE(h.user_id, ''), COALESCE(u.username, ''), h.created_at
+            FROM product_price_history h
+            LEFT JOIN users u ON u.id = h.user_id
+            WHERE h.product_id = ?
+            ORDER BY h.created_at DESC, h.rowid DESC
+            "#
+        )
+        .bind(&product_id)
+        .fetch_all(db.pool())
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let scheduled = list_scheduled(db.pool(), &product_id).await.map_err(|e| e.to_string())?;
+
+        Ok::<_, String>(PriceTimeline {
+            product_id: product_id.clone(),
+            price,
+            cost,
+            changes: changes
+                .into_iter()
+                .map(|(id, field, old_value, new_value, source, reference_id, user_id, username, created_at)| PriceChange {
+                    id,
+                    field,
+                    old_value,
+                    new_value,
+                    source,
+                    reference_id: if reference_id.is_empty() { None } else { Some(reference_id) },
+                    user_id: if user_id.is_empty() { None } else { Some(user_id) },
+                    username: if username.is_empty() { None } else { Some(username) },
+                    created_at,
+                })
+                .collect(),
+            scheduled,
+        })
+    }
+    .await;
+
+    match result {
+        Ok(timeline) => Json(ApiResponse {
+            success: true,
+            data: Some(timeline),
+            message: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        }),
+    }
+}
+
+type ScheduledChangeRow = (String, String, Option<f64>, Option<f64>, String, String, String, String, String);
+
+async fn list_scheduled(pool: &Pool<Sqlite>, product_id: &str) -> Result<Vec<ScheduledPriceChange>, sqlx::Error> {
+    let rows: Vec<ScheduledChangeRow> = sqlx::query_as(
+        r#"
+        SELECT id, product_id, price, cost, effective_at, status, user_id, created_at, COALESCE(applied_at, '')
+        FROM scheduled_price_changes
+        WHERE product_id = ?
+        ORDER BY effective_at DESC
+        "#
+    )
+    .bind(product_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(id, product_id, price, cost, effective_at, status, user_id, created_at, applied_at)| ScheduledPriceChange {
+            id,
+            product_id,
+            price,
+            cost,
+            effective_at,
+            status,
+            user_id,
+            created_at,
+            applied_at: if applied_at.is_empty() { None } else { Some(applied_at) },
+        })
+        .collect())
+}
+
+pub async fn schedule_price_change(
+    State(state): State<AppState>,
+    Path(product_id): Path<String>,
+    Json(payload): Json<SchedulePriceRequest>,
+) -> Json<ApiResponse<ScheduledPriceChange>> {
+    let db = state.db.lock().await;
+
+    let error = |message: &str| {
+        Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(message.to_string()),
+        })
+    };
+
+    if payload.price.is_none() && payload.cost.is_none() {
+        return error("Indica el nuevo precio o costo");
+    }
+    if payload.price.is_some_and(|p| p < 0.0) || payload.cost.is_some_and(|c| c < 0.0) {
+        return error("No se permiten valores negativos");
+    }
+
+    let effective_at = payload.effective_at.trim();
+    let effective_at = match chrono::NaiveDateTime::parse_from_str(effective_at, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| chrono::NaiveDate::parse_from_str(effective_at, "%Y-%m-%d").map(|d| d.and_time(chrono::NaiveTime::MIN)))
+    {
+        Ok(at) if at > chrono::Utc::now().naive_utc() => at.format("%Y-%m-%d %H:%M:%S").to_string(),
+        Ok(_) => return error("La fecha de aplicación debe ser futura"),
+        Err(_) => return error("Fecha inválida (YYYY-MM-DD o YYYY-MM-DD HH:MM:SS)"),
+    };
+
+    let schedule_id = uuid::Uuid::new_v4().to_string();
+    let result = sqlx::query(
+        r#"
+        INSERT INTO scheduled_price_changes (id, product_id, price, cost, effective_at, status, user_id, created_at)
+        VALUES (?, ?, ?, ?, ?, 'pending', ?, datetime('now'))
+        "#
+    )
+    .bind(&schedule_id)
+    .bind(&product_id)
+    .bind(payload.price)
+    .bind(payload.cost)
+    .bind(&effective_at)
+    .bind(&payload.user_id)
+    .execute(db.pool())
+    .await;
+
+    match result {
+        Ok(_) => Json(ApiResponse {
+            success: true,
+            data: Some(ScheduledPriceChange {
+                id: schedule_id,
+                product_id,
+                price: payload.price,
+                cost: payload.cost,
+                effective_at,
+                status: "pending".to_string(),
+                user_id: payload.user_id,
+                created_at: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+                applied_at: None,
+            }),
+            message: Some("Cambio de precio programado".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn cancel_scheduled_price_change(
+    State(state): State<AppState>,
+    Path(schedule_id): Path<String>,
+) -> Json<ApiResponse<String>> {
+    let db = state.db.lock().await;
+
+    let result = sqlx::query(
+        "UPDATE scheduled_price_changes SET status = 'cancelled' WHERE id = ? AND status = 'pending'"
+    )
+    .bind(&schedule_id)
+    .execute(db.pool())
+    .await;
+
+    match result {
+        Ok(r) if r.rows_affected() > 0 => Json(ApiResponse {
+            success: true,
+            data: Some(schedule_id),
+            message: Some("Cambio de precio cancelado".to_string()),
+        }),
+        Ok(_) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Solo se pueden cancelar cambios pendientes".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
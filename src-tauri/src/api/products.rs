@@ -7,7 +7,7 @@ pub async fn list_products(
 ) -> Json<ApiResponse<Vec<Product>>> {
     let db = state.db.lock().await;
     
-    let result: Result<Vec<(String, String, String, String, String, f64, f64, i32, i32, String, i32)>, sqlx::Error> = 
+    let result: Result<Vec<(String, String, String, String, String, f64, f64, f64, f64, String, i32)>, sqlx::Error> = 
         sqlx::query_as(
             "SELECT id, sku, COALESCE(barcode, ''), name, COALESCE(description, ''), price, cost, stock, min_stock, unit, is_active 
              FROM products WHERE is_active = 1 ORDER BY name"
@@ -56,7 +56,7 @@ pub async fn get_product(
 ) -> Json<ApiResponse<Product>> {
     let db = state.db.lock().await;
     
-    let result: Result<(String, String, String, String, String, f64, f64, i32, i32, String, i32), sqlx::Error> = 
+    let result: Result<(String, String, String, String, String, f64, f64, f64, f64, String, i32), sqlx::Error> = 
         sqlx::query_as(
             "SELECT id, sku, COALESCE(barcode, ''), name, COALESCE(description, ''), price, cost, stock, min_stock, unit, is_active 
              FROM products WHERE id = ?"
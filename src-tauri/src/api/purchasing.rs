@@ -0,0 +1,885 @@
+use axum::{Json, extract::{State, Path}};
+use serde::{Deserialize, Serialize};
+use sqlx::{Sqlite, Transaction};
+use crate::api::AppState;
+use crate::api::locations::{default_location_id, StockMovement};
+use crate::api::pricing::PriceUpdate;
+use crate::api::units::resolve_quantity;
+use crate::models::ApiResponse;
+
+#[derive(Serialize)]
+pub struct Supplier {
+    pub id: String,
+    pub name: String,
+    pub contact_name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub rfc: Option<String>,
+    /// Days between placing an order and receiving it.
+    pub lead_time_days: i32,
+    pub is_active: bool,
+}
+
+#[derive(Deserialize)]
+pub struct CreateSupplierRequest {
+    pub name: String,
+    pub contact_name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub rfc: Option<String>,
+    pub lead_time_days: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct PurchaseOrder {
+    pub id: String,
+    pub order_number: String,
+    pub supplier_id: String,
+    pub supplier_name: String,
+    pub user_id: String,
+    pub status: String,
+    pub expected_at: Option<String>,
+    pub notes: Option<String>,
+    pub total: f64,
+    pub created_at: String,
+}
+
+#[derive(Serialize)]
+pub struct PurchaseOrderItem {
+    pub id: String,
+    pub product_id: String,
+    pub product_name: String,
+    pub quantity_ordered: f64,
+    pub quantity_received: f64,
+    pub quantity_damaged: f64,
+    pub unit: String,
+    /// Base units of the product per ordered unit.
+    pub unit_factor: f64,
+    pub unit_cost: f64,
+}
+
+#[derive(Serialize)]
+pub struct PurchaseOrderDetail {
+    pub order: PurchaseOrder,
+    pub items: Vec<PurchaseOrderItem>,
+}
+
+#[derive(Deserialize)]
+pub struct CreatePurchaseOrderRequest {
+    pub supplier_id: String,
+    pub user_id: String,
+    pub expected_at: Option<String>,
+    pub notes: Option<String>,
+    pub items: Vec<PurchaseOrderItemRequest>,
+}
+
+#[derive(Deserialize)]
+pub struct PurchaseOrderItemRequest {
+    pub product_id: String,
+    pub quantity: f64,
+    /// Purchase unit (e.g. 'caja'); quantity and cost are per this unit.
+    pub unit: Option<String>,
+    pub unit_cost: f64,
+}
+
+#[derive(Deserialize)]
+pub struct ReceiveGoodsRequest {
+    pub user_id: String,
+    /// Location receiving the goods; defaults to the main store.
+    pub location_id: Option<String>,
+    pub freight_cost: Option<f64>,
+    pub notes: Option<String>,
+    pub items: Vec<ReceiveItemRequest>,
+}
+
+#[derive(Deserialize)]
+pub struct ReceiveItemRequest {
+    pub purchase_order_item_id: String,
+    /// Units delivered, including damaged ones.
+    pub quantity_received: f64,
+    pub quantity_damaged: Option<f64>,
+    /// Invoice cost when it differs from the cost agreed in the PO.
+    pub unit_cost: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct GoodsReceiptLine {
+    pub product_id: String,
+    pub quantity_accepted: f64,
+    pub quantity_damaged: f64,
+    pub unit_cost: f64,
+    pub landed_unit_cost: f64,
+    pub new_average_cost: f64,
+}
+
+#[derive(Serialize)]
+pub struct GoodsReceipt {
+    pub id: String,
+    pub receipt_number: String,
+    pub purchase_order_id: String,
+    pub order_status: String,
+    pub freight_cost: f64,
+    pub lines: Vec<GoodsReceiptLine>,
+}
+
+#[derive(Serialize)]
+pub struct ReceiptDiscrepancy {
+    pub purchase_order_item_id: String,
+    pub product_id: String,
+    pub product_name: String,
+    pub quantity_ordered: f64,
+    pub quantity_received: f64,
+    pub quantity_damaged: f64,
+    pub shortage: f64,
+    pub overage: f64,
+    pub ordered_unit_cost: f64,
+    pub average_received_cost: f64,
+}
+
+type SupplierRow = (String, String, String, String, String, String, i32, i32);
+
+pub async fn list_suppliers(
+    State(state): State<AppState>,
+) -> Json<ApiResponse<Vec<Supplier>>> {
+    let db = state.db.lock().await;
+
+    let result: Result<Vec<SupplierRow>, sqlx::Error> =
+        sqlx::query_as(
+            r#"
+            SELECT id, name, COALESCE(contact_name, ''), COALESCE(email, ''), COALESCE(phone, ''),
+                   COALESCE(rfc, ''), lead_time_days, is_active
+            FROM suppliers
+            ORDER BY name
+            "#
+        )
+        .fetch_all(db.pool())
+        .await;
+
+    match result {
+        Ok(rows) => {
+            let suppliers: Vec<Supplier> = rows
+                .into_iter()
+                .map(|(id, name, contact_name, email, phone, rfc, lead_time_days, is_active)| Supplier {
+                    id,
+                    name,
+                    contact_name: if contact_name.is_empty() { None } else { Some(contact_name) },
+                    email: if email.is_empty() { None } else { Some(email) },
+                    phone: if phone.is_empty() { None } else { Some(phone) },
+                    rfc: if rfc.is_empty() { None } else { Some(rfc) },
+                    lead_time_days,
+                    is_active: is_active == 1,
+                })
+                .collect();
+
+            Json(ApiResponse {
+                success: true,
+                data: Some(suppliers),
+                message: None,
+            })
+        }
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn create_supplier(
+    State(state): State<AppState>,
+    Json(payload): Json<CreateSupplierRequest>,
+) -> Json<ApiResponse<Supplier>> {
+    let db = state.db.lock().await;
+
+    let supplier_id = uuid::Uuid::new_v4().to_string();
+    let lead_time_days = payload.lead_time_days.unwrap_or(7).max(0);
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO suppliers (id, name, contact_name, email, phone, rfc, lead_time_days, is_active, created_at, updated_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, 1, datetime('now'), datetime('now'))
+        "#
+    )
+    .bind(&supplier_id)
+    .bind(&payload.name)
+    .bind(&payload.contact_name)
+    .bind(&payload.email)
+    .bind(&payload.phone)
+    .bind(&payload.rfc)
+    .bind(lead_time_days)
+    .execute(db.pool())
+    .await;
+
+    match result {
+        Ok(_) => {
+            let supplier = Supplier {
+                id: supplier_id,
+                name: payload.name,
+                contact_name: payload.contact_name,
+                email: payload.email,
+                phone: payload.phone,
+                rfc: payload.rfc,
+                lead_time_days,
+                is_active: true,
+            };
+
+            Json(ApiResponse {
+                success: true,
+                data: Some(supplier),
+                message: Some("Proveedor creado exitosamente".to_string()),
+            })
+        }
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error al crear proveedor: {}", e)),
+        }),
+    }
+}
+
+type OrderRow = (String, String, String, String, String, String, String, String, f64, String);
+
+pub async fn list_purchase_orders(
+    State(state): State<AppState>,
+) -> Json<ApiResponse<Vec<PurchaseOrder>>> {
+    let db = state.db.lock().await;
+
+    let result: Result<Vec<OrderRow>, sqlx::Error> =
+        sqlx::query_as(
+            r#"
+            SELECT po.id, po.order_number, po.supplier_id, s.name, po.user_id, po.status,
+                   COALESCE(po.expected_at, ''), COALESCE(po.notes, ''),
+                   COALESCE((SELECT SUM(quantity_ordered * unit_cost) FROM purchase_order_items WHERE purchase_order_id = po.id), 0.0),
+                   po.created_at
+            FROM purchase_orders po
+            JOIN suppliers s ON po.supplier_id = s.id
+            ORDER BY po.created_at DESC
+            LIMIT 100
+            "#
+        )
+        .fetch_all(db.pool())
+        .await;
+
+    match result {
+        Ok(rows) => {
+            let orders: Vec<PurchaseOrder> = rows
+                .into_iter()
+                .map(|(id, order_number, supplier_id, supplier_name, user_id, status, expected_at, notes, total, created_at)| {
+                    PurchaseOrder {
+                        id,
+                        order_number,
+                        supplier_id,
+                        supplier_name,
+                        user_id,
+                        status,
+                        expected_at: if expected_at.is_empty() { None } else { Some(expected_at) },
+                        notes: if notes.is_empty() { None } else { Some(notes) },
+                        total,
+                        created_at,
+                    }
+                })
+                .collect();
+
+            Json(ApiResponse {
+                success: true,
+                data: Some(orders),
+                message: None,
+            })
+        }
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn create_purchase_order(
+    State(state): State<AppState>,
+    Json(payload): Json<CreatePurchaseOrderRequest>,
+) -> Json<ApiResponse<PurchaseOrderDetail>> {
+    let db = state.db.lock().await;
+
+    if payload.items.is_empty() {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("La orden de compra no tiene productos".to_string()),
+        });
+    }
+    if payload.items.iter().any(|item| item.quantity <= 0.0 || item.unit_cost < 0.0) {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Cantidades y costos deben ser positivos".to_string()),
+        });
+    }
+
+    // Ordered quantities stay in the purchase unit; receiving converts them to base units
+    let mut units = Vec::new();
+    let conversions = async {
+        let mut conn = db.pool().acquire().await.map_err(|e| e.to_string())?;
+        for item in &payload.items {
+            units.push(resolve_quantity(&mut conn, &item.product_id, item.unit.as_deref(), item.quantity).await?);
+        }
+        Ok::<(), String>(())
+    }
+    .await;
+
+    if let Err(e) = conversions {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        });
+    }
+
+    let order_id = uuid::Uuid::new_v4().to_string();
+    let order_number = format!("PO-{}", uuid::Uuid::new_v4().simple().to_string()[..8].to_uppercase());
+
+    let result = async {
+        let mut tx = db.pool().begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO purchase_orders (id, order_number, supplier_id, user_id, status, expected_at, notes, created_at, updated_at)
+            VALUES (?, ?, ?, ?, 'ordered', ?, ?, datetime('now'), datetime('now'))
+            "#
+        )
+        .bind(&order_id)
+        .bind(&order_number)
+        .bind(&payload.supplier_id)
+        .bind(&payload.user_id)
+        .bind(&payload.expected_at)
+        .bind(&payload.notes)
+        .execute(&mut *tx)
+        .await?;
+
+        for (item, unit) in payload.items.iter().zip(&units) {
+            sqlx::query(
+                r#"
+                INSERT INTO purchase_order_items (id, purchase_order_id, product_id, quantity_ordered, unit, unit_factor, unit_cost)
+                VALUES (?, ?, ?, ?, ?, ?, ?)
+                "#
+            )
+            .bind(uuid::Uuid::new_v4().to_string())
+            .bind(&order_id)
+            .bind(&item.product_id)
+            .bind(item.quantity)
+            .bind(&unit.unit)
+            .bind(unit.factor)
+            .bind(item.unit_cost)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await
+    }
+    .await;
+
+    if let Err(e) = result {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error al crear orden de compra: {}", e)),
+        });
+    }
+
+    match fetch_purchase_order(&db, &order_id).await {
+        Ok(detail) => Json(ApiResponse {
+            success: true,
+            data: Some(detail),
+            message: Some(format!("Orden {} creada exitosamente", order_number)),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn get_purchase_order(
+    State(state): State<AppState>,
+    Path(order_id): Path<String>,
+) -> Json<ApiResponse<PurchaseOrderDetail>> {
+    let db = state.db.lock().await;
+
+    match fetch_purchase_order(&db, &order_id).await {
+        Ok(detail) => Json(ApiResponse {
+            success: true,
+            data: Some(detail),
+            message: None,
+        }),
+        Err(_) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Orden de compra no encontrada".to_string()),
+        }),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmOrderRequest {
+    pub user_id: String,
+    pub expected_at: Option<String>,
+}
+
+/// Sends a draft order to the supplier.
+pub async fn confirm_purchase_order(
+    State(state): State<AppState>,
+    Path(order_id): Path<String>,
+    Json(payload): Json<ConfirmOrderRequest>,
+) -> Json<ApiResponse<PurchaseOrderDetail>> {
+    let db = state.db.lock().await;
+
+    let result = sqlx::query(
+        r#"
+        UPDATE purchase_orders
+        SET status = 'ordered', user_id = ?, expected_at = COALESCE(?, expected_at), updated_at = datetime('now')
+        WHERE id = ? AND status = 'draft'
+        "#
+    )
+    .bind(&payload.user_id)
+    .bind(&payload.expected_at)
+    .bind(&order_id)
+    .execute(db.pool())
+    .await;
+
+    match result {
+        Ok(r) if r.rows_affected() > 0 => match fetch_purchase_order(&db, &order_id).await {
+            Ok(detail) => Json(ApiResponse {
+                success: true,
+                data: Some(detail),
+                message: Some("Orden confirmada".to_string()),
+            }),
+            Err(e) => Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some(format!("Error: {}", e)),
+            }),
+        },
+        Ok(_) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Solo se pueden confirmar órdenes en borrador".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+type OrderItemRow = (String, String, String, f64, f64, f64, String, f64, f64);
+
+pub async fn fetch_purchase_order(
+    db: &crate::db::Database,
+    order_id: &str,
+) -> Result<PurchaseOrderDetail, sqlx::Error> {
+    let (id, order_number, supplier_id, supplier_name, user_id, status, expected_at, notes, created_at): (String, String, String, String, String, String, String, String, String) =
+        sqlx::query_as(
+            r#"
+            SELECT po.id, po.order_number, po.supplier_id, s.name, po.user_id, po.status,
+                   COALESCE(po.expected_at, ''), COALESCE(po.notes, ''), po.created_at
+            FROM purchase_orders po
+            JOIN suppliers s ON po.supplier_id = s.id
+            WHERE po.id = ?
+            "#
+        )
+        .bind(order_id)
+        .fetch_one(db.pool())
+        .await?;
+
+    let rows: Vec<OrderItemRow> = sqlx::query_as(
+        r#"
+        SELECT i.id, i.product_id, p.name, i.quantity_ordered, i.quantity_received, i.quantity_damaged,
+               COALESCE(i.unit, p.unit), COALESCE(i.unit_factor, 1.0), i.unit_cost
+        FROM purchase_order_items i
+        JOIN products p ON i.product_id = p.id
+        WHERE i.purchase_order_id = ?
+        ORDER BY p.name
+        "#
+    )
+    .bind(order_id)
+    .fetch_all(db.pool())
+    .await?;
+
+    let items: Vec<PurchaseOrderItem> = rows
+        .into_iter()
+        .map(|(id, product_id, product_name, quantity_ordered, quantity_received, quantity_damaged, unit, unit_factor, unit_cost)| {
+            PurchaseOrderItem {
+                id,
+                product_id,
+                product_name,
+                quantity_ordered,
+                quantity_received,
+                quantity_damaged,
+                unit,
+                unit_factor,
+                unit_cost,
+            }
+        })
+        .collect();
+
+    let total = items
+        .iter()
+        .fold(0.0, |total, item| total + item.quantity_ordered * item.unit_cost);
+
+    Ok(PurchaseOrderDetail {
+        order: PurchaseOrder {
+            id,
+            order_number,
+            supplier_id,
+            supplier_name,
+            user_id,
+            status,
+            expected_at: if expected_at.is_empty() { None } else { Some(expected_at) },
+            notes: if notes.is_empty() { None } else { Some(notes) },
+            total,
+            created_at,
+        },
+        items,
+    })
+}
+
+/// Weighted-average cost after adding `quantity` units at `unit_cost`.
+/// Negative on-hand stock carries no value, so it does not dilute the new cost.
+pub fn weighted_average_cost(stock: f64, cost: f64, quantity: f64, unit_cost: f64) -> f64 {
+    let on_hand = stock.max(0.0);
+    let incoming = quantity;
+    if on_hand + incoming <= 0.0 {
+        return cost;
+    }
+    (on_hand * cost + incoming * unit_cost) / (on_hand + incoming)
+}
+
+pub async fn receive_purchase_order(
+    State(state): State<AppState>,
+    Path(order_id): Path<String>,
+    Json(payload): Json<ReceiveGoodsRequest>,
+) -> Json<ApiResponse<GoodsReceipt>> {
+    let db = state.db.lock().await;
+
+    if payload.items.is_empty() {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("No hay productos para recibir".to_string()),
+        });
+    }
+    for item in &payload.items {
+        let damaged = item.quantity_damaged.unwrap_or(0.0);
+        if item.quantity_received < 0.0 || damaged < 0.0 || damaged > item.quantity_received {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some("Cantidades recibidas o dañadas inválidas".to_string()),
+            });
+        }
+    }
+
+    let status: Result<(String,), sqlx::Error> = sqlx::query_as(
+        "SELECT status FROM purchase_orders WHERE id = ?"
+    )
+    .bind(&order_id)
+    .fetch_one(db.pool())
+    .await;
+
+    match status {
+        Ok((status,)) if status == "ordered" || status == "partial" => {}
+        Ok((status,)) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some(format!("La orden está en estado '{}' y no admite recepciones", status)),
+            });
+        }
+        Err(_) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some("Orden de compra no encontrada".to_string()),
+            });
+        }
+    }
+
+    let mut tx = match db.pool().begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some(format!("Error: {}", e)),
+            });
+        }
+    };
+
+    match post_receipt(&mut tx, &order_id, &payload).await {
+        Ok(receipt) => match tx.commit().await {
+            Ok(_) => Json(ApiResponse {
+                success: true,
+                data: Some(receipt),
+                message: Some("Mercancía recibida exitosamente".to_string()),
+            }),
+            Err(e) => Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some(format!("Error al recibir mercancía: {}", e)),
+            }),
+        },
+        // Dropping the transaction rolls back any partial receipt
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error al recibir mercancía: {}", e)),
+        }),
+    }
+}
+
+async fn post_receipt(
+    tx: &mut Transaction<'_, Sqlite>,
+    order_id: &str,
+    payload: &ReceiveGoodsRequest,
+) -> Result<GoodsReceipt, String> {
+    let receipt_id = uuid::Uuid::new_v4().to_string();
+    let receipt_number = format!("REC-{}", uuid::Uuid::new_v4().simple().to_string()[..8].to_uppercase());
+    let freight_cost = payload.freight_cost.unwrap_or(0.0).max(0.0);
+    let location_id = match &payload.location_id {
+        Some(location_id) => location_id.clone(),
+        None => default_location_id(tx).await.map_err(|e| e.to_string())?,
+    };
+
+    // Resolve every line against the PO before touching stock
+    let mut lines: Vec<(String, String, f64, f64, f64, f64)> = Vec::new();
+    for item in &payload.items {
+        let po_item: Option<(String, f64, f64)> = sqlx::query_as(
+            "SELECT product_id, unit_cost, COALESCE(unit_factor, 1.0) FROM purchase_order_items WHERE id = ? AND purchase_order_id = ?"
+        )
+        .bind(&item.purchase_order_item_id)
+        .bind(order_id)
+        .fetch_optional(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let (product_id, ordered_cost, unit_factor) = po_item.ok_or_else(|| {
+            format!("La partida {} no pertenece a la orden", item.purchase_order_item_id)
+        })?;
+
+        let damaged = item.quantity_damaged.unwrap_or(0.0);
+        lines.push((
+            item.purchase_order_item_id.clone(),
+            product_id,
+            item.quantity_received - damaged,
+            damaged,
+            item.unit_cost.unwrap_or(ordered_cost),
+            unit_factor,
+        ));
+    }
+
+    // Freight is spread across accepted units proportionally to line value
+    let accepted_value = lines
+        .iter()
+        .fold(0.0, |total, (_, _, accepted, _, unit_cost, _)| total + accepted * unit_cost);
+    let accepted_units = lines.iter().fold(0.0, |total, (_, _, accepted, _, _, _)| total + accepted);
+
+    sqlx::query(
+        r#"
+        INSERT INTO goods_receipts (id, receipt_number, purchase_order_id, user_id, freight_cost, notes, created_at)
+        VALUES (?, ?, ?, ?, ?, ?, datetime('now'))
+        "#
+    )
+    .bind(&receipt_id)
+    .bind(&receipt_number)
+    .bind(order_id)
+    .bind(&payload.user_id)
+    .bind(freight_cost)
+    .bind(&payload.notes)
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut receipt_lines = Vec::new();
+    for (po_item_id, product_id, accepted, damaged, unit_cost, unit_factor) in lines {
+        let freight_share = if accepted <= 0.0 {
+            0.0
+        } else if accepted_value > 0.0 {
+            freight_cost * (accepted * unit_cost) / accepted_value
+        } else {
+            freight_cost * accepted / accepted_units
+        };
+        let landed_unit_cost = if accepted > 0.0 {
+            unit_cost + freight_share / accepted
+        } else {
+            unit_cost
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO goods_receipt_items (id, receipt_id, purchase_order_item_id, product_id, quantity_received, quantity_damaged, unit_cost, landed_unit_cost)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(&receipt_id)
+        .bind(&po_item_id)
+        .bind(&product_id)
+        .bind(accepted)
+        .bind(damaged)
+        .bind(unit_cost)
+        .bind(landed_unit_cost)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        sqlx::query(
+            "UPDATE purchase_order_items SET quantity_received = quantity_received + ?, quantity_damaged = quantity_damaged + ? WHERE id = ?"
+        )
+        .bind(accepted)
+        .bind(damaged)
+        .bind(&po_item_id)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let (stock, cost): (f64, f64) = sqlx::query_as(
+            "SELECT stock, cost FROM products WHERE id = ?"
+        )
+        .bind(&product_id)
+        .fetch_one(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        // Stock and cost are kept per base unit
+        let base_quantity = accepted * unit_factor;
+        let new_average_cost = weighted_average_cost(stock, cost, base_quantity, landed_unit_cost / unit_factor);
+
+        if accepted > 0.0 {
+            PriceUpdate {
+                product_id: &product_id,
+                price: None,
+                cost: Some(new_average_cost),
+                source: "purchase",
+                reference_id: Some(&receipt_id),
+                user_id: Some(&payload.user_id),
+            }
+            .apply(tx)
+            .await
+            .map_err(|e| e.to_string())?;
+
+            StockMovement {
+                product_id: &product_id,
+                location_id: &location_id,
+                movement_type: "purchase",
+                delta: base_quantity,
+                quantity: base_quantity,
+                reference_id: Some(&receipt_id),
+                notes: Some(&format!("Recepción {}", receipt_number)),
+                user_id: &payload.user_id,
+                reason_code: None,
+            }
+            .post(tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+
+        receipt_lines.push(GoodsReceiptLine {
+            product_id,
+            quantity_accepted: accepted,
+            quantity_damaged: damaged,
+            unit_cost,
+            landed_unit_cost,
+            new_average_cost,
+        });
+    }
+
+    // The order is complete once every line has its ordered quantity in good condition
+    let (pending,): (i32,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM purchase_order_items WHERE purchase_order_id = ? AND quantity_received < quantity_ordered"
+    )
+    .bind(order_id)
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let order_status = if pending == 0 { "received" } else { "partial" };
+
+    sqlx::query("UPDATE purchase_orders SET status = ?, updated_at = datetime('now') WHERE id = ?")
+        .bind(order_status)
+        .bind(order_id)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(GoodsReceipt {
+        id: receipt_id,
+        receipt_number,
+        purchase_order_id: order_id.to_string(),
+        order_status: order_status.to_string(),
+        freight_cost,
+        lines: receipt_lines,
+    })
+}
+
+type ReceiptLineRow = (String, String, String, f64, f64, f64, f64, f64);
+
+pub async fn get_purchase_order_discrepancies(
+    State(state): State<AppState>,
+    Path(order_id): Path<String>,
+) -> Json<ApiResponse<Vec<ReceiptDiscrepancy>>> {
+    let db = state.db.lock().await;
+
+    let result: Result<Vec<ReceiptLineRow>, sqlx::Error> =
+        sqlx::query_as(
+            r#"
+            SELECT i.id, i.product_id, p.name, i.quantity_ordered, i.quantity_received, i.quantity_damaged,
+                   i.unit_cost,
+                   COALESCE((SELECT SUM(r.quantity_received * r.unit_cost) / NULLIF(SUM(r.quantity_received), 0)
+                             FROM goods_receipt_items r WHERE r.purchase_order_item_id = i.id), i.unit_cost)
+            FROM purchase_order_items i
+            JOIN products p ON i.product_id = p.id
+            WHERE i.purchase_order_id = ?
+              AND (i.quantity_received <> i.quantity_ordered OR i.quantity_damaged > 0
+                   OR EXISTS (SELECT 1 FROM goods_receipt_items r
+                              WHERE r.purchase_order_item_id = i.id AND ABS(r.unit_cost - i.unit_cost) > 0.005))
+            ORDER BY p.name
+            "#
+        )
+        .bind(&order_id)
+        .fetch_all(db.pool())
+        .await;
+
+    match result {
+        Ok(rows) => {
+            let discrepancies: Vec<ReceiptDiscrepancy> = rows
+                .into_iter()
+                .map(|(id, product_id, product_name, ordered, received, damaged, ordered_unit_cost, average_received_cost)| {
+                    ReceiptDiscrepancy {
+                        purchase_order_item_id: id,
+                        product_id,
+                        product_name,
+                        quantity_ordered: ordered,
+                        quantity_received: received,
+                        quantity_damaged: damaged,
+                        shortage: (ordered - received).max(0.0),
+                        overage: (received - ordered).max(0.0),
+                        ordered_unit_cost,
+                        average_received_cost,
+                    }
+                })
+                .collect();
+
+            Json(ApiResponse {
+                success: true,
+                data: Some(discrepancies),
+                message: None,
+            })
+        }
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
@@ -0,0 +1,339 @@
+use axum::{Json, extract::{State, Query}};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+use std::collections::BTreeMap;
+use crate::api::AppState;
+use crate::api::purchasing::{fetch_purchase_order, PurchaseOrderDetail};
+use crate::models::ApiResponse;
+
+const REORDER_DEFAULT_DAYS: i32 = 30;
+
+#[derive(Deserialize)]
+pub struct ReorderQuery {
+    /// Sales window used for the average daily demand.
+    pub days: Option<i32>,
+    pub supplier_id: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ReorderSuggestion {
+    pub product_id: String,
+    pub sku: String,
+    pub name: String,
+    pub unit: String,
+    pub supplier_id: Option<String>,
+    pub supplier_name: Option<String>,
+    pub lead_time_days: i32,
+    pub stock: f64,
+    pub min_stock: f64,
+    pub max_stock: Option<f64>,
+    /// Pending quantity on draft or open purchase orders.
+    pub on_order: f64,
+    pub average_daily_sales: f64,
+    pub days_of_stock: Option<f64>,
+    /// Stock expected when an order placed today arrives.
+    pub projected_stock: f64,
+    pub suggested_quantity: f64,
+    pub stockout_before_delivery: bool,
+}
+
+#[derive(Deserialize)]
+pub struct DraftOrdersRequest {
+    pub user_id: String,
+    pub days: Option<i32>,
+    pub supplier_id: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct DraftOrdersResult {
+    pub orders: Vec<PurchaseOrderDetail>,
+    /// Suggestions left out because the product has no known supplier.
+    pub without_supplier: Vec<ReorderSuggestion>,
+}
+
+type SuggestionRow = (String, String, String, String, f64, f64, f64, f64, f64, String, String, i32, i32);
+
+/// Products that need replenishing, based on their average daily demand over the last `days`.
+/// Kit sales count as demand for their components.
+async fn compute_suggestions(
+    pool: &Pool<Sqlite>,
+    days: i32,
+    supplier_id: Option<&str>,
+) -> Result<Vec<ReorderSuggestion>, sqlx::Error> {
+    let window = format!("-{} days", days);
+
+    let rows: Vec<SuggestionRow> = sqlx::query_as(
+        r#"
+        WITH demand AS (
+            SELECT si.product_id, si.quantity * COALESCE(si.unit_factor, 1) AS quantity
+            FROM sale_items si
+            JOIN sales s ON si.sale_id = s.id
+            WHERE s.status = 'completed' AND s.created_at >= datetime('now', ?)
+            UNION ALL
+            SELECT pc.component_id, si.quantity * COALESCE(si.unit_factor, 1) * pc.quantity
+            FROM sale_items si
+            JOIN sales s ON si.sale_id = s.id
+            JOIN product_components pc ON pc.kit_id = si.product_id
+            WHERE s.status = 'completed' AND s.created_at >= datetime('now', ?)
+        ),
+        candidates AS (
+            SELECT p.id, p.sku, p.name, p.unit, p.stock, COALESCE(p.min_stock, 0.0) AS min_stock,
+                   COALESCE(p.max_stock, 0.0) AS max_stock,
+                   COALESCE((SELECT SUM(d.quantity) FROM demand d WHERE d.product_id = p.id), 0.0) AS sold,
+                   COALESCE((SELECT SUM((i.quantity_ordered - i.quantity_received) * COALESCE(i.unit_factor, 1))
+                             FROM purchase_order_items i
+                             JOIN purchase_orders po ON i.purchase_order_id = po.id
+                             WHERE i.product_id = p.id AND po.status IN ('draft', 'ordered', 'partial')
+                               AND i.quantity_ordered > i.quantity_received), 0.0) AS on_order,
+                   COALESCE(p.supplier_id,
+                            (SELECT po.supplier_id FROM purchase_order_items i
+                             JOIN purchase_orders po ON i.purchase_order_id = po.id
+                             WHERE i.product_id = p.id
+                             ORDER BY po.created_at DESC LIMIT 1)) AS supplier_id,
+                   COALESCE(u.allows_fraction, 1) AS allows_fraction
+            FROM products p
+            LEFT JOIN units u ON u.code = p.unit
+            WHERE p.is_active = 1
+              AND NOT EXISTS (SELECT 1 FROM product_components pc WHERE pc.kit_id = p.id)
+        )
+        SELECT c.id, c.sku, c.name, c.unit, c.stock, c.min_stock, c.max_stock, c.sold, c.on_order,
+               COALESCE(c.supplier_id, ''), COALESCE(s.name, ''), COALESCE(s.lead_time_days, 0), c.allows_fraction
+        FROM candidates c
+        LEFT JOIN suppliers s ON c.supplier_id = s.id
+        WHERE ? IS NULL OR c.supplier_id = ?
+        ORDER BY c.name
+        "#
+    )
+    .bind(&window)
+    .bind(&window)
+    .bind(supplier_id)
+    .bind(supplier_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut suggestions: Vec<ReorderSuggestion> = rows
+        .into_iter()
+        .filter_map(|(product_id, sku, name, unit, stock, min_stock, max_stock, sold, on_order, supplier_id, supplier_name, lead_time_days, allows_fraction)| {
+            let average_daily_sales = sold / days as f64;
+            let lead_time_demand = average_daily_sales * lead_time_days as f64;
+            let projected_stock = stock + on_order - lead_time_demand;
+            if projected_stock > min_stock {
+                return None;
+            }
+
+            // Without a max_stock, cover the minimum plus the demand while the order is in transit
+            let target = if max_stock > 0.0 { max_stock } else { min_stock + lead_time_demand };
+            let mut suggested_quantity = (target - projected_stock).max(0.0);
+            if allows_fraction == 0 {
+                suggested_quantity = suggested_quantity.ceil();
+            }
+
+            let days_of_stock = if average_daily_sales > 0.0 {
+                Some(stock.max(0.0) / average_daily_sales)
+            } else {
+                None
+            };
+            let stockout_before_delivery = days_of_stock.is_some_and(|d| d < lead_time_days as f64);
+
+            if suggested_quantity <= 0.0 && !stockout_before_delivery {
+                return None;
+            }
+
+            Some(ReorderSuggestion {
+                product_id,
+                sku,
+                name,
+                unit,
+                supplier_id: if supplier_id.is_empty() { None } else { Some(supplier_id) },
+                supplier_name: if supplier_name.is_empty() { None } else { Some(supplier_name) },
+                lead_time_days,
+                stock,
+                min_stock,
+                max_stock: if max_stock == 0.0 { None } else { Some(max_stock) },
+                on_order,
+                average_daily_sales,
+                days_of_stock,
+                projected_stock,
+                suggested_quantity,
+                stockout_before_delivery,
+            })
+        })
+        .collect();
+
+    // Products that will run out first go on top
+    suggestions.sort_by(|a, b| {
+        b.stockout_before_delivery
+            .cmp(&a.stockout_before_delivery)
+            .then(a.days_of_stock.unwrap_or(f64::MAX).total_cmp(&b.days_of_stock.unwrap_or(f64::MAX)))
+    });
+    Ok(suggestions)
+}
+
+pub async fn get_reorder_suggestions(
+    State(state): State<AppState>,
+    Query(params): Query<ReorderQuery>,
+) -> Json<ApiResponse<Vec<ReorderSuggestion>>> {
+    let db = state.db.lock().await;
+
+    let days = params.days.unwrap_or(REORDER_DEFAULT_DAYS).max(1);
+    match compute_suggestions(db.pool(), days, params.supplier_id.as_deref()).await {
+        Ok(suggestions) => Json(ApiResponse {
+            success: true,
+            data: Some(suggestions),
+            message: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+/// Turns the current suggestions into one draft purchase order per supplier.
+pub async fn create_draft_orders(
+    State(state): State<AppState>,
+    Json(payload): Json<DraftOrdersRequest>,
+) -> Json<ApiResponse<DraftOrdersResult>> {
+    let db = state.db.lock().await;
+
+    let days = payload.days.unwrap_or(REORDER_DEFAULT_DAYS).max(1);
+    let suggestions = match compute_suggestions(db.pool(), days, payload.supplier_id.as_deref()).await {
+        Ok(suggestions) => suggestions,
+        Err(e) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some(format!("Error: {}", e)),
+            });
+        }
+    };
+
+    let mut by_supplier: BTreeMap<String, Vec<&ReorderSuggestion>> = BTreeMap::new();
+    for suggestion in suggestions.iter().filter(|s| s.suggested_quantity > 0.0) {
+        if let Some(supplier_id) = &suggestion.supplier_id {
+            by_supplier.entry(supplier_id.clone()).or_default().push(suggestion);
+        }
+    }
+
+    let result = async {
+        let mut tx = db.pool().begin().await?;
+        let mut order_ids = Vec::new();
+
+        for (supplier_id, lines) in &by_supplier {
+            let order_id = uuid::Uuid::new_v4().to_string();
+            let order_number = format!("PO-{}", uuid::Uuid::new_v4().simple().to_string()[..8].to_uppercase());
+
+            sqlx::query(
+                r#"
+                INSERT INTO purchase_orders (id, order_number, supplier_id, user_id, status, expected_at, notes, created_at, updated_at)
+                VALUES (?, ?, ?, ?, 'draft', date('now', ?), ?, datetime('now'), datetime('now'))
+                "#
+            )
+            .bind(&order_id)
+            .bind(&order_number)
+            .bind(supplier_id)
+            .bind(&payload.user_id)
+            .bind(format!("+{} days", lines[0].lead_time_days))
+            .bind(format!("Sugerencia de reabastecimiento ({} días de ventas)", days))
+            .execute(&mut *tx)
+            .await?;
+
+            for line in lines {
+                // Reuse the unit and cost of the last purchase from this supplier
+                let last: Option<(String, f64, f64)> = sqlx::query_as(
+                    r#"
+                    SELECT COALESCE(i.unit, ?), COALESCE(i.unit_factor, 1.0), i.unit_cost
+                    FROM purchase_order_items i
+                    JOIN purchase_orders po ON i.purchase_order_id = po.id
+                    WHERE i.product_id = ? AND po.supplier_id = ?
+                    ORDER BY po.created_at DESC
+                    LIMIT 1
+                    "#
+                )
+                .bind(&line.unit)
+                .bind(&line.product_id)
+                .bind(supplier_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+
+                let (unit, unit_factor, unit_cost) = match last {
+                    Some(last) => last,
+                    None => {
+                        let (cost,): (f64,) = sqlx::query_as("SELECT cost FROM products WHERE id = ?")
+                            .bind(&line.product_id)
+                            .fetch_one(&mut *tx)
+                            .await?;
+                        (line.unit.clone(), 1.0, cost)
+                    }
+                };
+                let quantity = if unit_factor > 1.0 {
+                    (line.suggested_quantity / unit_factor).ceil()
+                } else {
+                    line.suggested_quantity
+                };
+
+                sqlx::query(
+                    r#"
+                    INSERT INTO purchase_order_items (id, purchase_order_id, product_id, quantity_ordered, unit, unit_factor, unit_cost)
+                    VALUES (?, ?, ?, ?, ?, ?, ?)
+                    "#
+                )
+                .bind(uuid::Uuid::new_v4().to_string())
+                .bind(&order_id)
+                .bind(&line.product_id)
+                .bind(quantity)
+                .bind(&unit)
+                .bind(unit_factor)
+                .bind(unit_cost)
+                .execute(&mut *tx)
+                .await?;
+            }
+
+            order_ids.push(order_id);
+        }
+
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(order_ids)
+    }
+    .await;
+
+    let order_ids = match result {
+        Ok(order_ids) => order_ids,
+        Err(e) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some(format!("Error al generar órdenes: {}", e)),
+            });
+        }
+    };
+
+    let mut orders = Vec::new();
+    for order_id in &order_ids {
+        match fetch_purchase_order(&db, order_id).await {
+            Ok(order) => orders.push(order),
+            Err(e) => {
+                return Json(ApiResponse {
+                    success: false,
+                    data: None,
+                    message: Some(format!("Error: {}", e)),
+                });
+            }
+        }
+    }
+
+    let without_supplier: Vec<ReorderSuggestion> = suggestions
+        .into_iter()
+        .filter(|s| s.supplier_id.is_none() && s.suggested_quantity > 0.0)
+        .collect();
+
+    Json(ApiResponse {
+        success: true,
+        message: Some(format!("{} órdenes en borrador generadas", orders.len())),
+        data: Some(DraftOrdersResult {
+            orders,
+            without_supplier,
+        }),
+    })
+}
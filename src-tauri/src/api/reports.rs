@@ -1,6 +1,7 @@
 use axum::{Json, extract::{State, Query}};
 use serde::{Deserialize, Serialize};
 use crate::api::AppState;
+use crate::api::inventory::{ADJUSTMENT_REASONS, CATEGORY_TREE};
 use crate::models::ApiResponse;
 
 #[derive(Deserialize)]
@@ -58,12 +59,79 @@ pub struct InventoryValue {
     pub out_of_stock_items: i32,
 }
 
+#[derive(Serialize)]
+pub struct LocationInventoryValue {
+    pub location_id: String,
+    pub location_name: String,
+    pub location_type: String,
+    pub products_in_stock: i32,
+    pub total_units: f64,
+    pub stock_value: f64,
+}
+
+#[derive(Serialize)]
+pub struct InventoryByLocation {
+    pub locations: Vec<LocationInventoryValue>,
+    pub in_transit_units: f64,
+    pub in_transit_value: f64,
+}
+
 #[derive(Serialize)]
 pub struct CategorySales {
     pub category_id: String,
     pub category_name: String,
+    pub parent_id: Option<String>,
+    /// Sales of the category and all its subcategories.
     pub total_sales: f64,
     pub quantity_sold: f64,
+    /// Sales of products assigned directly to the category.
+    pub own_sales: f64,
+}
+
+#[derive(Deserialize)]
+pub struct InventoryValueQuery {
+    /// Limits the figures to this category and its subcategories.
+    pub category_id: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CategoryInventoryValue {
+    pub category_id: String,
+    pub category_name: String,
+    pub parent_id: Option<String>,
+    /// Totals over the category and all its subcategories.
+    pub total_products: i32,
+    pub total_units: f64,
+    pub stock_value: f64,
+    pub own_stock_value: f64,
+}
+
+#[derive(Deserialize)]
+pub struct ShrinkageQuery {
+    /// Defaults to the last 30 days.
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+    /// 'day', 'week' or 'month' (default).
+    pub period: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ShrinkageGroup {
+    pub key: String,
+    pub label: String,
+    pub quantity: f64,
+    /// Valued at the product's cost when the stock left.
+    pub value: f64,
+    pub movements: i32,
+}
+
+#[derive(Serialize)]
+pub struct ShrinkageReport {
+    pub total_quantity: f64,
+    pub total_value: f64,
+    pub by_reason: Vec<ShrinkageGroup>,
+    pub by_category: Vec<ShrinkageGroup>,
+    pub by_period: Vec<ShrinkageGroup>,
 }
 
 #[derive(Serialize)]
@@ -104,7 +172,7 @@ pub async fn get_sales_summary(
 
     // Total items sold
     let items_result: Result<(f64,), sqlx::Error> = sqlx::query_as(&format!(
-        "SELECT COALESCE(SUM(si.quantity), 0) FROM sale_items si 
+        "SELECT COALESCE(SUM(si.quantity * COALESCE(si.unit_factor, 1)), 0.0) FROM sale_items si 
          JOIN sales s ON si.sale_id = s.id 
          WHERE s.status = 'completed' {}",
         date_filter
@@ -168,7 +236,7 @@ pub async fn get_top_products(
 
     let result: Result<Vec<(String, String, f64, f64, i32)>, sqlx::Error> = sqlx::query_as(&format!(
         "SELECT p.id, p.name, 
-                SUM(si.quantity) as quantity_sold,
+                SUM(si.quantity * COALESCE(si.unit_factor, 1)) as quantity_sold,
                 SUM(si.total) as total_revenue,
                 COUNT(DISTINCT si.sale_id) as times_sold
          FROM sale_items si
@@ -355,33 +423,49 @@ pub async fn get_sales_by_payment_method(
 
 pub async fn get_inventory_value(
     State(state): State<AppState>,
+    Query(params): Query<InventoryValueQuery>,
 ) -> Json<ApiResponse<InventoryValue>> {
     let db = state.db.lock().await;
+
+    let scoped = |select: &str| {
+        format!(
+            "{} {} AND (? IS NULL OR category_id IN (SELECT category_id FROM category_tree WHERE ancestor_id = ?))",
+            CATEGORY_TREE, select
+        )
+    };
     
     let total_products: i32 = sqlx::query_as::<_, (i32,)>(
-        "SELECT COUNT(*) FROM products WHERE is_active = 1"
+        &scoped("SELECT COUNT(*) FROM products WHERE is_active = 1")
     )
+    .bind(&params.category_id)
+    .bind(&params.category_id)
     .fetch_one(db.pool())
     .await
     .unwrap_or((0,)).0;
 
     let total_stock_value: f64 = sqlx::query_as::<_, (f64,)>(
-        "SELECT COALESCE(SUM(stock * cost), 0) FROM products WHERE is_active = 1"
+        &scoped("SELECT COALESCE(SUM(stock * cost), 0) FROM products WHERE is_active = 1")
     )
+    .bind(&params.category_id)
+    .bind(&params.category_id)
     .fetch_one(db.pool())
     .await
     .unwrap_or((0.0,)).0;
 
     let low_stock_items: i32 = sqlx::query_as::<_, (i32,)>(
-        "SELECT COUNT(*) FROM products WHERE stock <= min_stock AND is_active = 1"
+        &scoped("SELECT COUNT(*) FROM products WHERE stock <= min_stock AND is_active = 1")
     )
+    .bind(&params.category_id)
+    .bind(&params.category_id)
     .fetch_one(db.pool())
     .await
     .unwrap_or((0,)).0;
 
     let out_of_stock_items: i32 = sqlx::query_as::<_, (i32,)>(
-        "SELECT COUNT(*) FROM products WHERE stock = 0 AND is_active = 1"
+        &scoped("SELECT COUNT(*) FROM products WHERE stock = 0 AND is_active = 1")
     )
+    .bind(&params.category_id)
+    .bind(&params.category_id)
     .fetch_one(db.pool())
     .await
     .unwrap_or((0,)).0;
@@ -400,6 +484,134 @@ pub async fn get_inventory_value(
     })
 }
 
+type LocationStockRow = (String, String, String, i32, f64, f64);
+
+pub async fn get_inventory_by_location(
+    State(state): State<AppState>,
+) -> Json<ApiResponse<InventoryByLocation>> {
+    let db = state.db.lock().await;
+
+    let result: Result<Vec<LocationStockRow>, sqlx::Error> = sqlx::query_as(
+        r#"
+        SELECT l.id, l.name, l.location_type,
+               COUNT(CASE WHEN ps.quantity > 0 THEN 1 END),
+               COALESCE(SUM(ps.quantity), 0.0),
+               COALESCE(SUM(ps.quantity * p.cost), 0.0)
+        FROM locations l
+        LEFT JOIN product_stock ps ON ps.location_id = l.id
+        LEFT JOIN products p ON ps.product_id = p.id AND p.is_active = 1
+        WHERE l.is_active = 1
+        GROUP BY l.id, l.name, l.location_type
+        ORDER BY l.is_default DESC, l.name
+        "#
+    )
+    .fetch_all(db.pool())
+    .await;
+
+    // Shipped transfers not yet received belong to no location
+    let (in_transit_units, in_transit_value): (f64, f64) = sqlx::query_as(
+        r#"
+        SELECT COALESCE(SUM(i.quantity_shipped), 0.0), COALESCE(SUM(i.quantity_shipped * p.cost), 0.0)
+        FROM stock_transfer_items i
+        JOIN stock_transfers t ON i.transfer_id = t.id
+        JOIN products p ON i.product_id = p.id
+        WHERE t.status = 'in_transit'
+        "#
+    )
+    .fetch_one(db.pool())
+    .await
+    .unwrap_or((0.0, 0.0));
+
+    match result {
+        Ok(rows) => {
+            let locations: Vec<LocationInventoryValue> = rows
+                .into_iter()
+                .map(|(location_id, location_name, location_type, products_in_stock, total_units, stock_value)| {
+                    LocationInventoryValue {
+                        location_id,
+                        location_name,
+                        location_type,
+                        products_in_stock,
+                        total_units,
+                        stock_value,
+                    }
+                })
+                .collect();
+
+            Json(ApiResponse {
+                success: true,
+                data: Some(InventoryByLocation {
+                    locations,
+                    in_transit_units,
+                    in_transit_value,
+                }),
+                message: None,
+            })
+        }
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+type CategoryValueRow = (String, String, String, i32, f64, f64, f64);
+
+pub async fn get_inventory_value_by_category(
+    State(state): State<AppState>,
+) -> Json<ApiResponse<Vec<CategoryInventoryValue>>> {
+    let db = state.db.lock().await;
+
+    let result: Result<Vec<CategoryValueRow>, sqlx::Error> = sqlx::query_as(&format!(
+        r#"
+        {}
+        SELECT c.id, c.name, COALESCE(c.parent_id, ''),
+               COUNT(p.id),
+               COALESCE(SUM(p.stock), 0.0),
+               COALESCE(SUM(p.stock * p.cost), 0.0),
+               COALESCE(SUM(CASE WHEN t.category_id = c.id THEN p.stock * p.cost END), 0.0)
+        FROM categories c
+        JOIN category_tree t ON t.ancestor_id = c.id
+        LEFT JOIN products p ON p.category_id = t.category_id AND p.is_active = 1
+        GROUP BY c.id, c.name, c.parent_id
+        ORDER BY c.name
+        "#,
+        CATEGORY_TREE
+    ))
+    .fetch_all(db.pool())
+    .await;
+
+    match result {
+        Ok(rows) => Json(ApiResponse {
+            success: true,
+            data: Some(
+                rows.into_iter()
+                    .map(|(category_id, category_name, parent_id, total_products, total_units, stock_value, own_stock_value)| {
+                        CategoryInventoryValue {
+                            category_id,
+                            category_name,
+                            parent_id: if parent_id.is_empty() { None } else { Some(parent_id) },
+                            total_products,
+                            total_units,
+                            stock_value,
+                            own_stock_value,
+                        }
+                    })
+                    .collect(),
+            ),
+            message: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+type CategorySalesRow = (String, String, String, f64, f64, f64);
+
 pub async fn get_category_sales(
     State(state): State<AppState>,
     Query(params): Query<DateRangeQuery>,
@@ -412,19 +624,33 @@ pub async fn get_category_sales(
         "AND DATE(s.created_at) = DATE('now')".to_string()
     };
 
-    let result: Result<Vec<(String, String, f64, f64)>, sqlx::Error> = sqlx::query_as(&format!(
-        "SELECT COALESCE(c.id, 'uncategorized') as category_id,
-                COALESCE(c.name, 'Sin categoría') as category_name,
+    // Lines are attributed to the category they were sold under, so moving or merging
+    // categories doesn't rewrite history. Each category totals its whole subtree.
+    let result: Result<Vec<CategorySalesRow>, sqlx::Error> = sqlx::query_as(&format!(
+        "{tree}
+         SELECT c.id, c.name, COALESCE(c.parent_id, ''),
                 SUM(si.total) as total_sales,
-                SUM(si.quantity) as quantity_sold
+                SUM(si.quantity * COALESCE(si.unit_factor, 1)),
+                COALESCE(SUM(CASE WHEN t.category_id = c.id THEN si.total END), 0.0)
+         FROM categories c
+         JOIN category_tree t ON t.ancestor_id = c.id
+         JOIN sale_items si ON si.category_id = t.category_id
+         JOIN sales s ON si.sale_id = s.id
+         WHERE s.status = 'completed' {filter}
+         GROUP BY c.id, c.name, c.parent_id
+         UNION ALL
+         SELECT 'uncategorized', 'Sin categoría', '',
+                SUM(si.total) as total_sales,
+                SUM(si.quantity * COALESCE(si.unit_factor, 1)),
+                SUM(si.total)
          FROM sale_items si
-         JOIN products p ON si.product_id = p.id
-         LEFT JOIN categories c ON p.category_id = c.id
          JOIN sales s ON si.sale_id = s.id
-         WHERE s.status = 'completed' {}
-         GROUP BY c.id, c.name
+         WHERE s.status = 'completed' {filter}
+           AND NOT EXISTS (SELECT 1 FROM categories c WHERE c.id = si.category_id)
+         GROUP BY 1
          ORDER BY total_sales DESC",
-        date_filter
+        tree = CATEGORY_TREE,
+        filter = date_filter
     ))
     .fetch_all(db.pool())
     .await;
@@ -433,11 +659,13 @@ pub async fn get_category_sales(
         Ok(rows) => {
             let data: Vec<CategorySales> = rows
                 .into_iter()
-                .map(|(id, name, total, quantity)| CategorySales {
+                .map(|(id, name, parent_id, total, quantity, own_sales)| CategorySales {
                     category_id: id,
                     category_name: name,
+                    parent_id: if parent_id.is_empty() { None } else { Some(parent_id) },
                     total_sales: total,
                     quantity_sold: quantity,
+                    own_sales,
                 })
                 .collect();
 
@@ -509,4 +737,108 @@ pub async fn get_user_performance(
             message: Some(format!("Error: {}", e)),
         }),
     }
-}
\ No newline at end of file
+}
+
+type ShrinkageRow = (String, String, String, String, f64, f64, i32);
+
+/// Outgoing adjustments, stock-take and transfer shortfalls by reason, category and period, valued at cost.
+pub async fn get_shrinkage_report(
+    State(state): State<AppState>,
+    Query(params): Query<ShrinkageQuery>,
+) -> Json<ApiResponse<ShrinkageReport>> {
+    let db = state.db.lock().await;
+
+    let period_format = match params.period.as_deref() {
+        Some("day") => "%Y-%m-%d",
+        Some("week") => "%Y-W%W",
+        _ => "%Y-%m",
+    };
+
+    let result: Result<Vec<ShrinkageRow>, sqlx::Error> = sqlx::query_as(
+        r#"
+        SELECT CASE m.type
+                   WHEN 'count' THEN 'count_variance'
+                   WHEN 'transfer_loss' THEN 'transfer_loss'
+                   ELSE COALESCE(m.reason_code, 'unspecified')
+               END,
+               COALESCE(c.id, 'uncategorized'), COALESCE(c.name, 'Sin categoría'),
+               strftime(?, m.created_at),
+               SUM(ABS(m.quantity)),
+               SUM(ABS(m.quantity) * COALESCE(m.unit_cost, p.cost)),
+               COUNT(*)
+        FROM inventory_movements m
+        JOIN products p ON m.product_id = p.id
+        LEFT JOIN categories c ON p.category_id = c.id
+        WHERE (m.type IN ('adjustment_out', 'transfer_loss') OR (m.type = 'count' AND m.quantity < 0))
+          AND m.created_at >= COALESCE(?, date('now', '-30 days'))
+          AND m.created_at < COALESCE(date(?, '+1 day'), datetime('now', '+1 day'))
+        GROUP BY 1, 2, 3, 4
+        "#
+    )
+    .bind(period_format)
+    .bind(&params.start_date)
+    .bind(&params.end_date)
+    .fetch_all(db.pool())
+    .await;
+
+    let rows = match result {
+        Ok(rows) => rows,
+        Err(e) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some(format!("Error: {}", e)),
+            });
+        }
+    };
+
+    let add = |groups: &mut Vec<ShrinkageGroup>, key: &str, label: &str, quantity: f64, value: f64, movements: i32| {
+        match groups.iter_mut().find(|g| g.key == key) {
+            Some(group) => {
+                group.quantity += quantity;
+                group.value += value;
+                group.movements += movements;
+            }
+            None => groups.push(ShrinkageGroup {
+                key: key.to_string(),
+                label: label.to_string(),
+                quantity,
+                value,
+                movements,
+            }),
+        }
+    };
+
+    let mut by_reason = Vec::new();
+    let mut by_category = Vec::new();
+    let mut by_period = Vec::new();
+    for (reason, category_id, category_name, period, quantity, value, movements) in &rows {
+        // Count variances and transfer shortfalls carry no reason code
+        let reason_label = match reason.as_str() {
+            "count_variance" => "Diferencia de conteo",
+            "transfer_loss" => "Faltante en traspaso",
+            _ => ADJUSTMENT_REASONS
+                .iter()
+                .find(|(code, _)| code == reason)
+                .map_or("Sin motivo", |(_, name)| *name),
+        };
+        add(&mut by_reason, reason, reason_label, *quantity, *value, *movements);
+        add(&mut by_category, category_id, category_name, *quantity, *value, *movements);
+        add(&mut by_period, period, period, *quantity, *value, *movements);
+    }
+    by_reason.sort_by(|a, b| b.value.total_cmp(&a.value));
+    by_category.sort_by(|a, b| b.value.total_cmp(&a.value));
+    by_period.sort_by(|a, b| a.key.cmp(&b.key));
+
+    Json(ApiResponse {
+        success: true,
+        data: Some(ShrinkageReport {
+            total_quantity: rows.iter().fold(0.0, |total, row| total + row.4),
+            total_value: rows.iter().fold(0.0, |total, row| total + row.5),
+            by_reason,
+            by_category,
+            by_period,
+        }),
+        message: None,
+    })
+}
@@ -0,0 +1,407 @@
+use axum::{Json, extract::{Path, Query, State}};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use std::collections::HashMap;
+use crate::api::AppState;
+use crate::api::locations::{default_location_id, location_quantity};
+use crate::api::units::resolve_quantity;
+use crate::models::ApiResponse;
+
+/// Reservations that still hold stock; expired ones stop counting before the sweep marks them.
+const ACTIVE: &str = "status = 'active' AND (expires_at IS NULL OR expires_at > datetime('now'))";
+
+#[derive(Serialize)]
+pub struct StockReservation {
+    pub id: String,
+    pub reservation_number: String,
+    pub product_id: String,
+    pub product_name: String,
+    pub location_id: String,
+    /// In the product's base unit.
+    pub quantity: f64,
+    pub source: String,
+    pub reference_id: Option<String>,
+    pub customer_id: Option<String>,
+    pub status: String,
+    pub expires_at: Option<String>,
+    pub sale_id: Option<String>,
+    pub notes: Option<String>,
+    pub user_id: String,
+    pub created_at: String,
+    pub closed_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateReservationRequest {
+    pub product_id: String,
+    pub quantity: f64,
+    pub unit: Option<String>,
+    /// Defaults to the default location.
+    pub location_id: Option<String>,
+    /// 'held_sale', 'layaway', 'web_order' or 'manual'.
+    pub source: String,
+    /// Held sale, layaway or web order the stock is kept for.
+    pub reference_id: Option<String>,
+    pub customer_id: Option<String>,
+    /// Without it the reservation holds until released or fulfilled.
+    pub expires_in_minutes: Option<i64>,
+    pub notes: Option<String>,
+    pub user_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct ReservationQuery {
+    pub product_id: Option<String>,
+    pub reference_id: Option<String>,
+    pub status: Option<String>,
+}
+
+/// Stock at a location left to sell once active reservations are set aside, with the
+/// product name and whether it may go negative.
+pub async fn available_quantity(
+    conn: &mut SqliteConnection,
+    product_id: &str,
+    location_id: &str,
+) -> Result<(String, f64, bool), sqlx::Error> {
+    let (name, allow_negative_stock): (String, i32) =
+        sqlx::query_as("SELECT name, allow_negative_stock FROM products WHERE id = ?")
+            .bind(product_id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+    let on_hand = location_quantity(&mut *conn, product_id, location_id).await?;
+    let (reserved,): (f64,) = sqlx::query_as(&format!(
+        "SELECT COALESCE(SUM(quantity), 0.0) FROM stock_reservations WHERE product_id = ? AND location_id = ? AND {}",
+        ACTIVE
+    ))
+    .bind(product_id)
+    .bind(location_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok((name, on_hand - reserved, allow_negative_stock == 1))
+}
+
+/// Reserved quantity and the negative stock flag of every product, across all locations.
+pub async fn stock_reservation_summary(
+    pool: &Pool<Sqlite>,
+) -> Result<HashMap<String, (f64, bool)>, sqlx::Error> {
+    let rows: Vec<(String, f64, i32)> = sqlx::query_as(&format!(
+        r#"
+        SELECT p.id, COALESCE(r.reserved, 0.0), p.allow_negative_stock
+        FROM products p
+        LEFT JOIN (
+            SELECT product_id, SUM(quantity) AS reserved FROM stock_reservations
+            WHERE {}
+            GROUP BY product_id
+        ) r ON r.product_id = p.id
+        "#,
+        ACTIVE
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(id, reserved, allow_negative_stock)| (id, (reserved, allow_negative_stock == 1)))
+        .collect())
+}
+
+/// Marks reservations past their expiry; returns how many expired.
+pub async fn expire_reservations(pool: &Pool<Sqlite>) -> Result<u64, sqlx::Error> {
+    sqlx::query(
+        r#"
+        UPDATE stock_reservations SET status = 'expired', closed_at = expires_at
+        WHERE status = 'active' AND expires_at <= datetime('now')
+        "#
+    )
+    .execute(pool)
+    .await
+    .map(|r| r.rows_affected())
+}
+
+/// Releases reserved stock to a sale. `sold` holds the base quantity the sale takes of each
+/// product at `location_id`; each reservation is fulfilled up to what is left of it, and a
+/// reservation for more than that is split, keeping the remainder active under its own id.
+pub async fn fulfill_reservations(
+    conn: &mut SqliteConnection,
+    reservation_ids: &[String],
+    sale_id: &str,
+    location_id: &str,
+    sold: &mut HashMap<String, f64>,
+) -> Result<(), String> {
+    for reservation_id in reservation_ids {
+        let reservation: Option<(String, String, String, f64)> = sqlx::query_as(&format!(
+            "SELECT reservation_number, product_id, location_id, quantity FROM stock_reservations WHERE id = ? AND {}",
+            ACTIVE
+        ))
+        .bind(reservation_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let Some((reservation_number, product_id, reserved_at, quantity)) = reservation else {
+            return Err(format!("La reserva {} no está activa", reservation_id));
+        };
+        let remaining = match sold.get_mut(&product_id) {
+            Some(remaining) if reserved_at == location_id && *remaining > 1e-9 => remaining,
+            _ => return Err(format!("La reserva {} no corresponde a los productos de esta venta", reservation_id)),
+        };
+        let taken = quantity.min(*remaining);
+        *remaining -= taken;
+
+        if taken >= quantity - 1e-9 {
+            sqlx::query("UPDATE stock_reservations SET status = 'fulfilled', sale_id = ?, closed_at = datetime('now') WHERE id = ?")
+                .bind(sale_id)
+                .bind(reservation_id)
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| e.to_string())?;
+            continue;
+        }
+
+        // The part the sale takes is closed as a reservation of its own
+        sqlx::query(
+            r#"
+            INSERT INTO stock_reservations
+                (id, reservation_number, product_id, location_id, quantity, source, reference_id, customer_id,
+                 status, expires_at, sale_id, notes, user_id, created_at, closed_at)
+            SELECT ?, ?, product_id, location_id, ?, source, reference_id, customer_id,
+                   'fulfilled', expires_at, ?, ?, user_id, created_at, datetime('now')
+            FROM stock_reservations WHERE id = ?
+            "#
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(format!("RSV-{}", uuid::Uuid::new_v4().simple().to_string()[..8].to_uppercase()))
+        .bind(taken)
+        .bind(sale_id)
+        .bind(format!("Parte de la reserva {}", reservation_number))
+        .bind(reservation_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        sqlx::query("UPDATE stock_reservations SET quantity = quantity - ? WHERE id = ?")
+            .bind(taken)
+            .bind(reservation_id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+async fn fetch_reservation(
+    pool: &Pool<Sqlite>,
+    reservation_id: &str,
+) -> Result<Option<StockReservation>, sqlx::Error> {
+    let mut rows = query_reservations(pool, "r.id = ?", &[reservation_id]).await?;
+    Ok(rows.pop())
+}
+
+async fn query_reservations(
+    pool: &Pool<Sqlite>,
+    filter: &str,
+    bindings: &[&str],
+) -> Result<Vec<StockReservation>, sqlx::Error> {
+    let sql = format!(
+        r#"
+        SELECT r.id, r.reservation_number, r.product_id, p.name, r.location_id, r.quantity, r.source,
+               COALESCE(r.reference_id, ''), COALESCE(r.customer_id, ''),
+               CASE WHEN r.status = 'active' AND r.expires_at <= datetime('now') THEN 'expired' ELSE r.status END,
+               COALESCE(r.expires_at, ''), COALESCE(r.sale_id, ''), COALESCE(r.notes, ''),
+               r.user_id, r.created_at, COALESCE(r.closed_at, '')
+        FROM stock_reservations r
+        JOIN products p ON p.id = r.product_id
+        WHERE {}
+        ORDER BY r.created_at DESC
+        LIMIT 200
+        "#,
+        filter
+    );
+    let mut query = sqlx::query_as::<_, (String, String, String, String, String, f64, String, String, String, String, String, String, String, String, String, String)>(&sql);
+    for binding in bindings {
+        query = query.bind(*binding);
+    }
+    let rows = query.fetch_all(pool).await?;
+
+    let optional = |value: String| if value.is_empty() { None } else { Some(value) };
+    Ok(rows
+        .into_iter()
+        .map(|(id, reservation_number, product_id, product_name, location_id, quantity, source, reference_id, customer_id, status, expires_at, sale_id, notes, user_id, created_at, closed_at)| {
+            StockReservation {
+                id,
+                reservation_number,
+                product_id,
+                product_name,
+                location_id,
+                quantity,
+                source,
+                reference_id: optional(reference_id),
+                customer_id: optional(customer_id),
+                status,
+                expires_at: optional(expires_at),
+                sale_id: optional(sale_id),
+                notes: optional(notes),
+                user_id,
+                created_at,
+                closed_at: optional(closed_at),
+            }
+        })
+        .collect())
+}
+
+pub async fn list_reservations(
+    State(state): State<AppState>,
+    Query(params): Query<ReservationQuery>,
+) -> Json<ApiResponse<Vec<StockReservation>>> {
+    let db = state.db.lock().await;
+
+    let mut filter = String::from("1 = 1");
+    let mut bindings: Vec<&str> = Vec::new();
+    if let Some(product_id) = &params.product_id {
+        filter.push_str(" AND r.product_id = ?");
+        bindings.push(product_id);
+    }
+    if let Some(reference_id) = &params.reference_id {
+        filter.push_str(" AND r.reference_id = ?");
+        bindings.push(reference_id);
+    }
+    match params.status.as_deref() {
+        Some("active") => filter.push_str(" AND r.status = 'active' AND (r.expires_at IS NULL OR r.expires_at > datetime('now'))"),
+        Some("expired") => filter.push_str(" AND (r.status = 'expired' OR (r.status = 'active' AND r.expires_at <= datetime('now')))"),
+        Some(status) => {
+            filter.push_str(" AND r.status = ?");
+            bindings.push(status);
+        }
+        None => {}
+    }
+
+    match query_reservations(db.pool(), &filter, &bindings).await {
+        Ok(reservations) => Json(ApiResponse {
+            success: true,
+            data: Some(reservations),
+            message: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn create_reservation(
+    State(state): State<AppState>,
+    Json(payload): Json<CreateReservationRequest>,
+) -> Json<ApiResponse<StockReservation>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        if !matches!(payload.source.as_str(), "held_sale" | "layaway" | "web_order" | "manual") {
+            return Err("Origen inválido (held_sale, layaway, web_order o manual)".to_string());
+        }
+        if payload.quantity <= 0.0 {
+            return Err("La cantidad debe ser mayor a cero".to_string());
+        }
+        if payload.expires_in_minutes.is_some_and(|minutes| minutes <= 0) {
+            return Err("La vigencia debe ser mayor a cero minutos".to_string());
+        }
+
+        let mut tx = db.pool().begin().await.map_err(|e| e.to_string())?;
+
+        let resolved = resolve_quantity(&mut tx, &payload.product_id, payload.unit.as_deref(), payload.quantity).await?;
+        let location_id = match &payload.location_id {
+            Some(location_id) => location_id.clone(),
+            None => default_location_id(&mut tx).await.map_err(|e| e.to_string())?,
+        };
+
+        let (name, available, allow_negative_stock) = available_quantity(&mut tx, &payload.product_id, &location_id)
+            .await
+            .map_err(|e| e.to_string())?;
+        if !allow_negative_stock && resolved.base_quantity > available + 1e-9 {
+            return Err(format!("Stock insuficiente de {}: disponible {}", name, available.max(0.0)));
+        }
+
+        let reservation_id = uuid::Uuid::new_v4().to_string();
+        let reservation_number = format!("RSV-{}", uuid::Uuid::new_v4().simple().to_string()[..8].to_uppercase());
+        sqlx::query(
+            r#"
+            INSERT INTO stock_reservations
+                (id, reservation_number, product_id, location_id, quantity, source, reference_id, customer_id,
+                 status, expires_at, notes, user_id, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, 'active',
+                    CASE WHEN ? IS NULL THEN NULL ELSE datetime('now', ?) END, ?, ?, datetime('now'))
+            "#
+        )
+        .bind(&reservation_id)
+        .bind(&reservation_number)
+        .bind(&payload.product_id)
+        .bind(&location_id)
+        .bind(resolved.base_quantity)
+        .bind(&payload.source)
+        .bind(&payload.reference_id)
+        .bind(&payload.customer_id)
+        .bind(payload.expires_in_minutes)
+        .bind(payload.expires_in_minutes.map(|minutes| format!("+{} minutes", minutes)))
+        .bind(&payload.notes)
+        .bind(&payload.user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        fetch_reservation(db.pool(), &reservation_id)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Reserva no encontrada".to_string())
+    }
+    .await;
+
+    match result {
+        Ok(reservation) => Json(ApiResponse {
+            success: true,
+            data: Some(reservation),
+            message: Some("Stock reservado".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        }),
+    }
+}
+
+pub async fn release_reservation(
+    State(state): State<AppState>,
+    Path(reservation_id): Path<String>,
+) -> Json<ApiResponse<String>> {
+    let db = state.db.lock().await;
+
+    let result = sqlx::query(&format!(
+        "UPDATE stock_reservations SET status = 'released', closed_at = datetime('now') WHERE id = ? AND {}",
+        ACTIVE
+    ))
+    .bind(&reservation_id)
+    .execute(db.pool())
+    .await;
+
+    match result {
+        Ok(r) if r.rows_affected() > 0 => Json(ApiResponse {
+            success: true,
+            data: Some(reservation_id),
+            message: Some("Reserva liberada".to_string()),
+        }),
+        Ok(_) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Solo se pueden liberar reservas activas".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
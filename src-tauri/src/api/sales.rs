@@ -1,6 +1,14 @@
 use axum::{Json, extract::State};
 use serde::Deserialize;
+use sqlx::{Sqlite, Transaction};
+use std::collections::HashMap;
 use crate::api::AppState;
+use crate::api::kits::{consume_for_sale, kit_components};
+use crate::api::locations::shift_location_id;
+use crate::api::loyalty::{customer_benefits, points_for_purchase, record_points, tier_price};
+use crate::api::reservations::{available_quantity, fulfill_reservations};
+use crate::api::settings::get_setting;
+use crate::api::units::resolve_quantity;
 use crate::models::{ApiResponse, Sale};
 
 #[derive(Deserialize)]
@@ -14,12 +22,18 @@ pub struct CreateSaleRequest {
     pub discount_amount: f64,
     pub total: f64,
     pub payment_method: Option<String>,
+    /// Cash handed over by the customer; the difference with the total is given back as change.
+    pub amount_tendered: Option<f64>,
+    /// Reservations (held sale, layaway, web order) this sale picks up.
+    pub reservation_ids: Option<Vec<String>>,
 }
 
 #[derive(Deserialize)]
 pub struct SaleItemRequest {
     pub product_id: String,
     pub quantity: f64,
+    /// Unit the quantity and price are expressed in; defaults to the product's unit.
+    pub unit: Option<String>,
     pub unit_price: f64,
     pub discount_amount: f64,
     pub tax_rate: f64,
@@ -30,16 +44,107 @@ pub async fn create_sale(
     Json(payload): Json<CreateSaleRequest>,
 ) -> Json<ApiResponse<Sale>> {
     let db = state.db.lock().await;
-    
+
+    let mut tx = match db.pool().begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some(format!("Error al crear venta: {}", e)),
+            });
+        }
+    };
+
+    let result = insert_sale(&mut tx, &payload).await;
+
+    match result {
+        Ok(sale) => match tx.commit().await {
+            Ok(_) => Json(ApiResponse {
+                success: true,
+                data: Some(sale),
+                message: Some("Venta creada exitosamente".to_string()),
+            }),
+            Err(e) => Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some(format!("Error al crear venta: {}", e)),
+            }),
+        },
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error al crear venta: {}", e)),
+        }),
+    }
+}
+
+async fn insert_sale(
+    tx: &mut Transaction<'_, Sqlite>,
+    payload: &CreateSaleRequest,
+) -> Result<Sale, String> {
     // Generate sale number
     let sale_number = format!("SALE-{}", uuid::Uuid::new_v4().simple().to_string()[..8].to_uppercase());
     let sale_id = uuid::Uuid::new_v4().to_string();
 
+    // Stock is taken from the location of the register the shift is open on
+    let location_id = shift_location_id(tx, payload.shift_id.as_deref())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // The customer's tier may lower prices and take a discount off what's left
+    let benefits = match &payload.customer_id {
+        Some(customer_id) => customer_benefits(tx, customer_id).await.map_err(|e| e.to_string())?,
+        None => None,
+    };
+    let mut unit_prices = Vec::with_capacity(payload.items.len());
+    let (mut subtotal, mut tax_amount, mut discount_amount, mut total) =
+        (payload.subtotal, payload.tax_amount, payload.discount_amount, payload.total);
+    for item in &payload.items {
+        let mut unit_price = item.unit_price;
+        if let Some(benefits) = &benefits {
+            // Tier prices are per base unit
+            let resolved = resolve_quantity(tx, &item.product_id, item.unit.as_deref(), item.quantity).await?;
+            let listed = tier_price(tx, &benefits.tier_id, &item.product_id).await.map_err(|e| e.to_string())?;
+            if let Some(price) = listed.map(|price| price * resolved.factor) {
+                if price < unit_price {
+                    let reduction = item.quantity * (unit_price - price);
+                    subtotal -= reduction;
+                    tax_amount -= reduction * item.tax_rate;
+                    total -= reduction * (1.0 + item.tax_rate);
+                    unit_price = price;
+                }
+            }
+        }
+        unit_prices.push(unit_price);
+    }
+    let mut loyalty_discount = 0.0;
+    if let Some(benefits) = benefits.as_ref().filter(|benefits| benefits.discount_percent > 0.0) {
+        let rate = benefits.discount_percent / 100.0;
+        loyalty_discount = round_cents((subtotal - discount_amount) * rate);
+        let tax_reduction = round_cents(tax_amount * rate);
+        discount_amount += loyalty_discount;
+        tax_amount -= tax_reduction;
+        total -= loyalty_discount + tax_reduction;
+    }
+    if benefits.is_some() {
+        (subtotal, tax_amount, discount_amount, total) =
+            (round_cents(subtotal), round_cents(tax_amount), round_cents(discount_amount), round_cents(total));
+    }
+    let points_earned = match &payload.customer_id {
+        Some(_) => {
+            let multiplier = benefits.as_ref().map_or(1.0, |benefits| benefits.points_multiplier);
+            points_for_purchase(tx, total, multiplier).await.map_err(|e| e.to_string())?
+        }
+        None => 0,
+    };
+    let loyalty_tier = benefits.map(|benefits| benefits.tier);
+
     // Insert sale
-    let result = sqlx::query(
+    sqlx::query(
         r#"
-        INSERT INTO sales (id, sale_number, user_id, customer_id, shift_id, subtotal, tax_amount, discount_amount, total, status, payment_status, created_at)
-        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, 'completed', 'paid', datetime('now'))
+        INSERT INTO sales (id, sale_number, user_id, customer_id, shift_id, subtotal, tax_amount, discount_amount, total, status, payment_status, loyalty_tier, loyalty_discount, points_earned, created_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, 'completed', 'paid', ?, ?, ?, datetime('now'))
         "#
     )
     .bind(&sale_id)
@@ -47,83 +152,138 @@ pub async fn create_sale(
     .bind(&payload.user_id)
     .bind(&payload.customer_id)
     .bind(&payload.shift_id)
-    .bind(payload.subtotal)
-    .bind(payload.tax_amount)
-    .bind(payload.discount_amount)
-    .bind(payload.total)
-    .execute(db.pool())
-    .await;
+    .bind(subtotal)
+    .bind(tax_amount)
+    .bind(discount_amount)
+    .bind(total)
+    .bind(&loyalty_tier)
+    .bind(loyalty_discount)
+    .bind(points_earned)
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| e.to_string())?;
 
-    match result {
-        Ok(_) => {
-            // Insert sale items
-            for item in payload.items {
-                let _ = sqlx::query(
-                    r#"
-                    INSERT INTO sale_items (id, sale_id, product_id, quantity, unit_price, discount_amount, tax_rate, subtotal, total)
-                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
-                    "#
-                )
-                .bind(uuid::Uuid::new_v4().to_string())
-                .bind(&sale_id)
-                .bind(&item.product_id)
-                .bind(item.quantity)
-                .bind(item.unit_price)
-                .bind(item.discount_amount)
-                .bind(item.tax_rate)
-                .bind(item.quantity * item.unit_price)
-                .bind(item.quantity * item.unit_price - item.discount_amount)
-                .execute(db.pool())
-                .await;
-
-                // Update stock
-                let _ = sqlx::query(
-                    "UPDATE products SET stock = stock - ? WHERE id = ?"
-                )
-                .bind(item.quantity as i32)
-                .bind(&item.product_id)
-                .execute(db.pool())
-                .await;
+    if let Some(customer_id) = &payload.customer_id {
+        record_points(tx, customer_id, points_earned, "sale", Some(&sale_id), Some(&payload.user_id))
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    // Stock reserved for this customer is released to the sale, up to what it takes of each
+    // product; kits may take their components instead
+    if let Some(reservation_ids) = &payload.reservation_ids {
+        let mut sold: HashMap<String, f64> = HashMap::new();
+        for item in &payload.items {
+            let resolved = resolve_quantity(tx, &item.product_id, item.unit.as_deref(), item.quantity).await?;
+            *sold.entry(item.product_id.clone()).or_default() += resolved.base_quantity;
+            for (component_id, _, per_kit) in kit_components(tx, &item.product_id).await.map_err(|e| e.to_string())? {
+                *sold.entry(component_id).or_default() += per_kit * resolved.base_quantity;
             }
+        }
+        fulfill_reservations(tx, reservation_ids, &sale_id, &location_id, &mut sold).await?;
+    }
+    let refuse_overselling = get_setting(tx, "refuse_overselling")
+        .await
+        .map_err(|e| e.to_string())?
+        .is_none_or(|value| value != "0");
 
-            let payment_method = payload.payment_method.unwrap_or_else(|| "cash".to_string());
-            let _ = sqlx::query(
-                r#"
-                INSERT INTO payments (id, sale_id, method, amount, status, created_at)
-                VALUES (?, ?, ?, ?, 'completed', datetime('now'))
-                "#
-            )
-            .bind(uuid::Uuid::new_v4().to_string())
-            .bind(&sale_id)
-            .bind(&payment_method)
-            .bind(payload.total)
-            .execute(db.pool())
-            .await;
-
-            let sale = Sale {
-                id: sale_id,
-                sale_number,
-                user_id: payload.user_id,
-                customer_id: payload.customer_id,
-                shift_id: payload.shift_id,
-                subtotal: payload.subtotal,
-                tax_amount: payload.tax_amount,
-                discount_amount: payload.discount_amount,
-                total: payload.total,
-                status: "completed".to_string(),
-                payment_status: "paid".to_string(),
-            };
-
-            Json(ApiResponse {
-                success: true,
-                data: Some(sale),
-                message: Some("Venta creada exitosamente".to_string()),
-            })
+    // Insert sale items
+    for (item, unit_price) in payload.items.iter().zip(unit_prices) {
+        let resolved = resolve_quantity(tx, &item.product_id, item.unit.as_deref(), item.quantity).await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO sale_items (id, sale_id, product_id, category_id, quantity, unit, unit_factor, unit_price, discount_amount, tax_rate, subtotal, total)
+            VALUES (?, ?, ?, (SELECT category_id FROM products WHERE id = ?), ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(&sale_id)
+        .bind(&item.product_id)
+        .bind(&item.product_id)
+        .bind(item.quantity)
+        .bind(&resolved.unit)
+        .bind(resolved.factor)
+        .bind(unit_price)
+        .bind(item.discount_amount)
+        .bind(item.tax_rate)
+        .bind(item.quantity * unit_price)
+        .bind(item.quantity * unit_price - item.discount_amount)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        // Update stock; kits draw on their components
+        consume_for_sale(tx, &item.product_id, &location_id, resolved.base_quantity, &sale_id, &sale_number, &payload.user_id)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        // Unless the store allows overselling, stock reserved for others can't be sold;
+        // kits are limited by their components
+        if !refuse_overselling {
+            continue;
+        }
+        let components = kit_components(tx, &item.product_id).await.map_err(|e| e.to_string())?;
+        let consumed: Vec<&str> = if components.is_empty() {
+            vec![item.product_id.as_str()]
+        } else {
+            components.iter().map(|(component_id, _, _)| component_id.as_str()).collect()
+        };
+        for product_id in consumed {
+            let (name, available, allow_negative_stock) = available_quantity(tx, product_id, &location_id)
+                .await
+                .map_err(|e| e.to_string())?;
+            if !allow_negative_stock && available < -1e-9 {
+                return Err(format!("Stock insuficiente de {}: faltan {}", name, -available));
+            }
         }
-        Err(e) => Json(ApiResponse {
-            success: false,
-            data: None,
-            message: Some(format!("Error al crear venta: {}", e)),
-        }),
     }
+
+    let payment_method = payload.payment_method.clone().unwrap_or_else(|| "cash".to_string());
+    let change_given = match payload.amount_tendered {
+        Some(tendered) if payment_method == "cash" => {
+            if tendered + 0.005 < total {
+                return Err(format!("El efectivo recibido no cubre el total de {:.2}", total));
+            }
+            round_cents(tendered - total)
+        }
+        _ => 0.0,
+    };
+    sqlx::query(
+        r#"
+        INSERT INTO payments (id, sale_id, method, amount, tendered, change_given, status, created_at)
+        VALUES (?, ?, ?, ?, ?, ?, 'completed', datetime('now'))
+        "#
+    )
+    .bind(uuid::Uuid::new_v4().to_string())
+    .bind(&sale_id)
+    .bind(&payment_method)
+    .bind(total)
+    .bind(if payment_method == "cash" { payload.amount_tendered } else { None })
+    .bind(change_given)
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(Sale {
+        id: sale_id,
+        sale_number,
+        user_id: payload.user_id.clone(),
+        customer_id: payload.customer_id.clone(),
+        shift_id: payload.shift_id.clone(),
+        subtotal,
+        tax_amount,
+        discount_amount,
+        total,
+        status: "completed".to_string(),
+        payment_status: "paid".to_string(),
+        loyalty_tier,
+        loyalty_discount,
+        points_earned,
+        change_given,
+    })
+}
+
+fn round_cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
 }
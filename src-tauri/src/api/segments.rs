@@ -0,0 +1,549 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+use crate::api::AppState;
+use crate::models::ApiResponse;
+
+/// Completed purchases per customer, as in the customer stats.
+const CUSTOMER_AGGREGATES: &str = r#"
+    SELECT customer_id, COUNT(*) AS purchases, SUM(total) AS total_spent, MAX(created_at) AS last_purchase
+    FROM sales
+    WHERE status = 'completed' AND customer_id IS NOT NULL
+    GROUP BY customer_id
+"#;
+
+const EXPORT_COLUMNS: [&str; 9] = [
+    "name", "email", "phone", "birth_date", "loyalty_tier", "purchases", "total_spent", "last_purchase", "id",
+];
+
+/// A condition every member of a segment meets.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SegmentRule {
+    /// Spent more than `amount` in the last `days` days.
+    MinSpent { amount: f64, days: i64 },
+    /// At least `count` purchases in the last `days` days.
+    MinPurchases { count: i64, days: i64 },
+    /// Birthday in `month` (1-12), or in the current month when omitted.
+    BirthdayMonth { month: Option<u32> },
+    /// Bought before, but not in the last `days` days.
+    Inactive { days: i64 },
+    LoyaltyTier { tier: String },
+}
+
+#[derive(Serialize)]
+pub struct CustomerSegment {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub rules: Vec<SegmentRule>,
+    pub member_count: i64,
+    pub created_by: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct SegmentRequest {
+    pub name: String,
+    pub description: Option<String>,
+    /// Members must meet all of them.
+    pub rules: Vec<SegmentRule>,
+    pub user_id: String,
+}
+
+#[derive(Serialize)]
+pub struct SegmentMember {
+    pub id: String,
+    pub name: String,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub birth_date: Option<String>,
+    pub loyalty_tier: Option<String>,
+    pub purchases: i64,
+    pub total_spent: f64,
+    pub last_purchase: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RfmQuery {
+    /// Sales considered, counting back from today; 12 by default.
+    pub months: Option<i64>,
+    pub segment: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CustomerRfm {
+    pub customer_id: String,
+    pub name: String,
+    pub last_purchase: String,
+    pub recency_days: i64,
+    pub frequency: i64,
+    pub monetary: f64,
+    /// 1 (worst) to 5 (best), by quintile among customers who bought in the period.
+    pub recency_score: u8,
+    pub frequency_score: u8,
+    pub monetary_score: u8,
+    /// The three scores as in "545".
+    pub rfm: String,
+    /// 'champions', 'loyal', 'new', 'at_risk', 'lost' or 'needs_attention'.
+    pub segment: String,
+}
+
+fn rfm_segment(recency: u8, frequency: u8) -> &'static str {
+    match (recency, frequency) {
+        (4..=5, 4..=5) => "champions",
+        (3..=5, 3..=5) => "loyal",
+        (4..=5, _) => "new",
+        (1..=2, 3..=5) => "at_risk",
+        (1..=2, _) => "lost",
+        _ => "needs_attention",
+    }
+}
+
+fn validate_rules(rules: &[SegmentRule]) -> Result<(), String> {
+    if rules.is_empty() {
+        return Err("El segmento necesita al menos una regla".to_string());
+    }
+    for rule in rules {
+        let valid = match rule {
+            SegmentRule::MinSpent { amount, days } => *amount >= 0.0 && *days > 0,
+            SegmentRule::MinPurchases { count, days } => *count > 0 && *days > 0,
+            SegmentRule::BirthdayMonth { month } => month.is_none_or(|m| (1..=12).contains(&m)),
+            SegmentRule::Inactive { days } => *days > 0,
+            SegmentRule::LoyaltyTier { tier } => !tier.trim().is_empty(),
+        };
+        if !valid {
+            return Err("Hay reglas con valores inválidos".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// SQL condition over customers `c` and their aggregates `a`, plus the text values to bind.
+/// Numbers come typed from the rules and are written inline.
+fn rules_condition(rules: &[SegmentRule]) -> (String, Vec<String>) {
+    let mut conditions = vec!["c.is_active = 1".to_string()];
+    let mut bindings = Vec::new();
+    for rule in rules {
+        conditions.push(match rule {
+            SegmentRule::MinSpent { amount, days } => format!(
+                "(SELECT COALESCE(SUM(s.total), 0) FROM sales s WHERE s.customer_id = c.id AND s.status = 'completed' AND s.created_at >= datetime('now', '-{} days')) > {}",
+                days, amount
+            ),
+            SegmentRule::MinPurchases { count, days } => format!(
+                "(SELECT COUNT(*) FROM sales s WHERE s.customer_id = c.id AND s.status = 'completed' AND s.created_at >= datetime('now', '-{} days')) >= {}",
+                days, count
+            ),
+            SegmentRule::BirthdayMonth { month: Some(month) } => {
+                format!("CAST(strftime('%m', c.birth_date) AS INTEGER) = {}", month)
+            }
+            SegmentRule::BirthdayMonth { month: None } => {
+                "strftime('%m', c.birth_date) = strftime('%m', 'now', 'localtime')".to_string()
+            }
+            SegmentRule::Inactive { days } => {
+                format!("a.last_purchase IS NOT NULL AND a.last_purchase < datetime('now', '-{} days')", days)
+            }
+            SegmentRule::LoyaltyTier { tier } => {
+                bindings.push(tier.clone());
+                "c.loyalty_tier = ?".to_string()
+            }
+        });
+    }
+    (conditions.join(" AND "), bindings)
+}
+
+async fn segment_members(pool: &Pool<Sqlite>, rules: &[SegmentRule]) -> Result<Vec<SegmentMember>, sqlx::Error> {
+    let (condition, bindings) = rules_condition(rules);
+    let sql = format!(
+        r#"
+        SELECT c.id, c.name, COALESCE(c.email, ''), COALESCE(c.phone, ''), COALESCE(c.birth_date, ''),
+               COALESCE(c.loyalty_tier, ''), COALESCE(a.purchases, 0), COALESCE(a.total_spent, 0.0),
+               COALESCE(a.last_purchase, '')
+        FROM customers c
+        LEFT JOIN ({}) a ON a.customer_id = c.id
+        WHERE {}
+        ORDER BY c.name COLLATE NOCASE
+        "#,
+        CUSTOMER_AGGREGATES, condition
+    );
+    let mut query = sqlx::query_as::<_, (String, String, String, String, String, String, i64, f64, String)>(&sql);
+    for binding in &bindings {
+        query = query.bind(binding);
+    }
+    let rows = query.fetch_all(pool).await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(id, name, email, phone, birth_date, loyalty_tier, purchases, total_spent, last_purchase)| SegmentMember {
+            id,
+            name,
+            email: if email.is_empty() { None } else { Some(email) },
+            phone: if phone.is_empty() { None } else { Some(phone) },
+            birth_date: if birth_date.is_empty() { None } else { Some(birth_date) },
+            loyalty_tier: if loyalty_tier.is_empty() { None } else { Some(loyalty_tier) },
+            purchases,
+            total_spent,
+            last_purchase: if last_purchase.is_empty() { None } else { Some(last_purchase) },
+        })
+        .collect())
+}
+
+async fn fetch_segment(pool: &Pool<Sqlite>, segment_id: &str) -> Result<Option<CustomerSegment>, String> {
+    let row: Option<(String, String, String, String, String, String, String)> = sqlx::query_as(
+        r#"
+        SELECT id, name, COALESCE(description, ''), rules, created_by, created_at, updated_at
+        FROM customer_segments
+        WHERE id = ? AND is_active = 1
+        "#
+    )
+    .bind(segment_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let Some((id, name, description, rules, created_by, created_at, updated_at)) = row else {
+        return Ok(None);
+    };
+    let rules: Vec<SegmentRule> = serde_json::from_str(&rules).map_err(|e| e.to_string())?;
+    let member_count = segment_members(pool, &rules).await.map_err(|e| e.to_string())?.len() as i64;
+
+    Ok(Some(CustomerSegment {
+        id,
+        name,
+        description: if description.is_empty() { None } else { Some(description) },
+        rules,
+        member_count,
+        created_by,
+        created_at,
+        updated_at,
+    }))
+}
+
+type RfmRow = (String, String, String, i64, i64, f64, f64, f64, f64);
+
+pub async fn get_rfm_scores(
+    State(state): State<AppState>,
+    Query(params): Query<RfmQuery>,
+) -> Json<ApiResponse<Vec<CustomerRfm>>> {
+    let db = state.db.lock().await;
+
+    // Ties share a score: cumulative distribution instead of NTILE
+    let result: Result<Vec<RfmRow>, sqlx::Error> = sqlx::query_as(
+        r#"
+        WITH activity AS (
+            SELECT customer_id, COUNT(*) AS frequency, SUM(total) AS monetary, MAX(created_at) AS last_purchase
+            FROM sales
+            WHERE status = 'completed' AND customer_id IS NOT NULL AND created_at >= datetime('now', ?)
+            GROUP BY customer_id
+        )
+        SELECT a.customer_id, c.name, a.last_purchase,
+               CAST(julianday('now') - julianday(a.last_purchase) AS INTEGER),
+               a.frequency, a.monetary,
+               CUME_DIST() OVER (ORDER BY a.last_purchase),
+               CUME_DIST() OVER (ORDER BY a.frequency),
+               CUME_DIST() OVER (ORDER BY a.monetary)
+        FROM activity a
+        JOIN customers c ON c.id = a.customer_id
+        WHERE c.is_active = 1
+        "#
+    )
+    .bind(format!("-{} months", params.months.unwrap_or(12).max(1)))
+    .fetch_all(db.pool())
+    .await;
+
+    match result {
+        Ok(rows) => {
+            let score = |distribution: f64| (distribution * 5.0).ceil().clamp(1.0, 5.0) as u8;
+            let mut scores: Vec<CustomerRfm> = rows
+                .into_iter()
+                .map(|(customer_id, name, last_purchase, recency_days, frequency, monetary, r, f, m)| {
+                    let (recency_score, frequency_score, monetary_score) = (score(r), score(f), score(m));
+                    CustomerRfm {
+                        customer_id,
+                        name,
+                        last_purchase,
+                        recency_days,
+                        frequency,
+                        monetary,
+                        recency_score,
+                        frequency_score,
+                        monetary_score,
+                        rfm: format!("{}{}{}", recency_score, frequency_score, monetary_score),
+                        segment: rfm_segment(recency_score, frequency_score).to_string(),
+                    }
+                })
+                .filter(|rfm| params.segment.as_ref().is_none_or(|segment| &rfm.segment == segment))
+                .collect();
+            scores.sort_by(|a, b| b.rfm.cmp(&a.rfm).then_with(|| b.monetary.total_cmp(&a.monetary)));
+
+            Json(ApiResponse {
+                success: true,
+                data: Some(scores),
+                message: None,
+            })
+        }
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn list_segments(
+    State(state): State<AppState>,
+) -> Json<ApiResponse<Vec<CustomerSegment>>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        let ids: Vec<(String,)> = sqlx::query_as("SELECT id FROM customer_segments WHERE is_active = 1 ORDER BY name")
+            .fetch_all(db.pool())
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let mut segments = Vec::new();
+        for (id,) in ids {
+            segments.extend(fetch_segment(db.pool(), &id).await?);
+        }
+        Ok::<_, String>(segments)
+    }
+    .await;
+
+    match result {
+        Ok(segments) => Json(ApiResponse {
+            success: true,
+            data: Some(segments),
+            message: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn create_segment(
+    State(state): State<AppState>,
+    Json(payload): Json<SegmentRequest>,
+) -> Json<ApiResponse<CustomerSegment>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        if payload.name.trim().is_empty() {
+            return Err("El nombre del segmento es obligatorio".to_string());
+        }
+        validate_rules(&payload.rules)?;
+
+        let segment_id = uuid::Uuid::new_v4().to_string();
+        sqlx::query(
+            r#"
+            INSERT INTO customer_segments (id, name, description, rules, is_active, created_by, created_at, updated_at)
+            VALUES (?, ?, ?, ?, 1, ?, datetime('now'), datetime('now'))
+            "#
+        )
+        .bind(&segment_id)
+        .bind(payload.name.trim())
+        .bind(&payload.description)
+        .bind(serde_json::to_string(&payload.rules).map_err(|e| e.to_string())?)
+        .bind(&payload.user_id)
+        .execute(db.pool())
+        .await
+        .map_err(|e| e.to_string())?;
+
+        fetch_segment(db.pool(), &segment_id)
+            .await?
+            .ok_or_else(|| "Segmento no encontrado".to_string())
+    }
+    .await;
+
+    match result {
+        Ok(segment) => Json(ApiResponse {
+            success: true,
+            data: Some(segment),
+            message: Some("Segmento creado".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        }),
+    }
+}
+
+pub async fn update_segment(
+    State(state): State<AppState>,
+    Path(segment_id): Path<String>,
+    Json(payload): Json<SegmentRequest>,
+) -> Json<ApiResponse<CustomerSegment>> {
+    let db = state.db.lock().await;
+
+    let result = async {
+        if payload.name.trim().is_empty() {
+            return Err("El nombre del segmento es obligatorio".to_string());
+        }
+        validate_rules(&payload.rules)?;
+
+        let updated = sqlx::query(
+            r#"
+            UPDATE customer_segments SET name = ?, description = ?, rules = ?, updated_at = datetime('now')
+            WHERE id = ? AND is_active = 1
+            "#
+        )
+        .bind(payload.name.trim())
+        .bind(&payload.description)
+        .bind(serde_json::to_string(&payload.rules).map_err(|e| e.to_string())?)
+        .bind(&segment_id)
+        .execute(db.pool())
+        .await
+        .map_err(|e| e.to_string())?;
+        if updated.rows_affected() == 0 {
+            return Err("Segmento no encontrado".to_string());
+        }
+
+        fetch_segment(db.pool(), &segment_id)
+            .await?
+            .ok_or_else(|| "Segmento no encontrado".to_string())
+    }
+    .await;
+
+    match result {
+        Ok(segment) => Json(ApiResponse {
+            success: true,
+            data: Some(segment),
+            message: Some("Segmento actualizado".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        }),
+    }
+}
+
+pub async fn delete_segment(
+    State(state): State<AppState>,
+    Path(segment_id): Path<String>,
+) -> Json<ApiResponse<String>> {
+    let db = state.db.lock().await;
+
+    let result = sqlx::query(
+        "UPDATE customer_segments SET is_active = 0, updated_at = datetime('now') WHERE id = ? AND is_active = 1"
+    )
+    .bind(&segment_id)
+    .execute(db.pool())
+    .await;
+
+    match result {
+        Ok(r) if r.rows_affected() > 0 => Json(ApiResponse {
+            success: true,
+            data: Some(segment_id),
+            message: Some("Segmento eliminado".to_string()),
+        }),
+        Ok(_) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Segmento no encontrado".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+async fn members_of(pool: &Pool<Sqlite>, segment_id: &str) -> Result<(String, Vec<SegmentMember>), String> {
+    let row: Option<(String, String)> =
+        sqlx::query_as("SELECT name, rules FROM customer_segments WHERE id = ? AND is_active = 1")
+            .bind(segment_id)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+    let (name, rules) = row.ok_or_else(|| "Segmento no encontrado".to_string())?;
+    let rules: Vec<SegmentRule> = serde_json::from_str(&rules).map_err(|e| e.to_string())?;
+
+    let members = segment_members(pool, &rules).await.map_err(|e| e.to_string())?;
+    Ok((name, members))
+}
+
+pub async fn get_segment_members(
+    State(state): State<AppState>,
+    Path(segment_id): Path<String>,
+) -> Json<ApiResponse<Vec<SegmentMember>>> {
+    let db = state.db.lock().await;
+
+    match members_of(db.pool(), &segment_id).await {
+        Ok((_, members)) => Json(ApiResponse {
+            success: true,
+            data: Some(members),
+            message: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(e),
+        }),
+    }
+}
+
+pub async fn export_segment_members(
+    State(state): State<AppState>,
+    Path(segment_id): Path<String>,
+) -> Response {
+    let db = state.db.lock().await;
+
+    let file = async {
+        let (name, members) = members_of(db.pool(), &segment_id).await?;
+
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(EXPORT_COLUMNS).map_err(|e| e.to_string())?;
+        for member in members {
+            writer
+                .write_record([
+                    member.name,
+                    member.email.unwrap_or_default(),
+                    member.phone.unwrap_or_default(),
+                    member.birth_date.unwrap_or_default(),
+                    member.loyalty_tier.unwrap_or_default(),
+                    member.purchases.to_string(),
+                    member.total_spent.to_string(),
+                    member.last_purchase.unwrap_or_default(),
+                    member.id,
+                ])
+                .map_err(|e| e.to_string())?;
+        }
+        let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+        Ok::<_, String>((name, bytes))
+    }
+    .await;
+
+    match file {
+        Ok((name, bytes)) => {
+            let file_name: String = name
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c.to_ascii_lowercase() } else { '_' })
+                .collect();
+            (
+                [
+                    (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                    (header::CONTENT_DISPOSITION, format!("attachment; filename=\"segmento_{}.csv\"", file_name)),
+                ],
+                bytes,
+            )
+                .into_response()
+        }
+        Err(e) => Json(ApiResponse::<String> {
+            success: false,
+            data: None,
+            message: Some(e),
+        })
+        .into_response(),
+    }
+}
@@ -0,0 +1,101 @@
+use axum::{Json, extract::{State, Path}};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use crate::api::AppState;
+use crate::api::auth::is_manager;
+use crate::models::ApiResponse;
+
+#[derive(Serialize)]
+pub struct Setting {
+    pub key: String,
+    pub value: String,
+    pub updated_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateSettingRequest {
+    pub value: String,
+    pub user_id: String,
+}
+
+pub async fn get_setting(conn: &mut SqliteConnection, key: &str) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_as::<_, (String,)>("SELECT value FROM settings WHERE key = ?")
+        .bind(key)
+        .fetch_optional(conn)
+        .await
+        .map(|row| row.map(|t| t.0))
+}
+
+pub async fn list_settings(
+    State(state): State<AppState>,
+) -> Json<ApiResponse<Vec<Setting>>> {
+    let db = state.db.lock().await;
+
+    let result: Result<Vec<(String, String, String)>, sqlx::Error> =
+        sqlx::query_as("SELECT key, value, updated_at FROM settings ORDER BY key")
+            .fetch_all(db.pool())
+            .await;
+
+    match result {
+        Ok(rows) => Json(ApiResponse {
+            success: true,
+            data: Some(
+                rows.into_iter()
+                    .map(|(key, value, updated_at)| Setting { key, value, updated_at })
+                    .collect(),
+            ),
+            message: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn update_setting(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    Json(payload): Json<UpdateSettingRequest>,
+) -> Json<ApiResponse<Setting>> {
+    let db = state.db.lock().await;
+
+    if !is_manager(db.pool(), &payload.user_id).await {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Solo un gerente puede cambiar la configuración".to_string()),
+        });
+    }
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO settings (key, value, updated_by, updated_at)
+        VALUES (?, ?, ?, datetime('now'))
+        ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_by = excluded.updated_by, updated_at = excluded.updated_at
+        "#
+    )
+    .bind(&key)
+    .bind(&payload.value)
+    .bind(&payload.user_id)
+    .execute(db.pool())
+    .await;
+
+    match result {
+        Ok(_) => Json(ApiResponse {
+            success: true,
+            data: Some(Setting {
+                key,
+                value: payload.value,
+                updated_at: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            }),
+            message: Some("Configuración guardada".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
@@ -0,0 +1,332 @@
+use axum::{Json, extract::{State, Path}};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use crate::api::AppState;
+use crate::models::ApiResponse;
+
+#[derive(Serialize)]
+pub struct Unit {
+    pub code: String,
+    pub name: String,
+    pub dimension: Option<String>,
+    pub to_base: f64,
+    pub allows_fraction: bool,
+}
+
+#[derive(Deserialize)]
+pub struct CreateUnitRequest {
+    pub code: String,
+    pub name: String,
+    /// Units with the same dimension ('mass', 'volume', 'length', 'count') convert automatically.
+    pub dimension: Option<String>,
+    pub to_base: Option<f64>,
+    pub allows_fraction: Option<bool>,
+}
+
+#[derive(Serialize)]
+pub struct ProductUnit {
+    pub unit_code: String,
+    pub unit_name: String,
+    pub factor: f64,
+    pub barcode: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ProductUnitRequest {
+    pub unit_code: String,
+    /// How many of the product's base unit make one `unit_code` (e.g. 24 pza per caja).
+    pub factor: f64,
+    pub barcode: Option<String>,
+}
+
+/// A quantity expressed in some unit, converted to the product's base unit.
+pub struct ResolvedQuantity {
+    pub unit: String,
+    pub factor: f64,
+    pub base_quantity: f64,
+}
+
+/// Converts `quantity` in `unit` to the product's base unit, using the product's own
+/// conversions first and then the generic ones between units of the same dimension.
+/// Whole-unit products reject quantities that don't come out as whole base units.
+pub async fn resolve_quantity(
+    conn: &mut SqliteConnection,
+    product_id: &str,
+    unit: Option<&str>,
+    quantity: f64,
+) -> Result<ResolvedQuantity, String> {
+    let product: Option<(String, String, i32)> = sqlx::query_as(
+        r#"
+        SELECT p.name, p.unit, COALESCE(u.allows_fraction, 1)
+        FROM products p
+        LEFT JOIN units u ON u.code = p.unit
+        WHERE p.id = ?
+        "#
+    )
+    .bind(product_id)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let (product_name, base_unit, allows_fraction) =
+        product.ok_or_else(|| format!("Producto {} no encontrado", product_id))?;
+
+    let unit = unit.unwrap_or(&base_unit).to_string();
+    let factor = if unit == base_unit {
+        1.0
+    } else {
+        let product_factor: Option<(f64,)> = sqlx::query_as(
+            "SELECT factor FROM product_units WHERE product_id = ? AND unit_code = ?"
+        )
+        .bind(product_id)
+        .bind(&unit)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        match product_factor {
+            Some((factor,)) => factor,
+            None => {
+                let generic: Option<(f64,)> = sqlx::query_as(
+                    r#"
+                    SELECT u.to_base / b.to_base
+                    FROM units u, units b
+                    WHERE u.code = ? AND b.code = ? AND u.dimension IS NOT NULL AND u.dimension = b.dimension
+                    "#
+                )
+                .bind(&unit)
+                .bind(&base_unit)
+                .fetch_optional(&mut *conn)
+                .await
+                .map_err(|e| e.to_string())?;
+
+                generic
+                    .map(|t| t.0)
+                    .ok_or_else(|| format!("La unidad '{}' no aplica para {} ({})", unit, product_name, base_unit))?
+            }
+        }
+    };
+
+    let base_quantity = quantity * factor;
+    if allows_fraction == 0 && (base_quantity - base_quantity.round()).abs() > 1e-9 {
+        return Err(format!("{} se maneja en unidades enteras de {}", product_name, base_unit));
+    }
+
+    Ok(ResolvedQuantity {
+        unit,
+        factor,
+        base_quantity,
+    })
+}
+
+pub async fn unit_exists(conn: &mut SqliteConnection, code: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_as::<_, (i32,)>("SELECT COUNT(*) FROM units WHERE code = ?")
+        .bind(code)
+        .fetch_one(conn)
+        .await
+        .map(|t| t.0 > 0)
+}
+
+type UnitRow = (String, String, String, f64, i32);
+
+pub async fn list_units(
+    State(state): State<AppState>,
+) -> Json<ApiResponse<Vec<Unit>>> {
+    let db = state.db.lock().await;
+
+    let result: Result<Vec<UnitRow>, sqlx::Error> = sqlx::query_as(
+        "SELECT code, name, COALESCE(dimension, ''), to_base, allows_fraction FROM units ORDER BY name"
+    )
+    .fetch_all(db.pool())
+    .await;
+
+    match result {
+        Ok(rows) => {
+            let units: Vec<Unit> = rows
+                .into_iter()
+                .map(|(code, name, dimension, to_base, allows_fraction)| Unit {
+                    code,
+                    name,
+                    dimension: if dimension.is_empty() { None } else { Some(dimension) },
+                    to_base,
+                    allows_fraction: allows_fraction == 1,
+                })
+                .collect();
+
+            Json(ApiResponse {
+                success: true,
+                data: Some(units),
+                message: None,
+            })
+        }
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn create_unit(
+    State(state): State<AppState>,
+    Json(payload): Json<CreateUnitRequest>,
+) -> Json<ApiResponse<Unit>> {
+    let db = state.db.lock().await;
+
+    let to_base = payload.to_base.unwrap_or(1.0);
+    if to_base <= 0.0 {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("El factor de conversión debe ser positivo".to_string()),
+        });
+    }
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO units (code, name, dimension, to_base, allows_fraction, created_at)
+        VALUES (?, ?, ?, ?, ?, datetime('now'))
+        "#
+    )
+    .bind(&payload.code)
+    .bind(&payload.name)
+    .bind(&payload.dimension)
+    .bind(to_base)
+    .bind(payload.allows_fraction.unwrap_or(false))
+    .execute(db.pool())
+    .await;
+
+    match result {
+        Ok(_) => Json(ApiResponse {
+            success: true,
+            data: Some(Unit {
+                code: payload.code,
+                name: payload.name,
+                dimension: payload.dimension,
+                to_base,
+                allows_fraction: payload.allows_fraction.unwrap_or(false),
+            }),
+            message: Some("Unidad creada exitosamente".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error al crear unidad: {}", e)),
+        }),
+    }
+}
+
+pub async fn list_product_units(
+    State(state): State<AppState>,
+    Path(product_id): Path<String>,
+) -> Json<ApiResponse<Vec<ProductUnit>>> {
+    let db = state.db.lock().await;
+
+    let result: Result<Vec<(String, String, f64, String)>, sqlx::Error> = sqlx::query_as(
+        r#"
+        SELECT pu.unit_code, u.name, pu.factor, COALESCE(pu.barcode, '')
+        FROM product_units pu
+        JOIN units u ON pu.unit_code = u.code
+        WHERE pu.product_id = ?
+        ORDER BY pu.factor
+        "#
+    )
+    .bind(&product_id)
+    .fetch_all(db.pool())
+    .await;
+
+    match result {
+        Ok(rows) => {
+            let units: Vec<ProductUnit> = rows
+                .into_iter()
+                .map(|(unit_code, unit_name, factor, barcode)| ProductUnit {
+                    unit_code,
+                    unit_name,
+                    factor,
+                    barcode: if barcode.is_empty() { None } else { Some(barcode) },
+                })
+                .collect();
+
+            Json(ApiResponse {
+                success: true,
+                data: Some(units),
+                message: None,
+            })
+        }
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn set_product_unit(
+    State(state): State<AppState>,
+    Path(product_id): Path<String>,
+    Json(payload): Json<ProductUnitRequest>,
+) -> Json<ApiResponse<String>> {
+    let db = state.db.lock().await;
+
+    if payload.factor <= 0.0 {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("El factor de conversión debe ser positivo".to_string()),
+        });
+    }
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO product_units (id, product_id, unit_code, factor, barcode, created_at)
+        VALUES (?, ?, ?, ?, ?, datetime('now'))
+        ON CONFLICT(product_id, unit_code) DO UPDATE SET factor = excluded.factor, barcode = excluded.barcode
+        "#
+    )
+    .bind(uuid::Uuid::new_v4().to_string())
+    .bind(&product_id)
+    .bind(&payload.unit_code)
+    .bind(payload.factor)
+    .bind(&payload.barcode)
+    .execute(db.pool())
+    .await;
+
+    match result {
+        Ok(_) => Json(ApiResponse {
+            success: true,
+            data: Some("Conversión guardada".to_string()),
+            message: Some(format!("1 {} = {} unidades base", payload.unit_code, payload.factor)),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
+
+pub async fn delete_product_unit(
+    State(state): State<AppState>,
+    Path((product_id, unit_code)): Path<(String, String)>,
+) -> Json<ApiResponse<String>> {
+    let db = state.db.lock().await;
+
+    let result = sqlx::query("DELETE FROM product_units WHERE product_id = ? AND unit_code = ?")
+        .bind(&product_id)
+        .bind(&unit_code)
+        .execute(db.pool())
+        .await;
+
+    match result {
+        Ok(_) => Json(ApiResponse {
+            success: true,
+            data: Some("Conversión eliminada".to_string()),
+            message: Some("Conversión eliminada exitosamente".to_string()),
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some(format!("Error: {}", e)),
+        }),
+    }
+}
@@ -95,6 +95,14 @@ impl Database {
         &self.db_path
     }
 
+    /// Product pictures are kept next to the database file.
+    pub fn images_dir(&self) -> PathBuf {
+        self.db_path
+            .parent()
+            .map(|dir| dir.join("images"))
+            .unwrap_or_else(|| PathBuf::from("images"))
+    }
+
     pub async fn run_migrations(&self) -> Result<()> {
         println!("🔄 Running database migrations...");
         
@@ -111,20 +119,185 @@ impl Database {
         self.create_payments_table().await?;
         self.create_inventory_movements_table().await?;
         self.create_audit_logs_table().await?;
+        self.create_suppliers_table().await?;
+        self.create_purchase_orders_table().await?;
+        self.create_purchase_order_items_table().await?;
+        self.create_goods_receipts_table().await?;
+        self.create_goods_receipt_items_table().await?;
+        self.create_inventory_counts_table().await?;
+        self.create_inventory_count_items_table().await?;
+        self.create_inventory_count_entries_table().await?;
+        self.create_locations_table().await?;
+        self.create_product_stock_table().await?;
+        self.create_stock_transfers_table().await?;
+        self.create_stock_transfer_items_table().await?;
+        self.create_units_table().await?;
+        self.create_product_units_table().await?;
+        self.create_product_components_table().await?;
+        self.create_kit_assemblies_table().await?;
+        self.create_settings_table().await?;
+        self.create_scale_barcode_layouts_table().await?;
+        self.create_product_price_history_table().await?;
+        self.create_scheduled_price_changes_table().await?;
+        self.create_stock_reservations_table().await?;
+        self.create_customer_segments_table().await?;
+        self.create_loyalty_tables().await?;
+        self.create_cash_movements_table().await?;
+        self.create_shift_tenders_table().await?;
+        self.create_denomination_tables().await?;
+
+        // Add columns introduced after the original schema
+        self.add_column_if_missing("cash_registers", "location_id", "TEXT REFERENCES locations(id)").await?;
+        self.add_column_if_missing("inventory_movements", "location_id", "TEXT REFERENCES locations(id)").await?;
+        self.add_column_if_missing("inventory_counts", "location_id", "TEXT REFERENCES locations(id)").await?;
+        self.add_column_if_missing("sale_items", "unit", "TEXT").await?;
+        self.add_column_if_missing("sale_items", "unit_factor", "REAL DEFAULT 1").await?;
+        self.add_column_if_missing("purchase_order_items", "unit", "TEXT").await?;
+        self.add_column_if_missing("purchase_order_items", "unit_factor", "REAL DEFAULT 1").await?;
+        self.add_column_if_missing("suppliers", "lead_time_days", "INTEGER NOT NULL DEFAULT 7").await?;
+        self.add_column_if_missing("products", "supplier_id", "TEXT REFERENCES suppliers(id)").await?;
+        self.add_column_if_missing("products", "content_quantity", "REAL").await?;
+        self.add_column_if_missing("products", "content_unit", "TEXT REFERENCES units(code)").await?;
+        self.add_column_if_missing("products", "price_updated_at", "TEXT").await?;
+        self.add_column_if_missing("products", "plu", "TEXT").await?;
+        self.add_column_if_missing("products", "allow_negative_stock", "INTEGER NOT NULL DEFAULT 0").await?;
+        self.add_column_if_missing("inventory_movements", "unit_cost", "REAL").await?;
+        self.add_column_if_missing("inventory_movements", "reason_code", "TEXT").await?;
+        if self.add_column_if_missing("sale_items", "category_id", "TEXT").await? {
+            // Sales made before categories were recorded per line keep the product's current one
+            sqlx::query(
+                "UPDATE sale_items SET category_id = (SELECT category_id FROM products WHERE products.id = sale_items.product_id)"
+            )
+            .execute(&self.pool)
+            .await?;
+        }
+
+        self.add_column_if_missing("customers", "loyalty_tier", "TEXT").await?;
+        self.add_column_if_missing("customers", "merged_into", "TEXT REFERENCES customers(id)").await?;
+        self.add_column_if_missing("customers", "business_name", "TEXT").await?;
+        self.add_column_if_missing("customers", "tax_regime", "TEXT").await?;
+        self.add_column_if_missing("customers", "cfdi_use", "TEXT").await?;
+        self.add_column_if_missing("customers", "birth_date", "TEXT").await?;
+        self.add_column_if_missing("customers", "anonymized_at", "TEXT").await?;
+        self.add_column_if_missing("sales", "loyalty_tier", "TEXT").await?;
+        self.add_column_if_missing("sales", "loyalty_discount", "REAL NOT NULL DEFAULT 0").await?;
+        self.add_column_if_missing("sales", "points_earned", "INTEGER NOT NULL DEFAULT 0").await?;
+        self.add_column_if_missing("payments", "tendered", "REAL").await?;
+        self.add_column_if_missing("payments", "change_given", "REAL NOT NULL DEFAULT 0").await?;
+        self.add_column_if_missing("cash_registers", "blind_close", "INTEGER NOT NULL DEFAULT 0").await?;
+        self.add_column_if_missing("shifts", "blind_close", "INTEGER NOT NULL DEFAULT 0").await?;
+        self.add_column_if_missing("shifts", "reviewed_by", "TEXT REFERENCES users(id)").await?;
+        self.add_column_if_missing("shifts", "reviewed_at", "TEXT").await?;
+        self.add_column_if_missing("shifts", "review_notes", "TEXT").await?;
+
+        // Quantities used to be whole units; fractional stock needs REAL columns
+        self.convert_columns_to_real("products", &["stock", "min_stock", "max_stock"]).await?;
+        self.convert_columns_to_real("product_stock", &["quantity"]).await?;
+        self.convert_columns_to_real("purchase_order_items", &["quantity_ordered", "quantity_received", "quantity_damaged"]).await?;
+        self.convert_columns_to_real("goods_receipt_items", &["quantity_received", "quantity_damaged"]).await?;
+        self.convert_columns_to_real("inventory_count_items", &["expected_quantity"]).await?;
+        self.convert_columns_to_real("inventory_count_entries", &["quantity"]).await?;
+        self.convert_columns_to_real("stock_transfer_items", &["quantity_requested", "quantity_shipped", "quantity_received"]).await?;
         
         // Create indexes for better performance
         self.create_indexes().await?;
+        self.create_customer_search_index().await?;
         
         // Insert default data
         self.insert_default_roles().await?;
         self.insert_default_admin().await?;
         self.insert_default_cash_register().await?;
         self.insert_sample_data().await?;
+        self.insert_default_location().await?;
+        self.insert_default_units().await?;
+        self.insert_default_settings().await?;
+        self.insert_default_loyalty_tiers().await?;
+        self.insert_default_denominations().await?;
+        self.insert_default_scale_layouts().await?;
         
         println!("✅ Database migrations completed successfully");
         Ok(())
     }
 
+    /// Returns whether the column had to be added.
+    async fn add_column_if_missing(&self, table: &str, column: &str, definition: &str) -> Result<bool> {
+        let exists: Option<(String,)> = sqlx::query_as(
+            "SELECT name FROM pragma_table_info(?) WHERE name = ?"
+        )
+        .bind(table)
+        .bind(column)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if exists.is_none() {
+            sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(exists.is_none())
+    }
+
+    /// Rebuilds `table` with `columns` declared as REAL, following SQLite's
+    /// create-copy-drop-rename procedure since column types can't be altered in place.
+    async fn convert_columns_to_real(&self, table: &str, columns: &[&str]) -> Result<()> {
+        let declared: Vec<(String, String)> = sqlx::query_as(
+            "SELECT name, type FROM pragma_table_info(?)"
+        )
+        .bind(table)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let pending: Vec<&str> = columns
+            .iter()
+            .copied()
+            .filter(|column| {
+                declared
+                    .iter()
+                    .any(|(name, col_type)| name == column && col_type.eq_ignore_ascii_case("INTEGER"))
+            })
+            .collect();
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let (create_sql,): (String,) = sqlx::query_as(
+            "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?"
+        )
+        .bind(table)
+        .fetch_one(&self.pool)
+        .await?;
+
+        let temp_table = format!("{}_migrating", table);
+        let mut new_sql = create_sql.replacen(table, &temp_table, 1);
+        for column in &pending {
+            new_sql = new_sql.replacen(&format!(" {} INTEGER", column), &format!(" {} REAL", column), 1);
+        }
+
+        // Foreign keys must be off on this connection while the table is swapped
+        let mut conn = self.pool.acquire().await?;
+        sqlx::query("PRAGMA foreign_keys = OFF").execute(&mut *conn).await?;
+
+        let result = async {
+            let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+            sqlx::query(&new_sql).execute(&mut *tx).await?;
+            sqlx::query(&format!("INSERT INTO {} SELECT * FROM {}", temp_table, table))
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(&format!("DROP TABLE {}", table)).execute(&mut *tx).await?;
+            sqlx::query(&format!("ALTER TABLE {} RENAME TO {}", temp_table, table))
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await
+        }
+        .await;
+
+        sqlx::query("PRAGMA foreign_keys = ON").execute(&mut *conn).await?;
+        result?;
+
+        println!("🔧 Converted {}({}) to REAL", table, pending.join(", "));
+        Ok(())
+    }
+
     async fn create_indexes(&self) -> Result<()> {
         let indexes = vec![
             "CREATE INDEX IF NOT EXISTS idx_users_username ON users(username)",
@@ -135,11 +308,33 @@ impl Database {
             "CREATE INDEX IF NOT EXISTS idx_sales_user ON sales(user_id)",
             "CREATE INDEX IF NOT EXISTS idx_sales_customer ON sales(customer_id)",
             "CREATE INDEX IF NOT EXISTS idx_sales_created ON sales(created_at)",
+            "CREATE INDEX IF NOT EXISTS idx_cash_movements_shift ON cash_movements(shift_id)",
+            "CREATE INDEX IF NOT EXISTS idx_loyalty_ledger_customer ON loyalty_points_ledger(customer_id, created_at)",
+            "CREATE INDEX IF NOT EXISTS idx_loyalty_tier_changes_customer ON loyalty_tier_changes(customer_id, created_at)",
             "CREATE INDEX IF NOT EXISTS idx_sale_items_sale ON sale_items(sale_id)",
             "CREATE INDEX IF NOT EXISTS idx_sale_items_product ON sale_items(product_id)",
             "CREATE INDEX IF NOT EXISTS idx_payments_sale ON payments(sale_id)",
             "CREATE INDEX IF NOT EXISTS idx_shifts_user ON shifts(user_id)",
             "CREATE INDEX IF NOT EXISTS idx_shifts_register ON shifts(register_id)",
+            "CREATE INDEX IF NOT EXISTS idx_movements_product ON inventory_movements(product_id)",
+            "CREATE INDEX IF NOT EXISTS idx_movements_type ON inventory_movements(type, created_at)",
+            "CREATE INDEX IF NOT EXISTS idx_purchase_orders_supplier ON purchase_orders(supplier_id)",
+            "CREATE INDEX IF NOT EXISTS idx_purchase_order_items_order ON purchase_order_items(purchase_order_id)",
+            "CREATE INDEX IF NOT EXISTS idx_goods_receipts_order ON goods_receipts(purchase_order_id)",
+            "CREATE INDEX IF NOT EXISTS idx_goods_receipt_items_receipt ON goods_receipt_items(receipt_id)",
+            "CREATE INDEX IF NOT EXISTS idx_count_items_count ON inventory_count_items(count_id)",
+            "CREATE INDEX IF NOT EXISTS idx_count_entries_count ON inventory_count_entries(count_id, product_id)",
+            "CREATE INDEX IF NOT EXISTS idx_product_stock_location ON product_stock(location_id)",
+            "CREATE INDEX IF NOT EXISTS idx_transfer_items_transfer ON stock_transfer_items(transfer_id)",
+            "CREATE INDEX IF NOT EXISTS idx_product_components_component ON product_components(component_id)",
+            "CREATE INDEX IF NOT EXISTS idx_kit_assemblies_kit ON kit_assemblies(kit_id)",
+            "CREATE INDEX IF NOT EXISTS idx_products_plu ON products(plu)",
+            "CREATE INDEX IF NOT EXISTS idx_price_history_product ON product_price_history(product_id, created_at)",
+            "CREATE INDEX IF NOT EXISTS idx_scheduled_prices_due ON scheduled_price_changes(status, effective_at)",
+            "CREATE INDEX IF NOT EXISTS idx_reservations_product ON stock_reservations(product_id, status)",
+            "CREATE INDEX IF NOT EXISTS idx_reservations_expiry ON stock_reservations(status, expires_at)",
+            "CREATE INDEX IF NOT EXISTS idx_customers_name ON customers(name COLLATE NOCASE)",
+            "CREATE INDEX IF NOT EXISTS idx_customers_created ON customers(created_at)",
         ];
 
         for index in indexes {
@@ -148,6 +343,91 @@ impl Database {
         Ok(())
     }
 
+    /// Full-text index over customer name, phone, email and RFC, kept in sync by triggers.
+    /// Each entry shares its rowid with the customer row, so triggers update it by rowid.
+    /// Phones are indexed as bare digits so they match however they were typed.
+    async fn create_customer_search_index(&self) -> Result<()> {
+        // Indexes built before entries were keyed by rowid are dropped and rebuilt
+        let legacy: Option<(String,)> = sqlx::query_as(
+            "SELECT name FROM sqlite_master WHERE type = 'trigger' AND name = 'customers_fts_delete' AND sql NOT LIKE '%old.rowid%'"
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        if legacy.is_some() {
+            for statement in [
+                "DROP TRIGGER IF EXISTS customers_fts_insert",
+                "DROP TRIGGER IF EXISTS customers_fts_update",
+                "DROP TRIGGER IF EXISTS customers_fts_delete",
+                "DROP TABLE IF EXISTS customers_fts",
+            ] {
+                sqlx::query(statement).execute(&self.pool).await?;
+            }
+        }
+
+        let exists: Option<(String,)> = sqlx::query_as(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'customers_fts'"
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE VIRTUAL TABLE IF NOT EXISTS customers_fts USING fts5(
+                customer_id UNINDEXED, name, phone, email, rfc,
+                tokenize = 'unicode61 remove_diacritics 2', prefix = '2 3'
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        let digits = |column: &str| {
+            ["' '", "'-'", "'('", "')'", "'+'", "'.'"]
+                .iter()
+                .fold(column.to_string(), |expr, c| format!("REPLACE({}, {}, '')", expr, c))
+        };
+        let triggers = [
+            format!(
+                r#"
+                CREATE TRIGGER IF NOT EXISTS customers_fts_insert AFTER INSERT ON customers BEGIN
+                    INSERT INTO customers_fts (rowid, customer_id, name, phone, email, rfc)
+                    VALUES (new.rowid, new.id, new.name, {}, new.email, new.rfc);
+                END
+                "#,
+                digits("new.phone")
+            ),
+            format!(
+                r#"
+                CREATE TRIGGER IF NOT EXISTS customers_fts_update AFTER UPDATE OF name, phone, email, rfc ON customers BEGIN
+                    DELETE FROM customers_fts WHERE rowid = old.rowid;
+                    INSERT INTO customers_fts (rowid, customer_id, name, phone, email, rfc)
+                    VALUES (new.rowid, new.id, new.name, {}, new.email, new.rfc);
+                END
+                "#,
+                digits("new.phone")
+            ),
+            r#"
+            CREATE TRIGGER IF NOT EXISTS customers_fts_delete AFTER DELETE ON customers BEGIN
+                DELETE FROM customers_fts WHERE rowid = old.rowid;
+            END
+            "#
+            .to_string(),
+        ];
+        for trigger in &triggers {
+            sqlx::query(trigger).execute(&self.pool).await?;
+        }
+
+        if exists.is_none() {
+            sqlx::query(&format!(
+                "INSERT INTO customers_fts (rowid, customer_id, name, phone, email, rfc) SELECT rowid, id, name, {}, email, rfc FROM customers",
+                digits("phone")
+            ))
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
     async fn create_users_table(&self) -> Result<()> {
         sqlx::query(
             r#"
@@ -221,9 +501,9 @@ impl Database {
                 category_id TEXT,
                 price REAL NOT NULL,
                 cost REAL NOT NULL,
-                stock INTEGER NOT NULL DEFAULT 0,
-                min_stock INTEGER DEFAULT 0,
-                max_stock INTEGER,
+                stock REAL NOT NULL DEFAULT 0,
+                min_stock REAL DEFAULT 0,
+                max_stock REAL,
                 unit TEXT NOT NULL,
                 image_url TEXT,
                 is_active INTEGER DEFAULT 1,
@@ -429,70 +709,879 @@ impl Database {
         Ok(())
     }
 
-    async fn insert_default_roles(&self) -> Result<()> {
-        let roles = vec![
-            ("admin", r#"["all"]"#, "Administrador con acceso completo"),
-            ("manager", r#"["sales","inventory","reports","customers"]"#, "Gerente con permisos avanzados"),
-            ("cashier", r#"["sales"]"#, "Cajero con permisos básicos"),
-        ];
+    async fn create_suppliers_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS suppliers (
+                id TEXT PRIMARY KEY NOT NULL,
+                name TEXT NOT NULL,
+                contact_name TEXT,
+                email TEXT,
+                phone TEXT,
+                rfc TEXT,
+                is_active INTEGER DEFAULT 1,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
 
-        for (name, permissions, _desc) in roles {
-            sqlx::query(
-                r#"
-                INSERT OR IGNORE INTO roles (id, name, permissions, created_at)
-                VALUES (?, ?, ?, datetime('now'))
-                "#
+    async fn create_purchase_orders_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS purchase_orders (
+                id TEXT PRIMARY KEY NOT NULL,
+                order_number TEXT UNIQUE NOT NULL,
+                supplier_id TEXT NOT NULL,
+                user_id TEXT NOT NULL,
+                status TEXT NOT NULL,
+                expected_at TEXT,
+                notes TEXT,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                FOREIGN KEY (supplier_id) REFERENCES suppliers(id),
+                FOREIGN KEY (user_id) REFERENCES users(id)
             )
-            .bind(uuid::Uuid::new_v4().to_string())
-            .bind(name)
-            .bind(permissions)
-            .execute(&self.pool)
-            .await?;
-        }
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
         Ok(())
     }
 
-    async fn insert_default_admin(&self) -> Result<()> {
-        let admin_role: Option<(String,)> = sqlx::query_as(
-            "SELECT id FROM roles WHERE name = 'admin'"
+    async fn create_purchase_order_items_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS purchase_order_items (
+                id TEXT PRIMARY KEY NOT NULL,
+                purchase_order_id TEXT NOT NULL,
+                product_id TEXT NOT NULL,
+                quantity_ordered REAL NOT NULL,
+                quantity_received REAL NOT NULL DEFAULT 0,
+                quantity_damaged REAL NOT NULL DEFAULT 0,
+                unit_cost REAL NOT NULL,
+                FOREIGN KEY (purchase_order_id) REFERENCES purchase_orders(id),
+                FOREIGN KEY (product_id) REFERENCES products(id)
+            )
+            "#
         )
-        .fetch_optional(&self.pool)
+        .execute(&self.pool)
         .await?;
+        Ok(())
+    }
 
-        if let Some((role_id,)) = admin_role {
-            let password_hash = bcrypt::hash("admin123", bcrypt::DEFAULT_COST)?;
-            
-            sqlx::query(
-                r#"
-                INSERT OR IGNORE INTO users (id, username, email, password_hash, full_name, role_id, created_at, updated_at)
-                VALUES (?, ?, ?, ?, ?, ?, datetime('now'), datetime('now'))
-                "#
+    async fn create_goods_receipts_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS goods_receipts (
+                id TEXT PRIMARY KEY NOT NULL,
+                receipt_number TEXT UNIQUE NOT NULL,
+                purchase_order_id TEXT NOT NULL,
+                user_id TEXT NOT NULL,
+                freight_cost REAL DEFAULT 0,
+                notes TEXT,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (purchase_order_id) REFERENCES purchase_orders(id),
+                FOREIGN KEY (user_id) REFERENCES users(id)
             )
-            .bind(uuid::Uuid::new_v4().to_string())
-            .bind("admin")
-            .bind("admin@pos.local")
-            .bind(password_hash)
-            .bind("Administrador")
-            .bind(role_id)
-            .execute(&self.pool)
-            .await?;
-        }
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
         Ok(())
     }
 
-    async fn insert_default_cash_register(&self) -> Result<()> {
+    async fn create_goods_receipt_items_table(&self) -> Result<()> {
         sqlx::query(
             r#"
-            INSERT OR IGNORE INTO cash_registers (id, name, location, is_active, created_at)
-            VALUES (?, 'Caja Principal', 'Principal', 1, datetime('now'))
+            CREATE TABLE IF NOT EXISTS goods_receipt_items (
+                id TEXT PRIMARY KEY NOT NULL,
+                receipt_id TEXT NOT NULL,
+                purchase_order_item_id TEXT NOT NULL,
+                product_id TEXT NOT NULL,
+                quantity_received REAL NOT NULL,
+                quantity_damaged REAL NOT NULL DEFAULT 0,
+                unit_cost REAL NOT NULL,
+                landed_unit_cost REAL NOT NULL,
+                FOREIGN KEY (receipt_id) REFERENCES goods_receipts(id),
+                FOREIGN KEY (purchase_order_item_id) REFERENCES purchase_order_items(id),
+                FOREIGN KEY (product_id) REFERENCES products(id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn create_inventory_counts_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS inventory_counts (
+                id TEXT PRIMARY KEY NOT NULL,
+                count_number TEXT UNIQUE NOT NULL,
+                name TEXT NOT NULL,
+                status TEXT NOT NULL,
+                abc_class TEXT,
+                scheduled_for TEXT,
+                started_at TEXT,
+                created_by TEXT NOT NULL,
+                approved_by TEXT,
+                approved_at TEXT,
+                notes TEXT,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (created_by) REFERENCES users(id),
+                FOREIGN KEY (approved_by) REFERENCES users(id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn create_inventory_count_items_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS inventory_count_items (
+                id TEXT PRIMARY KEY NOT NULL,
+                count_id TEXT NOT NULL,
+                product_id TEXT NOT NULL,
+                expected_quantity REAL,
+                unit_cost REAL,
+                UNIQUE (count_id, product_id),
+                FOREIGN KEY (count_id) REFERENCES inventory_counts(id),
+                FOREIGN KEY (product_id) REFERENCES products(id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn create_inventory_count_entries_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS inventory_count_entries (
+                id TEXT PRIMARY KEY NOT NULL,
+                count_id TEXT NOT NULL,
+                product_id TEXT NOT NULL,
+                quantity REAL NOT NULL,
+                device_id TEXT,
+                user_id TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (count_id) REFERENCES inventory_counts(id),
+                FOREIGN KEY (product_id) REFERENCES products(id),
+                FOREIGN KEY (user_id) REFERENCES users(id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn create_locations_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS locations (
+                id TEXT PRIMARY KEY NOT NULL,
+                name TEXT NOT NULL,
+                location_type TEXT NOT NULL,
+                address TEXT,
+                is_default INTEGER DEFAULT 0,
+                is_active INTEGER DEFAULT 1,
+                created_at TEXT NOT NULL
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn create_product_stock_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS product_stock (
+                product_id TEXT NOT NULL,
+                location_id TEXT NOT NULL,
+                quantity REAL NOT NULL DEFAULT 0,
+                PRIMARY KEY (product_id, location_id),
+                FOREIGN KEY (product_id) REFERENCES products(id),
+                FOREIGN KEY (location_id) REFERENCES locations(id)
+            )
             "#
         )
-        .bind(uuid::Uuid::new_v4().to_string())
         .execute(&self.pool)
         .await?;
         Ok(())
     }
 
+    async fn create_stock_transfers_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS stock_transfers (
+                id TEXT PRIMARY KEY NOT NULL,
+                transfer_number TEXT UNIQUE NOT NULL,
+                from_location_id TEXT NOT NULL,
+                to_location_id TEXT NOT NULL,
+                status TEXT NOT NULL,
+                requested_by TEXT NOT NULL,
+                shipped_by TEXT,
+                received_by TEXT,
+                shipped_at TEXT,
+                received_at TEXT,
+                notes TEXT,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (from_location_id) REFERENCES locations(id),
+                FOREIGN KEY (to_location_id) REFERENCES locations(id),
+                FOREIGN KEY (requested_by) REFERENCES users(id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn create_stock_transfer_items_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS stock_transfer_items (
+                id TEXT PRIMARY KEY NOT NULL,
+                transfer_id TEXT NOT NULL,
+                product_id TEXT NOT NULL,
+                quantity_requested REAL NOT NULL,
+                quantity_shipped REAL NOT NULL DEFAULT 0,
+                quantity_received REAL NOT NULL DEFAULT 0,
+                FOREIGN KEY (transfer_id) REFERENCES stock_transfers(id),
+                FOREIGN KEY (product_id) REFERENCES products(id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn create_units_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS units (
+                code TEXT PRIMARY KEY NOT NULL,
+                name TEXT NOT NULL,
+                dimension TEXT,
+                to_base REAL NOT NULL DEFAULT 1,
+                allows_fraction INTEGER DEFAULT 0,
+                created_at TEXT NOT NULL
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn create_product_units_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS product_units (
+                id TEXT PRIMARY KEY NOT NULL,
+                product_id TEXT NOT NULL,
+                unit_code TEXT NOT NULL,
+                factor REAL NOT NULL,
+                barcode TEXT,
+                created_at TEXT NOT NULL,
+                UNIQUE (product_id, unit_code),
+                FOREIGN KEY (product_id) REFERENCES products(id),
+                FOREIGN KEY (unit_code) REFERENCES units(code)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn create_product_components_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS product_components (
+                id TEXT PRIMARY KEY NOT NULL,
+                kit_id TEXT NOT NULL,
+                component_id TEXT NOT NULL,
+                quantity REAL NOT NULL,
+                created_at TEXT NOT NULL,
+                UNIQUE (kit_id, component_id),
+                FOREIGN KEY (kit_id) REFERENCES products(id),
+                FOREIGN KEY (component_id) REFERENCES products(id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn create_kit_assemblies_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS kit_assemblies (
+                id TEXT PRIMARY KEY NOT NULL,
+                assembly_number TEXT UNIQUE NOT NULL,
+                kit_id TEXT NOT NULL,
+                location_id TEXT NOT NULL,
+                operation TEXT NOT NULL,
+                quantity REAL NOT NULL,
+                user_id TEXT NOT NULL,
+                notes TEXT,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (kit_id) REFERENCES products(id),
+                FOREIGN KEY (location_id) REFERENCES locations(id),
+                FOREIGN KEY (user_id) REFERENCES users(id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn create_settings_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS settings (
+                key TEXT PRIMARY KEY NOT NULL,
+                value TEXT NOT NULL,
+                updated_by TEXT,
+                updated_at TEXT NOT NULL
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn create_scale_barcode_layouts_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS scale_barcode_layouts (
+                prefix TEXT PRIMARY KEY NOT NULL,
+                plu_start INTEGER NOT NULL,
+                plu_length INTEGER NOT NULL,
+                value_type TEXT NOT NULL CHECK (value_type IN ('weight', 'price')),
+                value_start INTEGER NOT NULL,
+                value_length INTEGER NOT NULL,
+                decimals INTEGER NOT NULL,
+                is_active INTEGER NOT NULL DEFAULT 1,
+                updated_at TEXT NOT NULL
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn create_product_price_history_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS product_price_history (
+                id TEXT PRIMARY KEY NOT NULL,
+                product_id TEXT NOT NULL,
+                field TEXT NOT NULL CHECK (field IN ('price', 'cost')),
+                old_value REAL NOT NULL,
+                new_value REAL NOT NULL,
+                source TEXT NOT NULL,
+                reference_id TEXT,
+                user_id TEXT,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (product_id) REFERENCES products(id),
+                FOREIGN KEY (user_id) REFERENCES users(id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn create_scheduled_price_changes_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS scheduled_price_changes (
+                id TEXT PRIMARY KEY NOT NULL,
+                product_id TEXT NOT NULL,
+                price REAL,
+                cost REAL,
+                effective_at TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'applied', 'cancelled')),
+                user_id TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                applied_at TEXT,
+                FOREIGN KEY (product_id) REFERENCES products(id),
+                FOREIGN KEY (user_id) REFERENCES users(id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn create_stock_reservations_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS stock_reservations (
+                id TEXT PRIMARY KEY NOT NULL,
+                reservation_number TEXT UNIQUE NOT NULL,
+                product_id TEXT NOT NULL,
+                location_id TEXT NOT NULL,
+                quantity REAL NOT NULL CHECK (quantity > 0),
+                source TEXT NOT NULL CHECK (source IN ('held_sale', 'layaway', 'web_order', 'manual')),
+                reference_id TEXT,
+                customer_id TEXT,
+                status TEXT NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'released', 'fulfilled', 'expired')),
+                expires_at TEXT,
+                sale_id TEXT,
+                notes TEXT,
+                user_id TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                closed_at TEXT,
+                FOREIGN KEY (product_id) REFERENCES products(id),
+                FOREIGN KEY (location_id) REFERENCES locations(id),
+                FOREIGN KEY (customer_id) REFERENCES customers(id),
+                FOREIGN KEY (sale_id) REFERENCES sales(id),
+                FOREIGN KEY (user_id) REFERENCES users(id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn create_customer_segments_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS customer_segments (
+                id TEXT PRIMARY KEY NOT NULL,
+                name TEXT NOT NULL,
+                description TEXT,
+                rules TEXT NOT NULL,
+                is_active INTEGER NOT NULL DEFAULT 1,
+                created_by TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                FOREIGN KEY (created_by) REFERENCES users(id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn create_loyalty_tables(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS loyalty_tiers (
+                id TEXT PRIMARY KEY NOT NULL,
+                name TEXT UNIQUE NOT NULL,
+                rank INTEGER NOT NULL,
+                min_annual_spend REAL,
+                min_annual_points INTEGER,
+                points_multiplier REAL NOT NULL DEFAULT 1,
+                discount_percent REAL NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS loyalty_tier_prices (
+                tier_id TEXT NOT NULL,
+                product_id TEXT NOT NULL,
+                price REAL NOT NULL CHECK (price >= 0),
+                updated_at TEXT NOT NULL,
+                PRIMARY KEY (tier_id, product_id),
+                FOREIGN KEY (tier_id) REFERENCES loyalty_tiers(id),
+                FOREIGN KEY (product_id) REFERENCES products(id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS loyalty_points_ledger (
+                id TEXT PRIMARY KEY NOT NULL,
+                customer_id TEXT NOT NULL,
+                points INTEGER NOT NULL,
+                reason TEXT NOT NULL CHECK (reason IN ('sale', 'manual')),
+                sale_id TEXT,
+                user_id TEXT,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (customer_id) REFERENCES customers(id),
+                FOREIGN KEY (sale_id) REFERENCES sales(id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS loyalty_tier_changes (
+                id TEXT PRIMARY KEY NOT NULL,
+                customer_id TEXT NOT NULL,
+                from_tier TEXT,
+                to_tier TEXT,
+                annual_spend REAL NOT NULL,
+                annual_points INTEGER NOT NULL,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (customer_id) REFERENCES customers(id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn create_cash_movements_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS cash_movements (
+                id TEXT PRIMARY KEY NOT NULL,
+                shift_id TEXT NOT NULL,
+                movement_type TEXT NOT NULL CHECK (movement_type IN ('pay_in', 'pay_out', 'safe_drop')),
+                amount REAL NOT NULL CHECK (amount > 0),
+                reason TEXT NOT NULL,
+                user_id TEXT NOT NULL,
+                authorized_by TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (shift_id) REFERENCES shifts(id),
+                FOREIGN KEY (user_id) REFERENCES users(id),
+                FOREIGN KEY (authorized_by) REFERENCES users(id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn create_shift_tenders_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS shift_tenders (
+                shift_id TEXT NOT NULL,
+                method TEXT NOT NULL,
+                expected_amount REAL NOT NULL,
+                declared_amount REAL,
+                difference REAL,
+                PRIMARY KEY (shift_id, method),
+                FOREIGN KEY (shift_id) REFERENCES shifts(id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn create_denomination_tables(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS currency_denominations (
+                currency TEXT NOT NULL,
+                kind TEXT NOT NULL CHECK (kind IN ('bill', 'coin')),
+                value REAL NOT NULL CHECK (value > 0),
+                PRIMARY KEY (currency, kind, value)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS shift_cash_counts (
+                shift_id TEXT NOT NULL,
+                stage TEXT NOT NULL CHECK (stage IN ('open', 'close')),
+                currency TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                value REAL NOT NULL,
+                count INTEGER NOT NULL CHECK (count >= 0),
+                PRIMARY KEY (shift_id, stage, kind, value),
+                FOREIGN KEY (shift_id) REFERENCES shifts(id)
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_default_roles(&self) -> Result<()> {
+        let roles = vec![
+            ("admin", r#"["all"]"#, "Administrador con acceso completo"),
+            ("manager", r#"["sales","inventory","reports","customers"]"#, "Gerente con permisos avanzados"),
+            ("cashier", r#"["sales"]"#, "Cajero con permisos básicos"),
+        ];
+
+        for (name, permissions, _desc) in roles {
+            sqlx::query(
+                r#"
+                INSERT OR IGNORE INTO roles (id, name, permissions, created_at)
+                VALUES (?, ?, ?, datetime('now'))
+                "#
+            )
+            .bind(uuid::Uuid::new_v4().to_string())
+            .bind(name)
+            .bind(permissions)
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn insert_default_admin(&self) -> Result<()> {
+        let admin_role: Option<(String,)> = sqlx::query_as(
+            "SELECT id FROM roles WHERE name = 'admin'"
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if let Some((role_id,)) = admin_role {
+            let password_hash = bcrypt::hash("admin123", bcrypt::DEFAULT_COST)?;
+            
+            sqlx::query(
+                r#"
+                INSERT OR IGNORE INTO users (id, username, email, password_hash, full_name, role_id, created_at, updated_at)
+                VALUES (?, ?, ?, ?, ?, ?, datetime('now'), datetime('now'))
+                "#
+            )
+            .bind(uuid::Uuid::new_v4().to_string())
+            .bind("admin")
+            .bind("admin@pos.local")
+            .bind(password_hash)
+            .bind("Administrador")
+            .bind(role_id)
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn insert_default_cash_register(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO cash_registers (id, name, location, is_active, created_at)
+            VALUES (?, 'Caja Principal', 'Principal', 1, datetime('now'))
+            "#
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_default_location(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO locations (id, name, location_type, is_default, is_active, created_at)
+            SELECT ?, 'Piso de venta', 'store', 1, 1, datetime('now')
+            WHERE NOT EXISTS (SELECT 1 FROM locations WHERE is_default = 1)
+            "#
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .execute(&self.pool)
+        .await?;
+
+        // Registers, counts and stock that predate locations belong to the default one
+        for table in ["cash_registers", "inventory_counts"] {
+            sqlx::query(&format!(
+                "UPDATE {} SET location_id = (SELECT id FROM locations WHERE is_default = 1) WHERE location_id IS NULL",
+                table
+            ))
+            .execute(&self.pool)
+            .await?;
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO product_stock (product_id, location_id, quantity)
+            SELECT p.id, (SELECT id FROM locations WHERE is_default = 1), p.stock
+            FROM products p
+            WHERE NOT EXISTS (SELECT 1 FROM product_stock ps WHERE ps.product_id = p.id)
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_default_units(&self) -> Result<()> {
+        // Units sharing a dimension convert through `to_base`; packaging units
+        // such as "caja" need a per-product factor in product_units
+        let units = vec![
+            ("pza", "Pieza", Some("count"), 1.0, false),
+            ("kg", "Kilogramo", Some("mass"), 1.0, true),
+            ("g", "Gramo", Some("mass"), 0.001, true),
+            ("L", "Litro", Some("volume"), 1.0, true),
+            ("mL", "Mililitro", Some("volume"), 0.001, true),
+            ("m", "Metro", Some("length"), 1.0, true),
+            ("caja", "Caja", None, 1.0, false),
+            ("paq", "Paquete", None, 1.0, false),
+        ];
+
+        for (code, name, dimension, to_base, allows_fraction) in units {
+            sqlx::query(
+                r#"
+                INSERT OR IGNORE INTO units (code, name, dimension, to_base, allows_fraction, created_at)
+                VALUES (?, ?, ?, ?, ?, datetime('now'))
+                "#
+            )
+            .bind(code)
+            .bind(name)
+            .bind(dimension)
+            .bind(to_base)
+            .bind(allows_fraction)
+            .execute(&self.pool)
+            .await?;
+        }
+
+        // Products created before the unit catalog used these codes
+        for (legacy, code) in [("pz", "pza"), ("lt", "L"), ("mt", "m")] {
+            sqlx::query("UPDATE products SET unit = ? WHERE unit = ?")
+                .bind(code)
+                .bind(legacy)
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn insert_default_settings(&self) -> Result<()> {
+        let settings = vec![
+            // GS1 reserves UPC number system 4 for in-store codes
+            ("barcode_prefix", "0400"),
+            // Outgoing adjustments worth more than this at cost need a manager
+            ("adjustment_approval_threshold", "1000"),
+            ("currency", "MXN"),
+            // "0" lets sales take stock below zero or out of other customers' reservations
+            ("refuse_overselling", "1"),
+            // Loyalty points per peso spent, before the tier multiplier
+            ("loyalty_points_rate", "0.1"),
+            // Roles (comma separated) that close shifts without seeing the expected cash
+            ("blind_close_roles", ""),
+            // Shift differences larger than this wait for a manager's review
+            ("cash_review_threshold", "100"),
+        ];
+
+        for (key, value) in settings {
+            sqlx::query("INSERT OR IGNORE INTO settings (key, value, updated_at) VALUES (?, ?, datetime('now'))")
+                .bind(key)
+                .bind(value)
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn insert_default_loyalty_tiers(&self) -> Result<()> {
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM loyalty_tiers")
+            .fetch_one(&self.pool)
+            .await?;
+        if count > 0 {
+            return Ok(());
+        }
+
+        // Every customer starts in Bronce; the others are earned by annual spend or points
+        let tiers = vec![
+            ("Bronce", 1, None, None, 1.0, 0.0),
+            ("Plata", 2, Some(10000.0), Some(1000), 1.25, 3.0),
+            ("Oro", 3, Some(30000.0), Some(3000), 1.5, 5.0),
+        ];
+
+        for (name, rank, min_annual_spend, min_annual_points, points_multiplier, discount_percent) in tiers {
+            sqlx::query(
+                r#"
+                INSERT INTO loyalty_tiers (id, name, rank, min_annual_spend, min_annual_points, points_multiplier, discount_percent, created_at, updated_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?, datetime('now'), datetime('now'))
+                "#
+            )
+            .bind(uuid::Uuid::new_v4().to_string())
+            .bind(name)
+            .bind(rank)
+            .bind(min_annual_spend)
+            .bind(min_annual_points)
+            .bind(points_multiplier)
+            .bind(discount_percent)
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn insert_default_denominations(&self) -> Result<()> {
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM currency_denominations WHERE currency = 'MXN'")
+            .fetch_one(&self.pool)
+            .await?;
+        if count > 0 {
+            return Ok(());
+        }
+
+        // Banco de México bills and coins in circulation
+        let denominations = vec![
+            ("bill", 1000.0), ("bill", 500.0), ("bill", 200.0), ("bill", 100.0), ("bill", 50.0), ("bill", 20.0),
+            ("coin", 20.0), ("coin", 10.0), ("coin", 5.0), ("coin", 2.0), ("coin", 1.0), ("coin", 0.5),
+        ];
+
+        for (kind, value) in denominations {
+            sqlx::query("INSERT OR IGNORE INTO currency_denominations (currency, kind, value) VALUES ('MXN', ?, ?)")
+                .bind(kind)
+                .bind(value)
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn insert_default_scale_layouts(&self) -> Result<()> {
+        // 2P PPPP VVVVV C: 20-24 carry the weight in grams, 25-29 the price in cents
+        for prefix in 20..30 {
+            let (value_type, decimals) = if prefix < 25 { ("weight", 3) } else { ("price", 2) };
+            sqlx::query(
+                r#"
+                INSERT OR IGNORE INTO scale_barcode_layouts
+                    (prefix, plu_start, plu_length, value_type, value_start, value_length, decimals, is_active, updated_at)
+                VALUES (?, 2, 5, ?, 7, 5, ?, 1, datetime('now'))
+                "#
+            )
+            .bind(prefix.to_string())
+            .bind(value_type)
+            .bind(decimals)
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
     async fn insert_sample_data(&self) -> Result<()> {
         // Insertar categorías de ejemplo
         let categories = vec![
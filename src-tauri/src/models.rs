@@ -22,14 +22,14 @@ pub struct Product {
     pub category_id: Option<String>,
     pub price: f64,
     pub cost: f64,
-    pub stock: i32,
-    pub min_stock: i32,
+    pub stock: f64,
+    pub min_stock: f64,
     pub unit: String,
     pub image_url: Option<String>,
     pub is_active: bool,
 }
 
-/* #[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Sale {
     pub id: String,
     pub sale_number: String,
@@ -42,7 +42,12 @@ pub struct Sale {
     pub total: f64,
     pub status: String,
     pub payment_status: String,
-} */
+    /// Customer tier whose benefits the sale got.
+    pub loyalty_tier: Option<String>,
+    pub loyalty_discount: f64,
+    pub points_earned: i64,
+    pub change_given: f64,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ApiResponse<T> {
@@ -50,3 +55,29 @@ pub struct ApiResponse<T> {
     pub data: Option<T>,
     pub message: Option<String>,
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+/// An `ApiResponse` that failed validation, with one entry per offending field.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ValidationErrorResponse {
+    pub success: bool,
+    pub data: Option<()>,
+    pub message: Option<String>,
+    pub errors: Vec<FieldError>,
+}
+
+impl ValidationErrorResponse {
+    pub fn new(errors: Vec<FieldError>) -> Self {
+        ValidationErrorResponse {
+            success: false,
+            data: None,
+            message: Some("Revisa los datos marcados".to_string()),
+            errors,
+        }
+    }
+}
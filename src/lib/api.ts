@@ -1,9 +1,16 @@
 const API_BASE_URL = "http://127.0.0.1:3030/api"
 
+export interface FieldError {
+  field: string
+  message: string
+}
+
 export interface ApiResponse<T> {
   success: boolean
   data?: T
   message?: string
+  /** Present when the request failed validation. */
+  errors?: FieldError[]
 }
 
 export interface User {
@@ -64,6 +71,10 @@ export interface Sale {
   total: number
   status: string
   payment_status: string
+  loyalty_tier?: string
+  loyalty_discount: number
+  points_earned: number
+  change_given: number
 }
 
 export interface CashRegister {
@@ -71,6 +82,7 @@ export interface CashRegister {
   name: string
   location?: string
   is_active: boolean
+  blind_close: boolean
 }
 
 export interface Shift {
@@ -86,16 +98,75 @@ export interface Shift {
   expected_balance?: number
   difference?: number
   notes?: string
-  status: string
+  status: "open" | "pending_review" | "closed"
+}
+
+export interface ShiftReview {
+  reviewed_by: string
+  reviewer_name: string
+  reviewed_at: string
+  notes: string
 }
 
+// Expected figures are null when a non-manager views a blind-closed shift
 export interface ShiftSummary {
   shift: Shift
-  total_sales: number
+  total_sales: number | null
   total_transactions: number
-  cash_sales: number
-  card_sales: number
-  other_sales: number
+  cash_sales: number | null
+  card_sales: number | null
+  other_sales: number | null
+  cash: ExpectedCash | null
+  tenders: TenderReconciliation[]
+  opening_count?: CashCount
+  closing_count?: CashCount
+  blind_close: boolean
+  review: ShiftReview | null
+}
+
+export interface DenominationCount {
+  kind: "bill" | "coin"
+  value: number
+  count: number
+}
+
+export interface CashCount {
+  currency: string
+  denominations: DenominationCount[]
+  total: number
+}
+
+export interface ExpectedCash {
+  opening_balance: number
+  cash_payments: number
+  cash_refunds: number
+  change_given: number
+  pay_ins: number
+  pay_outs: number
+  safe_drops: number
+  expected: number
+}
+
+export interface TenderReconciliation {
+  method: string
+  expected: number | null
+  declared?: number
+  difference?: number
+}
+
+export type CashMovementType = "pay_in" | "pay_out" | "safe_drop"
+
+export interface CashMovement {
+  id: string
+  shift_id: string
+  movement_type: CashMovementType
+  amount: number
+  reason: string
+  user_id: string
+  user_name: string
+  authorized_by: string
+  authorized_by_name: string
+  created_at: string
 }
 
 export interface Category {
@@ -107,6 +178,11 @@ export interface Category {
   is_active: boolean
 }
 
+export interface AdjustmentReason {
+  code: string
+  name: string
+}
+
 export interface InventoryMovement {
   id: string
   product_id: string
@@ -187,14 +263,38 @@ export interface Customer {
   city?: string
   state?: string
   postal_code?: string
+  business_name?: string
+  tax_regime?: string
+  cfdi_use?: string
+  birth_date?: string
   credit_limit: number
   current_balance: number
   loyalty_points: number
+  loyalty_tier?: string
   notes?: string
   is_active: boolean
   created_at: string
 }
 
+export interface CustomerPage {
+  customers: Customer[]
+  total: number
+  limit: number
+  offset: number
+}
+
+export interface CustomerListParams {
+  q?: string
+  is_active?: boolean
+  has_balance?: boolean
+  over_credit_limit?: boolean
+  loyalty_tier?: string
+  sort?: "name" | "created_at" | "balance" | "loyalty_points" | "relevance"
+  order?: "asc" | "desc"
+  limit?: number
+  offset?: number
+}
+
 export interface CustomerPurchase {
   sale_id: string
   sale_number: string
@@ -244,8 +344,12 @@ export const api = {
     },
   },
   customers: {
-    list: async (): Promise<ApiResponse<Customer[]>> => {
-      const response = await fetch(`${API_BASE_URL}/customers`)
+    list: async (filters: CustomerListParams = {}): Promise<ApiResponse<CustomerPage>> => {
+      const params = new URLSearchParams()
+      for (const [key, value] of Object.entries(filters)) {
+        if (value !== undefined && value !== "") params.append(key, String(value))
+      }
+      const response = await fetch(`${API_BASE_URL}/customers?${params}`)
       return response.json()
     },
     get: async (id: string): Promise<ApiResponse<Customer>> => {
@@ -296,15 +400,35 @@ export const api = {
       const response = await fetch(`${API_BASE_URL}/cash-registers`)
       return response.json()
     },
-    listShifts: async (): Promise<ApiResponse<Shift[]>> => {
-      const response = await fetch(`${API_BASE_URL}/shifts`)
+    listShifts: async (userId?: string): Promise<ApiResponse<Shift[]>> => {
+      const query = userId ? `?user_id=${userId}` : ""
+      const response = await fetch(`${API_BASE_URL}/shifts${query}`)
+      return response.json()
+    },
+    getShiftSummary: async (shiftId: string, userId?: string): Promise<ApiResponse<ShiftSummary>> => {
+      const query = userId ? `?user_id=${userId}` : ""
+      const response = await fetch(`${API_BASE_URL}/shifts/${shiftId}/summary${query}`)
+      return response.json()
+    },
+    reviewShift: async (shiftId: string, data: { user_id: string; notes: string }): Promise<ApiResponse<ShiftSummary>> => {
+      const response = await fetch(`${API_BASE_URL}/shifts/${shiftId}/review`, {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify(data),
+      })
       return response.json()
     },
     getCurrentShift: async (userId: string): Promise<ApiResponse<Shift>> => {
       const response = await fetch(`${API_BASE_URL}/shifts/current/${userId}`)
       return response.json()
     },
-    openShift: async (data: { user_id: string; register_id: string; opening_balance: number }): Promise<
+    openShift: async (data: {
+      user_id: string
+      register_id: string
+      opening_balance: number
+      denominations?: DenominationCount[]
+      currency?: string
+    }): Promise<
       ApiResponse<Shift>
     > => {
       const response = await fetch(`${API_BASE_URL}/shifts/open`, {
@@ -316,7 +440,14 @@ export const api = {
     },
     closeShift: async (
       shiftId: string,
-      data: { closing_balance: number; notes?: string },
+      data: {
+        closing_balance: number
+        notes?: string
+        user_id?: string
+        tender_totals?: { method: string; amount: number }[]
+        denominations?: DenominationCount[]
+        currency?: string
+      },
     ): Promise<ApiResponse<ShiftSummary>> => {
       const response = await fetch(`${API_BASE_URL}/shifts/${shiftId}/close`, {
         method: "POST",
@@ -325,6 +456,21 @@ export const api = {
       })
       return response.json()
     },
+    listCashMovements: async (shiftId: string): Promise<ApiResponse<CashMovement[]>> => {
+      const response = await fetch(`${API_BASE_URL}/shifts/${shiftId}/cash-movements`)
+      return response.json()
+    },
+    createCashMovement: async (
+      shiftId: string,
+      data: { movement_type: CashMovementType; amount: number; reason: string; user_id: string; authorized_by: string },
+    ): Promise<ApiResponse<CashMovement>> => {
+      const response = await fetch(`${API_BASE_URL}/shifts/${shiftId}/cash-movements`, {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify(data),
+      })
+      return response.json()
+    },
   },
   inventory: {
     listProducts: async (): Promise<ApiResponse<ProductWithCategory[]>> => {
@@ -360,7 +506,9 @@ export const api = {
     adjustStock: async (data: {
       product_id: string
       quantity: number
-      adjustment_type: string
+      adjustment_type: "in" | "out"
+      /** Required for "out"; one of listAdjustmentReasons. */
+      reason_code?: string
       notes?: string
       user_id: string
     }): Promise<ApiResponse<string>> => {
@@ -371,6 +519,10 @@ export const api = {
       })
       return response.json()
     },
+    listAdjustmentReasons: async (): Promise<ApiResponse<AdjustmentReason[]>> => {
+      const response = await fetch(`${API_BASE_URL}/inventory/adjustment-reasons`)
+      return response.json()
+    },
     listMovements: async (): Promise<ApiResponse<InventoryMovement[]>> => {
       const response = await fetch(`${API_BASE_URL}/inventory/movements`)
       return response.json()
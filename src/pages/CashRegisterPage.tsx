@@ -52,8 +52,8 @@ export default function CashRegisterPage() {
 
   // Fetch shift history
   const { data: shiftsResponse } = useQuery({
-    queryKey: ["shifts"],
-    queryFn: () => api.cashRegister.listShifts(),
+    queryKey: ["shifts", user?.id],
+    queryFn: () => api.cashRegister.listShifts(user?.id),
   })
 
   // Open shift mutation
@@ -77,11 +77,21 @@ export default function CashRegisterPage() {
   // Close shift mutation
   const closeShiftMutation = useMutation({
     mutationFn: (data: { shiftId: string; closing_balance: number; notes?: string }) =>
-      api.cashRegister.closeShift(data.shiftId, { closing_balance: data.closing_balance, notes: data.notes }),
+      api.cashRegister.closeShift(data.shiftId, {
+        closing_balance: data.closing_balance,
+        notes: data.notes,
+        user_id: user?.id,
+      }),
     onSuccess: (response) => {
       if (response.success) {
         clearCurrentShift()
-        toast.success(`Turno cerrado - Diferencia: ${formatCurrency(response.data?.shift.difference || 0)}`)
+        const difference = response.data?.shift.difference
+        // Blind closes don't reveal the difference to the cashier
+        toast.success(
+          difference != null
+            ? `${response.message} - Diferencia: ${formatCurrency(difference)}`
+            : response.message || "Turno cerrado",
+        )
         setCloseShiftDialog(false)
         setClosingBalance("")
         setCloseNotes("")
@@ -305,6 +315,11 @@ export default function CashRegisterPage() {
                             <Clock className="w-4 h-4" />
                             <span className="text-sm font-medium">Abierto</span>
                           </div>
+                        ) : shift.status === "pending_review" ? (
+                          <div className="flex items-center gap-2 text-destructive">
+                            <Clock className="w-4 h-4" />
+                            <span className="text-sm font-medium">Pendiente de revisión</span>
+                          </div>
                         ) : (
                           <div className="flex items-center gap-2 text-muted-foreground">
                             <CheckCircle className="w-4 h-4" />
@@ -327,13 +342,13 @@ export default function CashRegisterPage() {
                           <p className="text-muted-foreground">Saldo Inicial</p>
                           <p className="font-medium">{formatCurrency(shift.opening_balance)}</p>
                         </div>
-                        {shift.closing_balance !== undefined && (
+                        {shift.closing_balance != null && (
                           <div>
                             <p className="text-muted-foreground">Saldo Final</p>
                             <p className="font-medium">{formatCurrency(shift.closing_balance)}</p>
                           </div>
                         )}
-                        {shift.difference !== undefined && (
+                        {shift.difference != null && (
                           <div className="col-span-2">
                             <p className="text-muted-foreground">Diferencia</p>
                             <div className="flex items-center gap-2">
@@ -20,13 +20,16 @@ import {
 import { Table, TableBody, TableCell, TableHead, TableHeader, TableRow } from "@/components/ui/table"
 import { Textarea } from "@/components/ui/textarea"
 import { Badge } from "@/components/ui/badge"
-import { Search, Plus, User, ShoppingBag, Gift, Mail, Phone, MapPin } from "lucide-react"
+import { Search, Plus, User, ShoppingBag, Gift, Mail, Phone, MapPin, ChevronLeft, ChevronRight } from "lucide-react"
 import { toast } from "sonner"
 
+const PAGE_SIZE = 50
+
 export default function CustomersPage() {
   const queryClient = useQueryClient()
 
   const [searchTerm, setSearchTerm] = useState("")
+  const [page, setPage] = useState(0)
   const [addCustomerDialog, setAddCustomerDialog] = useState(false)
   const [viewCustomerDialog, setViewCustomerDialog] = useState(false)
   const [selectedCustomer, setSelectedCustomer] = useState<Customer | null>(null)
@@ -46,8 +49,9 @@ export default function CustomersPage() {
 
   // Fetch customers
   const { data: customersResponse } = useQuery({
-    queryKey: ["customers"],
-    queryFn: () => api.customers.list(),
+    queryKey: ["customers", searchTerm, page],
+    queryFn: () =>
+      api.customers.list({ q: searchTerm.trim() || undefined, limit: PAGE_SIZE, offset: page * PAGE_SIZE }),
   })
 
   // Fetch customer details when selected
@@ -63,17 +67,12 @@ export default function CustomersPage() {
     enabled: !!selectedCustomer,
   })
 
-  const customers = customersResponse?.data || []
+  const filteredCustomers = customersResponse?.data?.customers || []
+  const totalCustomers = customersResponse?.data?.total ?? 0
+  const pageCount = Math.max(1, Math.ceil(totalCustomers / PAGE_SIZE))
   const customerStats = customerStatsResponse?.data
   const customerPurchases = customerPurchasesResponse?.data || []
 
-  const filteredCustomers = customers.filter(
-    (c) =>
-      c.name.toLowerCase().includes(searchTerm.toLowerCase()) ||
-      c.email?.toLowerCase().includes(searchTerm.toLowerCase()) ||
-      c.phone?.toLowerCase().includes(searchTerm.toLowerCase()) ||
-      c.rfc?.toLowerCase().includes(searchTerm.toLowerCase()),
-  )
 
   // Create customer mutation
   const createCustomerMutation = useMutation({
@@ -277,13 +276,16 @@ export default function CustomersPage() {
           placeholder="Buscar por nombre, email, teléfono o RFC..."
           className="pl-10"
           value={searchTerm}
-          onChange={(e) => setSearchTerm(e.target.value)}
+          onChange={(e) => {
+            setSearchTerm(e.target.value)
+            setPage(0)
+          }}
         />
       </div>
 
       <Card>
         <CardHeader>
-          <CardTitle>Lista de Clientes ({filteredCustomers.length})</CardTitle>
+          <CardTitle>Lista de Clientes ({totalCustomers})</CardTitle>
         </CardHeader>
         <CardContent>
           <Table>
@@ -349,6 +351,19 @@ export default function CustomersPage() {
               ))}
             </TableBody>
           </Table>
+          {pageCount > 1 && (
+            <div className="flex items-center justify-end gap-2 pt-4">
+              <span className="text-sm text-muted-foreground">
+                Página {page + 1} de {pageCount}
+              </span>
+              <Button size="sm" variant="outline" disabled={page === 0} onClick={() => setPage(page - 1)}>
+                <ChevronLeft className="w-4 h-4" />
+              </Button>
+              <Button size="sm" variant="outline" disabled={page + 1 >= pageCount} onClick={() => setPage(page + 1)}>
+                <ChevronRight className="w-4 h-4" />
+              </Button>
+            </div>
+          )}
         </CardContent>
       </Card>
 
@@ -49,13 +49,14 @@ export default function InventoryPage() {
     stock: "",
     min_stock: "",
     max_stock: "",
-    unit: "pz",
+    unit: "pza",
   })
 
   // Form states for stock adjustment
   const [stockAdjustment, setStockAdjustment] = useState({
     quantity: "",
     type: "in",
+    reason_code: "",
     notes: "",
   })
 
@@ -90,10 +91,17 @@ export default function InventoryPage() {
     queryFn: () => api.inventory.listMovements(),
   })
 
+  // Fetch reasons for outgoing adjustments
+  const { data: reasonsResponse } = useQuery({
+    queryKey: ["adjustment-reasons"],
+    queryFn: () => api.inventory.listAdjustmentReasons(),
+  })
+
   const products = productsResponse?.data || []
   const categories = categoriesResponse?.data || []
   const lowStockProducts = lowStockResponse?.data || []
   const movements = movementsResponse?.data || []
+  const adjustmentReasons = reasonsResponse?.data || []
 
   const filteredProducts = products.filter(
     (p) =>
@@ -123,7 +131,7 @@ export default function InventoryPage() {
           stock: "",
           min_stock: "",
           max_stock: "",
-          unit: "pz",
+          unit: "pza",
         })
         queryClient.invalidateQueries({ queryKey: ["inventory-products"] })
         queryClient.invalidateQueries({ queryKey: ["low-stock"] })
@@ -146,10 +154,14 @@ export default function InventoryPage() {
           duration: 3000,
         })
         setAdjustStockDialog(false)
-        setStockAdjustment({ quantity: "", type: "in", notes: "" })
+        setStockAdjustment({ quantity: "", type: "in", reason_code: "", notes: "" })
         queryClient.invalidateQueries({ queryKey: ["inventory-products"] })
         queryClient.invalidateQueries({ queryKey: ["inventory-movements"] })
         queryClient.invalidateQueries({ queryKey: ["low-stock"] })
+      } else {
+        toast.error("Error al ajustar stock", {
+          description: response.message || "No se pudo ajustar el stock",
+        })
       }
     },
     onError: (error: any) => {
@@ -229,6 +241,12 @@ export default function InventoryPage() {
       })
       return
     }
+    if (stockAdjustment.type === "out" && !stockAdjustment.reason_code) {
+      toast.error("Motivo requerido", {
+        description: "Selecciona el motivo de la salida de inventario",
+      })
+      return
+    }
 
     const quantity = Number.parseInt(stockAdjustment.quantity)
     const newStock = stockAdjustment.type === "in" 
@@ -249,6 +267,7 @@ export default function InventoryPage() {
         product_id: selectedProduct.id,
         quantity,
         adjustment_type: stockAdjustment.type,
+        reason_code: stockAdjustment.type === "out" ? stockAdjustment.reason_code : undefined,
         notes: stockAdjustment.notes || undefined,
         user_id: user.id,
       },
@@ -294,7 +313,7 @@ export default function InventoryPage() {
       stock: "",
       min_stock: "",
       max_stock: "",
-      unit: "pz",
+      unit: "pza",
     })
   }
 
@@ -468,10 +487,10 @@ export default function InventoryPage() {
                         <SelectValue />
                       </SelectTrigger>
                       <SelectContent>
-                        <SelectItem value="pz">Pieza</SelectItem>
+                        <SelectItem value="pza">Pieza</SelectItem>
                         <SelectItem value="kg">Kilogramo</SelectItem>
-                        <SelectItem value="lt">Litro</SelectItem>
-                        <SelectItem value="mt">Metro</SelectItem>
+                        <SelectItem value="L">Litro</SelectItem>
+                        <SelectItem value="m">Metro</SelectItem>
                         <SelectItem value="paq">Paquete</SelectItem>
                       </SelectContent>
                     </Select>
@@ -691,7 +710,10 @@ export default function InventoryPage() {
                         <TableCell className="font-medium">{movement.product_name}</TableCell>
                         <TableCell>
                           <div className="flex items-center gap-2">
-                            {movement.movement_type.includes("in") ? (
+                            {/* Counts and imports store a signed quantity */}
+                            {(["count", "import"].includes(movement.movement_type)
+                              ? movement.quantity > 0
+                              : movement.movement_type.includes("in")) ? (
                               <TrendingUp className="w-4 h-4 text-green-600" />
                             ) : (
                               <TrendingDown className="w-4 h-4 text-red-600" />
@@ -800,6 +822,26 @@ export default function InventoryPage() {
                 </SelectContent>
               </Select>
             </div>
+            {stockAdjustment.type === "out" && (
+              <div className="space-y-2">
+                <Label htmlFor="adjustment-reason">Motivo *</Label>
+                <Select
+                  value={stockAdjustment.reason_code}
+                  onValueChange={(v) => setStockAdjustment({ ...stockAdjustment, reason_code: v })}
+                >
+                  <SelectTrigger id="adjustment-reason">
+                    <SelectValue placeholder="Seleccionar motivo..." />
+                  </SelectTrigger>
+                  <SelectContent>
+                    {adjustmentReasons.map((reason) => (
+                      <SelectItem key={reason.code} value={reason.code}>
+                        {reason.name}
+                      </SelectItem>
+                    ))}
+                  </SelectContent>
+                </Select>
+              </div>
+            )}
             <div className="space-y-2">
               <Label htmlFor="quantity">Cantidad *</Label>
               <Input
@@ -828,7 +870,7 @@ export default function InventoryPage() {
                 id="adj-notes"
                 value={stockAdjustment.notes}
                 onChange={(e) => setStockAdjustment({ ...stockAdjustment, notes: e.target.value })}
-                placeholder="Motivo del ajuste (recomendado)..."
+                placeholder="Detalles del ajuste (opcional)..."
                 rows={3}
               />
             </div>